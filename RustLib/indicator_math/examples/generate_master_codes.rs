@@ -0,0 +1,32 @@
+//! Regenerate the shared master-code table so every valid descriptor has a code.
+//!
+//! cargo run --example generate_master_codes -- ../../indicator_math/candle_master_codes.json
+
+use indicator_math::master_codes::generate_complete_table;
+use indicator_math::MasterCodeTable;
+use std::path::PathBuf;
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("../../indicator_math/candle_master_codes.json"));
+
+    let current = MasterCodeTable::load(&path)?;
+    let missing = current.missing_descriptors();
+    if missing.is_empty() {
+        println!("{:?}: all {} descriptors mapped (version {})", path, current.codes.len(), current.version);
+        return Ok(());
+    }
+
+    let table = generate_complete_table(&current);
+    std::fs::write(&path, table.to_pretty_json())?;
+    println!(
+        "{:?}: added {} descriptors, version {} -> {}",
+        path,
+        missing.len(),
+        current.version,
+        table.version
+    );
+    Ok(())
+}
@@ -5,7 +5,12 @@ use serde_json::{json, Value};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use url::Url;
 
-pub async fn fetch_candles(ws_url: &str, symbol: &str, count: usize) -> Result<Vec<Candle>> {
+pub async fn fetch_candles(
+    ws_url: &str,
+    symbol: &str,
+    count: usize,
+    granularity: u64,
+) -> Result<Vec<Candle>> {
     let url = Url::parse(ws_url)?;
     let (ws_stream, _) = connect_async(url).await?;
     let (mut write, mut read) = ws_stream.split();
@@ -17,7 +22,7 @@ pub async fn fetch_candles(ws_url: &str, symbol: &str, count: usize) -> Result<V
         "end": "latest",
         "start": 1,
         "style": "candles",
-        "granularity": 60 
+        "granularity": granularity
     });
 
     write.send(Message::Text(req.to_string())).await?;
@@ -25,42 +30,145 @@ pub async fn fetch_candles(ws_url: &str, symbol: &str, count: usize) -> Result<V
     // Simple robust loop: look for response matching symbol or just first candles response
     while let Some(msg) = read.next().await {
         let msg = msg?;
-        match msg {
-            Message::Text(text) => {
-                let v: Value = serde_json::from_str(&text)?;
-                
-                if let Some(error) = v.get("error") {
-                    return Err(anyhow::anyhow!("Deriv API Error: {:?}", error));
-                }
+        if let Message::Text(text) = msg {
+            let v: Value = serde_json::from_str(&text)?;
+            
+            if let Some(error) = v.get("error") {
+                return Err(anyhow::anyhow!("Deriv API Error: {:?}", error));
+            }
 
-                if let Some(msg_type) = v.get("msg_type") {
-                    if msg_type == "candles" {
-                        if let Some(candles) = v.get("candles") {
-                            // Map manually if structure differs slightly or use serde
-                            // Deriv candle: { epoch, open, high, low, close }
-                            // Our Candle: { time, open, high, low, close }
-                            // We need to map 'epoch' to 'time'
-                            
-                            if let Some(list) = candles.as_array() {
-                                let mut result = Vec::new();
-                                for c in list {
-                                    let time = c.get("epoch").and_then(|v| v.as_u64()).unwrap_or(0);
-                                    let open = c.get("open").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                    let high = c.get("high").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                    let low = c.get("low").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                    let close = c.get("close").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                    
-                                    result.push(Candle { time, open, high, low, close });
-                                }
-                                return Ok(result);
+            if let Some(msg_type) = v.get("msg_type") {
+                if msg_type == "candles" {
+                    if let Some(candles) = v.get("candles") {
+                        // Map manually if structure differs slightly or use serde
+                        // Deriv candle: { epoch, open, high, low, close }
+                        // Our Candle: { time, open, high, low, close }
+                        // We need to map 'epoch' to 'time'
+                        
+                        if let Some(list) = candles.as_array() {
+                            let mut result = Vec::new();
+                            for c in list {
+                                let time = c.get("epoch").and_then(|v| v.as_u64()).unwrap_or(0);
+                                let open = c.get("open").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                                let high = c.get("high").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                                let low = c.get("low").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                                let close = c.get("close").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                                
+                                result.push(Candle { time, open, high, low, close });
                             }
+                            return Ok(result);
                         }
                     }
                 }
-            },
-            _ => {}
+            }
         }
     }
     
     Err(anyhow::anyhow!("Connection closed without data"))
 }
+
+/// Live data source for `AnalysisManager::run_live`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveFeed {
+    /// `ticks` subscription; ticks are bucketed into candles by the generator
+    Ticks,
+    /// `ticks_history` candles subscription; streams the forming candle as `ohlc`
+    Ohlc,
+}
+
+/// Subscription request for one symbol on the given feed.
+pub fn subscription_request(feed: LiveFeed, symbol: &str, granularity: u64) -> Value {
+    match feed {
+        LiveFeed::Ticks => json!({
+            "ticks": symbol.to_uppercase(),
+            "subscribe": 1
+        }),
+        LiveFeed::Ohlc => json!({
+            "ticks_history": symbol.to_uppercase(),
+            "adjust_start_time": 1,
+            "count": 1,
+            "end": "latest",
+            "start": 1,
+            "style": "candles",
+            "granularity": granularity,
+            "subscribe": 1
+        }),
+    }
+}
+
+/// Parse a `tick` or `ohlc` message into (symbol, candle).
+/// A tick becomes a flat candle at its epoch; an ohlc update becomes the forming candle at its `open_time`.
+pub fn parse_live_message(v: &Value) -> Option<(String, Candle)> {
+    let num = |o: &Value, key: &str| {
+        o.get(key)
+            .and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()))
+    };
+
+    if let Some(tick) = v.get("tick") {
+        let symbol = tick.get("symbol")?.as_str()?.to_string();
+        let price = num(tick, "quote")?;
+        let time = tick.get("epoch")?.as_u64()?;
+        return Some((
+            symbol,
+            Candle {
+                time,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+            },
+        ));
+    }
+
+    if let Some(ohlc) = v.get("ohlc") {
+        let symbol = ohlc.get("symbol")?.as_str()?.to_string();
+        let time = ohlc
+            .get("open_time")
+            .or_else(|| ohlc.get("epoch"))?
+            .as_u64()?;
+        return Some((
+            symbol,
+            Candle {
+                time,
+                open: num(ohlc, "open")?,
+                high: num(ohlc, "high")?,
+                low: num(ohlc, "low")?,
+                close: num(ohlc, "close")?,
+            },
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tick_message() {
+        let v: Value = serde_json::from_str(
+            r#"{"msg_type":"tick","tick":{"symbol":"R_10","quote":6123.45,"epoch":1700000061}}"#,
+        )
+        .unwrap();
+        let (symbol, c) = parse_live_message(&v).unwrap();
+        assert_eq!(symbol, "R_10");
+        assert_eq!(c.time, 1700000061);
+        assert_eq!(c.close, 6123.45);
+        assert_eq!(c.high, c.low);
+    }
+
+    #[test]
+    fn test_parse_ohlc_message_uses_open_time() {
+        let v: Value = serde_json::from_str(
+            r#"{"msg_type":"ohlc","ohlc":{"symbol":"R_75","open_time":1700000040,"epoch":1700000055,
+                "open":"10.5","high":"11.0","low":"10.1","close":"10.8","granularity":60}}"#,
+        )
+        .unwrap();
+        let (symbol, c) = parse_live_message(&v).unwrap();
+        assert_eq!(symbol, "R_75");
+        assert_eq!(c.time, 1700000040);
+        assert_eq!((c.open, c.high, c.low, c.close), (10.5, 11.0, 10.1, 10.8));
+        assert!(parse_live_message(&serde_json::json!({"msg_type": "ping"})).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Price/oscillator divergence on confirmed swing points.
+// A swing high (low) is a candle whose high (low) is beyond the `strength` candles on
+// either side, so a swing is only known `strength` candles after it happened. Each new
+// swing is compared with the previous swing of the same side:
+//
+//   Regular bearish: price higher high, oscillator lower high
+//   Hidden bearish:  price lower high,  oscillator higher high
+//   Regular bullish: price lower low,   oscillator higher low
+//   Hidden bullish:  price higher low,  oscillator lower low
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// "Regular" or "Hidden"
+    pub kind: String,
+    /// "Bullish" or "Bearish"
+    pub direction: String,
+    /// "RSI" or "MACD"
+    pub oscillator: String,
+    /// Candle indices (`AnalysisResult::index`) of the earlier and later swing
+    pub from_index: usize,
+    pub to_index: usize,
+    pub from_price: f64,
+    pub to_price: f64,
+    pub from_value: f64,
+    pub to_value: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SwingCandle {
+    pub index: usize,
+    pub high: f64,
+    pub low: f64,
+    pub rsi: Option<f64>,
+    pub macd: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DivergenceDetector {
+    pub strength: usize,
+    pub max_distance: usize,
+    pub window: VecDeque<SwingCandle>,
+    pub last_high: Option<SwingCandle>,
+    pub last_low: Option<SwingCandle>,
+}
+
+impl DivergenceDetector {
+    pub fn new(strength: usize, max_distance: usize) -> Self {
+        let strength = strength.max(1);
+        Self {
+            strength,
+            max_distance,
+            window: VecDeque::with_capacity(2 * strength + 2),
+            last_high: None,
+            last_low: None,
+        }
+    }
+
+    /// Feed one closed candle. Returns divergences confirmed by it (they refer to a
+    /// swing `strength` candles back).
+    pub fn update(&mut self, candle: SwingCandle) -> Vec<Divergence> {
+        self.window.push_back(candle);
+        if self.window.len() > 2 * self.strength + 1 {
+            self.window.pop_front();
+        }
+        if self.window.len() < 2 * self.strength + 1 {
+            return Vec::new();
+        }
+
+        let mid = self.window[self.strength];
+        let (left, right) = (
+            self.window.range(..self.strength),
+            self.window.range(self.strength + 1..),
+        );
+        let is_high =
+            left.clone().all(|c| c.high < mid.high) && right.clone().all(|c| c.high <= mid.high);
+        let is_low =
+            left.clone().all(|c| c.low > mid.low) && right.clone().all(|c| c.low >= mid.low);
+
+        let mut found = Vec::new();
+        if is_high {
+            if let Some(prev) = self
+                .last_high
+                .filter(|p| mid.index - p.index <= self.max_distance)
+            {
+                compare(&prev, &mid, true, &mut found);
+            }
+            self.last_high = Some(mid);
+        }
+        if is_low {
+            if let Some(prev) = self
+                .last_low
+                .filter(|p| mid.index - p.index <= self.max_distance)
+            {
+                compare(&prev, &mid, false, &mut found);
+            }
+            self.last_low = Some(mid);
+        }
+        found
+    }
+}
+
+fn compare(prev: &SwingCandle, cur: &SwingCandle, highs: bool, out: &mut Vec<Divergence>) {
+    let (p_price, c_price) = if highs {
+        (prev.high, cur.high)
+    } else {
+        (prev.low, cur.low)
+    };
+    for (name, p_val, c_val) in [("RSI", prev.rsi, cur.rsi), ("MACD", prev.macd, cur.macd)] {
+        let (Some(p_val), Some(c_val)) = (p_val, c_val) else {
+            continue;
+        };
+        let price_up = c_price > p_price;
+        let price_down = c_price < p_price;
+        let osc_up = c_val > p_val;
+        let osc_down = c_val < p_val;
+        let kind = match (highs, price_up, price_down, osc_up, osc_down) {
+            (true, true, _, _, true) | (false, _, true, true, _) => "Regular",
+            (true, _, true, true, _) | (false, true, _, _, true) => "Hidden",
+            _ => continue,
+        };
+        out.push(Divergence {
+            kind: kind.to_string(),
+            direction: if highs { "Bearish" } else { "Bullish" }.to_string(),
+            oscillator: name.to_string(),
+            from_index: prev.index,
+            to_index: cur.index,
+            from_price: p_price,
+            to_price: c_price,
+            from_value: p_val,
+            to_value: c_val,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Zig-zag of (price, rsi) points; `highs` picks which side of the candle the price is
+    fn feed(points: &[(f64, f64)], highs: bool) -> Vec<Divergence> {
+        let mut d = DivergenceDetector::new(2, 50);
+        let mut out = Vec::new();
+        for (i, &(price, rsi)) in points.iter().enumerate() {
+            let (high, low) = if highs {
+                (price, price - 1.0)
+            } else {
+                (price + 1.0, price)
+            };
+            out.extend(d.update(SwingCandle {
+                index: i,
+                high,
+                low,
+                rsi: Some(rsi),
+                macd: None,
+            }));
+        }
+        out
+    }
+
+    #[test]
+    fn test_regular_bearish_and_hidden_bullish() {
+        // Higher high in price at 7 vs 2, lower RSI
+        let pts = [
+            (1.0, 50.0),
+            (2.0, 60.0),
+            (5.0, 70.0),
+            (3.0, 55.0),
+            (2.0, 50.0),
+            (3.0, 52.0),
+            (4.0, 55.0),
+            (6.0, 65.0),
+            (4.0, 50.0),
+            (3.0, 45.0),
+        ];
+        let found = feed(&pts, true);
+        assert_eq!(found.len(), 1);
+        let d = &found[0];
+        assert_eq!(
+            (d.kind.as_str(), d.direction.as_str()),
+            ("Regular", "Bearish")
+        );
+        assert_eq!((d.from_index, d.to_index), (2, 7));
+        assert_eq!(d.oscillator, "RSI");
+
+        // Higher low in price, lower RSI low
+        let pts = [
+            (9.0, 50.0),
+            (8.0, 45.0),
+            (5.0, 30.0),
+            (7.0, 40.0),
+            (8.0, 50.0),
+            (7.0, 40.0),
+            (6.5, 30.0),
+            (6.0, 25.0),
+            (7.0, 40.0),
+            (8.0, 50.0),
+        ];
+        let found = feed(&pts, false);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].kind.as_str(), found[0].direction.as_str()),
+            ("Hidden", "Bullish")
+        );
+        assert_eq!((found[0].from_index, found[0].to_index), (2, 7));
+    }
+
+    #[test]
+    fn test_no_divergence_when_oscillator_confirms() {
+        let pts = [
+            (1.0, 50.0),
+            (2.0, 60.0),
+            (5.0, 70.0),
+            (3.0, 55.0),
+            (2.0, 50.0),
+            (3.0, 52.0),
+            (4.0, 55.0),
+            (6.0, 75.0),
+            (4.0, 50.0),
+            (3.0, 45.0),
+        ];
+        assert!(feed(&pts, true).is_empty());
+    }
+}
@@ -1,6 +1,14 @@
+use crate::divergence::{DivergenceDetector, SwingCandle};
+use crate::indicators::IndicatorState;
+use crate::master_codes;
+use crate::patterns::{self, PATTERN_LOOKBACK};
+use crate::streaming::MaState;
 use crate::structs::{AnalysisOptions, AnalysisResult, BBValues, Candle, CandleMasterCode};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GeneratorState {
@@ -18,6 +26,8 @@ pub struct GeneratorState {
     pub prev_analysis: Option<AnalysisResult>,
     pub last_analysis: Option<AnalysisResult>,
     pub last_candle: Option<Candle>,
+    /// Candles analysed so far (the next `AnalysisResult::index`), independent of retention
+    pub candle_count: usize,
 
     // RSI
     pub rsi_period: usize,
@@ -47,25 +57,23 @@ pub struct GeneratorState {
     pub ema_2_k: f64,
     pub ema_3_k: f64,
 
-    // HMA/EHMA State
-    // We need to store history for WMA/EMA calculations if we want to do incremental HMA/EHMA
-    // HMA(n) = WMA(2*WMA(n/2) - WMA(n), sqrt(n))
-    // This is complex for O(1).
-    // For now, let's implement effective buffering for these types.
-    // Ideally O(1) HMA is hard. We might need O(N) over window.
-    pub close_window: VecDeque<f64>, // For types needing window like HMA
-    pub max_ma_period: usize,
+    // MA line state (EMA uses last_ema_N/ema_N_k; HMA/EHMA carry O(1) streaming state)
+    pub ma_1: MaState,
+    pub ma_2: MaState,
+    pub ma_3: MaState,
+
+    // Stochastic, MACD, SuperTrend, PSAR, Donchian, Keltner, Williams %R
+    pub indicators: IndicatorState,
+
+    // Last closed candles for pattern detection (oldest first)
+    pub pattern_window: VecDeque<Candle>,
+
+    // Swing points for RSI/MACD divergence
+    pub divergence: DivergenceDetector,
 }
 
 impl GeneratorState {
     pub fn new(options: &AnalysisOptions) -> Self {
-        let max_period = options
-            .ema1_period
-            .max(options.ema2_period)
-            .max(options.ema3_period);
-        // HMA needs slightly more for safety or internal WMAs
-        let buffer_size = max_period * 2;
-
         Self {
             last_ema_1: 0.0,
             last_ema_2: 0.0,
@@ -81,6 +89,7 @@ impl GeneratorState {
             prev_analysis: None,
             last_analysis: None,
             last_candle: None,
+            candle_count: 0,
             atr_period: options.atr_period,
             rsi_period: options.rsi_period,
             tr_sum: 0.0,
@@ -97,13 +106,57 @@ impl GeneratorState {
             ema_1_k: 2.0 / (options.ema1_period as f64 + 1.0),
             ema_2_k: 2.0 / (options.ema2_period as f64 + 1.0),
             ema_3_k: 2.0 / (options.ema3_period as f64 + 1.0),
+            ma_1: MaState::new(&options.ema1_type, options.ema1_period),
+            ma_2: MaState::new(&options.ema2_type, options.ema2_period),
+            ma_3: MaState::new(&options.ema3_type, options.ema3_period),
+            indicators: IndicatorState::new(&options.indicators),
+            pattern_window: VecDeque::with_capacity(PATTERN_LOOKBACK + 1),
+            divergence: DivergenceDetector::new(
+                options.indicators.divergence_swing_strength,
+                options.indicators.divergence_max_distance,
+            ),
+        }
+    }
+}
+
+/// Bumped whenever `GeneratorState` or `AnalysisResult` change shape.
+pub const SNAPSHOT_VERSION: u32 = 5;
+
+/// How much closed-candle history (`analysis_array` / `candle_data`) a generator keeps in memory.
+/// Indicator state never depends on this history, only on `GeneratorState`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum RetentionPolicy {
+    #[default]
+    Unbounded,
+    /// Keep only the last N results/candles.
+    KeepLast(usize),
+    /// Keep the last `keep_last` in memory and append older results to `path` as JSON lines.
+    SpillToDisk { keep_last: usize, path: PathBuf },
+}
 
-            close_window: VecDeque::with_capacity(buffer_size),
-            max_ma_period: buffer_size,
+impl RetentionPolicy {
+    fn keep_last(&self) -> Option<usize> {
+        match self {
+            RetentionPolicy::Unbounded => None,
+            RetentionPolicy::KeepLast(n) => Some(*n),
+            RetentionPolicy::SpillToDisk { keep_last, .. } => Some(*keep_last),
         }
     }
 }
 
+/// Serializable form of an `AnalysisGenerator` (master codes are re-attached on restore).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GeneratorSnapshot {
+    pub version: u32,
+    pub options: AnalysisOptions,
+    pub state: GeneratorState,
+    pub analysis_array: Vec<AnalysisResult>,
+    pub candle_data: Vec<Candle>,
+    pub current_candle: Option<Candle>,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
 #[derive(Clone)]
 pub struct AnalysisGenerator {
     options: AnalysisOptions,
@@ -112,6 +165,7 @@ pub struct AnalysisGenerator {
     pub candle_data: Vec<Candle>,
     pub current_candle: Option<Candle>,
     pub master_codes: std::sync::Arc<Vec<CandleMasterCode>>,
+    pub retention: RetentionPolicy,
 }
 
 impl AnalysisGenerator {
@@ -127,161 +181,58 @@ impl AnalysisGenerator {
             candle_data: Vec::new(),
             current_candle: None,
             master_codes,
+            retention: RetentionPolicy::Unbounded,
         }
     }
 
-    // Helper: WMA Calculation
-    fn calculate_wma(data: &[f64], period: usize) -> f64 {
-        if data.len() < period {
-            return 0.0;
-        }
-        let mut num = 0.0;
-        let mut den = 0.0;
-        for j in 0..period {
-            let val = data[data.len() - 1 - j];
-            let w = (period - j) as f64;
-            num += val * w;
-            den += w;
-        }
-        num / den
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self.enforce_retention();
+        self
     }
 
-    // Helper: EMA Calculation on slice (simple)
-    fn calculate_ema_slice(data: &[f64], period: usize) -> f64 {
-        if data.is_empty() {
-            return 0.0;
-        }
-        let k = 2.0 / (period as f64 + 1.0);
-        let mut ema = data[0];
-        for i in 1..data.len() {
-            ema = data[i] * k + ema * (1.0 - k);
-        }
-        ema
+    pub fn options(&self) -> &AnalysisOptions {
+        &self.options
     }
 
-    // Helper: HMA Calculation
-    // HMA = WMA(2 * WMA(n/2) - WMA(n), sqrt(n))
-    fn calculate_ma(
-        &self,
-        ma_type: &str,
-        period: usize,
-        current_price: f64,
-        last_ema_state: f64,
-        ema_k: f64,
-    ) -> f64 {
-        match ma_type {
-            "EMA" => current_price * ema_k + last_ema_state * (1.0 - ema_k),
-            "HMA" | "EHMA" => {
-                // For HMA/EHMA in incremental, we need the window.
-                // We use state.close_window which is updated in append_candle
-                // Note: 'current_price' is new, not yet in window if we call this before pushing?
-                // Let's assume input 'data' includes everything or we pass window.
-
-                // Construct a temporary window including current price
-                // Only needed for calculation
-                // Performance warning: copying vector
-
-                // We can optimize by iterating strictly.
-
-                // Ensure we have enough data
-                if self.state.close_window.len() + 1 < period {
-                    return current_price;
-                }
-
-                // Combine window + current
-                let iter = self
-                    .state
-                    .close_window
-                    .iter()
-                    .chain(std::iter::once(&current_price));
-                // Collecting to vec is expensive. We should change design if perf critical.
-                // But for < 200 items it's microsecond scale.
-                let data: Vec<f64> = iter.copied().collect();
-
-                if ma_type == "HMA" {
-                    let half = (period / 2).max(1);
-                    let sqrt = (period as f64).sqrt() as usize;
-
-                    // We need WMA of last 'sqrt' points.
-                    // The inputs to this WMA are (2*WMA(half) - WMA(period)).
-                    // We need to generate a series of these "raw" values for the last 'sqrt' points.
-
-                    let mut raw_series = Vec::new();
-                    let needed = sqrt; // We need 'needed' points of Raw Val.
-                                       // To get 1 Raw Val at index T, we need WMA(half) and WMA(period) ending at T.
-                                       // So we need data up to T.
-
-                    // We need to calculate RawVal for i = len-sqrt to len-1
-                    let len = data.len();
-                    if len < period {
-                        return current_price;
-                    }
-
-                    for i in 0..needed {
-                        let end_idx = len - (needed - 1 - i); // ending index (exclusive of slice?) no, include data[end_idx-1]
-                                                              // slice 0..end_idx
-                        if end_idx < period {
-                            continue;
-                        }
-
-                        let slice = &data[0..end_idx];
-                        // Efficiency: This re-calculates WMAs many times.
-                        // For incremental tick: O(period * sqrt(period)).
-                        // With period=200, ~200*14 = 2800 ops. Very fast.
-
-                        let wma_half = Self::calculate_wma(slice, half);
-                        let wma_full = Self::calculate_wma(slice, period);
-                        let raw = 2.0 * wma_half - wma_full;
-                        raw_series.push(raw);
-                    }
-
-                    Self::calculate_wma(&raw_series, sqrt)
-                } else {
-                    // EHMA
-                    let half = (period / 2).max(1);
-                    let sqrt = (period as f64).sqrt() as usize;
-
-                    // Similar to HMA but using EMA
-                    // EMA is stateful. We need to recalculate EMAs from start of window or keep state?
-                    // Recalculating EMA from scratch for the window is safer for consistence.
-                    // Optimization: Use cached EMA if possible?
-                    // Incremental EHMA is tricky without full series re-calc.
-                    // We will do full re-calc on the window for correctness.
-
-                    let len = data.len();
-                    if len < period {
-                        return current_price;
-                    }
-
-                    // We need enough raw values for the final EMA(sqrt)
-                    // But EMA needs to settle.
-                    // Simple implementation: Calculate Raw Series for whole available window, then EMA it.
-
-                    let mut raw_series = Vec::new();
-                    // Optimization: Calculate EMA_Half and EMA_Full incrementally over the window
-
-                    let k_half = 2.0 / (half as f64 + 1.0);
-                    let k_full = 2.0 / (period as f64 + 1.0);
-
-                    let mut val_half = data[0];
-                    let mut val_full = data[0];
-
-                    // Spin up to period
-                    for i in 1..len {
-                        val_half = data[i] * k_half + val_half * (1.0 - k_half);
-                        val_full = data[i] * k_full + val_full * (1.0 - k_full);
-
-                        let raw = 2.0 * val_half - val_full;
-                        raw_series.push(raw);
-                    }
+    pub fn snapshot(&self) -> GeneratorSnapshot {
+        GeneratorSnapshot {
+            version: SNAPSHOT_VERSION,
+            options: self.options.clone(),
+            state: self.state.clone(),
+            analysis_array: self.analysis_array.clone(),
+            candle_data: self.candle_data.clone(),
+            current_candle: self.current_candle,
+            retention: self.retention.clone(),
+        }
+    }
 
-                    // Now EMA(sqrt) on raw_series
-                    // We only care about the last value
-                    Self::calculate_ema_slice(&raw_series, sqrt)
-                }
-            }
-            _ => current_price * ema_k + last_ema_state * (1.0 - ema_k),
+    /// Rebuild a generator from a snapshot taken with the same `SNAPSHOT_VERSION`.
+    pub fn restore(
+        snapshot: GeneratorSnapshot,
+        master_codes: std::sync::Arc<Vec<CandleMasterCode>>,
+    ) -> anyhow::Result<Self> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Snapshot version {} not supported (expected {})",
+                snapshot.version,
+                SNAPSHOT_VERSION
+            ));
         }
+        Ok(Self {
+            options: snapshot.options,
+            state: snapshot.state,
+            analysis_array: snapshot.analysis_array,
+            candle_data: snapshot.candle_data,
+            current_candle: snapshot.current_candle,
+            master_codes,
+            retention: snapshot.retention,
+        })
+    }
+
+    /// Open time of the last closed (analysed) candle.
+    pub fn last_candle_time(&self) -> Option<u64> {
+        self.state.last_candle.map(|c| c.time)
     }
 
     // Helper: EMA Direction
@@ -297,54 +248,33 @@ impl AnalysisGenerator {
     }
 
     pub fn append_candle(&mut self, new_candle: Candle) -> AnalysisResult {
-        let i = self.analysis_array.len();
+        let i = self.state.candle_count;
         let prev_candle = self.state.last_candle; // Copy
 
         // Push candle
         self.candle_data.push(new_candle);
 
         // 1. EMA/HMA/EHMA Logic
-        // Update history window first
-        self.state.close_window.push_back(new_candle.close);
-        if self.state.close_window.len() > self.state.max_ma_period {
-            self.state.close_window.pop_front();
-        }
-
         let close = new_candle.close;
 
-        // Handle init (first candle) - if it's the very first, MA is just the price
-        let new_ema_1 = if i == 0 {
-            close
-        } else {
-            self.calculate_ma(
-                &self.options.ema1_type,
-                self.options.ema1_period,
-                close,
-                self.state.last_ema_1,
-                self.state.ema_1_k,
-            )
-        };
-        let new_ema_2 = if i == 0 {
-            close
+        // Every line is advanced on every candle so HMA/EHMA state keeps warming up;
+        // on the very first candle the MA is just the price
+        let ma_1 = self
+            .state
+            .ma_1
+            .update(close, self.state.last_ema_1, self.state.ema_1_k);
+        let ma_2 = self
+            .state
+            .ma_2
+            .update(close, self.state.last_ema_2, self.state.ema_2_k);
+        let ma_3 = self
+            .state
+            .ma_3
+            .update(close, self.state.last_ema_3, self.state.ema_3_k);
+        let (new_ema_1, new_ema_2, new_ema_3) = if i == 0 {
+            (close, close, close)
         } else {
-            self.calculate_ma(
-                &self.options.ema2_type,
-                self.options.ema2_period,
-                close,
-                self.state.last_ema_2,
-                self.state.ema_2_k,
-            )
-        };
-        let new_ema_3 = if i == 0 {
-            close
-        } else {
-            self.calculate_ma(
-                &self.options.ema3_type,
-                self.options.ema3_period,
-                close,
-                self.state.last_ema_3,
-                self.state.ema_3_k,
-            )
+            (ma_1, ma_2, ma_3)
         };
 
         // Directions
@@ -384,8 +314,7 @@ impl AnalysisGenerator {
                     .state
                     .last_analysis
                     .as_ref()
-                    .map(|a| a.ema_short_value)
-                    .flatten()
+                    .and_then(|a| a.ema_short_value)
                 {
                     // This corresponds to state.last_ema_1
                     // Wait, last_ema_1 IS the value at i-1.
@@ -477,7 +406,10 @@ impl AnalysisGenerator {
             "neutral".to_string()
         }; // Or handle as Option
 
-        let ema_long_convergence_type = if let Some(prev) = prev_macd_23 {
+        // Narrow (|medium - long| at or under macd_narrow) wins over the direction, as in JS
+        let ema_long_convergence_type = if macd_23 <= self.options.macd_narrow {
+            "N".to_string()
+        } else if let Some(prev) = prev_macd_23 {
             if macd_23 > prev {
                 "D".to_string()
             } else if macd_23 < prev {
@@ -747,7 +679,25 @@ impl AnalysisGenerator {
             }
         }
 
-        // 8. Properties
+        // 8. Additional indicators
+        let extra = self.state.indicators.update(&new_candle);
+
+        self.state.pattern_window.push_back(new_candle);
+        if self.state.pattern_window.len() > PATTERN_LOOKBACK {
+            self.state.pattern_window.pop_front();
+        }
+        let candle_patterns = patterns::detect(self.state.pattern_window.make_contiguous());
+
+        // Divergence runs on the signed short/medium spread (macd_12 itself is absolute)
+        let divergences = self.state.divergence.update(SwingCandle {
+            index: i,
+            high: new_candle.high,
+            low: new_candle.low,
+            rsi: rsi_value,
+            macd: (i > 0).then_some(new_ema_1 - new_ema_2),
+        });
+
+        // 9. Properties
         let color = if close > new_candle.open {
             "Green"
         } else if close < new_candle.open {
@@ -854,7 +804,7 @@ impl AnalysisGenerator {
             "F"
         };
         let c4 = &color[0..1];
-        let c5 = if ema_long_convergence_type != "" {
+        let c5 = if !ema_long_convergence_type.is_empty() {
             &ema_long_convergence_type
         } else {
             "-"
@@ -871,6 +821,9 @@ impl AnalysisGenerator {
                 break;
             }
         }
+        if status_code.is_empty() && !self.master_codes.is_empty() {
+            master_codes::record_unmapped(&status_desc);
+        }
 
         // Match StatusCode
         // We need the `CandleMasterCode` list from init?
@@ -897,7 +850,7 @@ impl AnalysisGenerator {
             pip_size,
             ema_short_value: Some(new_ema_1),
             ema_short_direction: ema_1_dir,
-            ema_short_turn_type: ema_short_turn_type,
+            ema_short_turn_type,
             ema_medium_value: Some(new_ema_2),
             ema_medium_direction: ema_2_dir,
             ema_long_value: Some(new_ema_3),
@@ -950,6 +903,15 @@ impl AnalysisGenerator {
             down_con_medium_ema,
             up_con_long_ema,
             down_con_long_ema,
+            stochastic: extra.stochastic,
+            macd: extra.macd,
+            supertrend: extra.supertrend,
+            psar: extra.psar,
+            donchian: extra.donchian,
+            keltner: extra.keltner,
+            williams_r: extra.williams_r,
+            patterns: candle_patterns,
+            divergences,
             is_mark: "n".to_string(),
             status_code,
             status_desc: status_desc.clone(),
@@ -967,6 +929,7 @@ impl AnalysisGenerator {
         }
 
         self.analysis_array.push(analysis_obj.clone());
+        self.enforce_retention();
 
         // Update state
         self.state.last_ema_1 = new_ema_1;
@@ -984,16 +947,73 @@ impl AnalysisGenerator {
         self.state.prev_analysis = self.state.last_analysis.clone();
         self.state.last_analysis = Some(analysis_obj.clone());
         self.state.last_candle = Some(new_candle);
+        self.state.candle_count = i + 1;
 
         analysis_obj
     }
 
+    /// Trim history down to the retention limit. Trimming happens in chunks so the
+    /// front drain stays amortized O(1) per candle.
+    fn enforce_retention(&mut self) {
+        // At least the last result stays in memory so its `next_color` can be filled in
+        let Some(keep_last) = self.retention.keep_last().map(|n| n.max(1)) else {
+            return;
+        };
+        let slack = (keep_last / 4).max(16);
+        if self.analysis_array.len() <= keep_last + slack {
+            return;
+        }
+
+        let excess = self.analysis_array.len() - keep_last;
+        let dropped: Vec<AnalysisResult> = self.analysis_array.drain(..excess).collect();
+        let candle_excess = self.candle_data.len().saturating_sub(keep_last);
+        self.candle_data.drain(..candle_excess);
+
+        if let RetentionPolicy::SpillToDisk { path, .. } = &self.retention {
+            if let Err(e) = Self::spill(path, &dropped) {
+                log::warn!("Failed to spill {} results to {:?}: {}", dropped.len(), path, e);
+            }
+        }
+    }
+
+    fn spill(path: &Path, results: &[AnalysisResult]) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut buf = String::new();
+        for r in results {
+            buf.push_str(&serde_json::to_string(r)?);
+            buf.push('\n');
+        }
+        file.write_all(buf.as_bytes())?;
+        Ok(())
+    }
+
+    /// Candle period (seconds) this generator buckets ticks into.
+    pub fn granularity(&self) -> u64 {
+        self.options.granularity.max(1)
+    }
+
     pub fn append_tick(&mut self, price: f64, time: u64) -> Option<AnalysisResult> {
-        let tick_minute = (time / 60) * 60;
+        self.fold_candle(Candle {
+            time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+        })
+    }
+
+    /// Merge a lower-timeframe candle (or a single tick) into the forming candle.
+    /// When the input belongs to a later bucket, the forming candle is closed and
+    /// analysed first, and its result is returned.
+    pub fn fold_candle(&mut self, part: Candle) -> Option<AnalysisResult> {
+        let granularity = self.granularity();
+        let bucket_time = (part.time / granularity) * granularity;
 
         if let Some(mut current) = self.current_candle {
-            if time >= current.time + 60 {
-                // Or robust check: tick_minute > current.time
+            if bucket_time > current.time {
                 // Complete previous candle
                 let completed_candle = current; // Copy
 
@@ -1002,32 +1022,94 @@ impl AnalysisGenerator {
 
                 // Start new candle
                 self.current_candle = Some(Candle {
-                    time: tick_minute,
-                    open: price,
-                    high: price,
-                    low: price,
-                    close: price,
+                    time: bucket_time,
+                    ..part
                 });
 
                 Some(result)
             } else {
                 // Update current
-                current.high = current.high.max(price);
-                current.low = current.low.min(price);
-                current.close = price;
+                current.high = current.high.max(part.high);
+                current.low = current.low.min(part.low);
+                current.close = part.close;
                 self.current_candle = Some(current);
                 None
             }
         } else {
             // First tick ever seen (or after reset)
             self.current_candle = Some(Candle {
-                time: tick_minute,
-                open: price,
-                high: price,
-                low: price,
-                close: price,
+                time: bucket_time,
+                ..part
             });
             None
         }
     }
+
+    /// Close and analyse the forming candle now (e.g. once its period is known to be complete).
+    pub fn close_current_candle(&mut self) -> Option<AnalysisResult> {
+        let completed_candle = self.current_candle.take()?;
+        Some(self.append_candle(completed_candle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn candle(i: u64) -> Candle {
+        let t = i as f64;
+        Candle {
+            time: i * 60,
+            open: 100.0 + (t * 0.21).sin() * 2.0,
+            high: 102.0 + (t * 0.21).sin() * 2.0,
+            low: 98.0 + (t * 0.21).sin() * 2.0,
+            close: 100.0 + (t * 0.17).cos() * 2.0,
+        }
+    }
+
+    #[test]
+    fn test_retention_keeps_results_identical_to_unbounded() {
+        let mut unbounded = AnalysisGenerator::new(AnalysisOptions::default(), Arc::new(Vec::new()));
+        let mut bounded = AnalysisGenerator::new(AnalysisOptions::default(), Arc::new(Vec::new()))
+            .with_retention(RetentionPolicy::KeepLast(50));
+
+        for i in 0..2000 {
+            let a = unbounded.append_candle(candle(i));
+            let b = bounded.append_candle(candle(i));
+            assert_eq!(a.index, b.index);
+            assert_eq!(a.ema_short_turn_type, b.ema_short_turn_type);
+            assert_eq!(a.candles_since_ema_cut, b.candles_since_ema_cut);
+            assert_eq!(a.status_desc, b.status_desc);
+        }
+
+        assert!(bounded.analysis_array.len() <= 50 + 16);
+        assert!(bounded.candle_data.len() <= 50 + 16);
+        assert_eq!(bounded.analysis_array.last().unwrap().index, 1999);
+        assert_eq!(bounded.state.candle_count, 2000);
+    }
+
+    #[test]
+    fn test_spill_to_disk_writes_trimmed_results() {
+        let path = std::env::temp_dir().join(format!("indicator_math_spill_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut gen = AnalysisGenerator::new(AnalysisOptions::default(), Arc::new(Vec::new()))
+            .with_retention(RetentionPolicy::SpillToDisk {
+                keep_last: 20,
+                path: path.clone(),
+            });
+        for i in 0..100 {
+            gen.append_candle(candle(i));
+        }
+
+        let spilled: Vec<AnalysisResult> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let first_kept = gen.analysis_array.first().unwrap().index;
+        assert_eq!(spilled.len(), first_kept);
+        assert!(spilled.iter().enumerate().all(|(i, r)| r.index == i));
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,643 @@
+use crate::streaming::StreamingEma;
+use crate::structs::{
+    Candle, ChannelValues, IndicatorOptions, MacdValues, StochasticValues, TrendLevel,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Streaming versions of the oscillators and channels not covered by the JS generator.
+// Every update is O(1) (amortized for the rolling highs/lows), so they can run on
+// every closed candle without keeping history.
+
+/// Highest high / lowest low over the last `period` candles, via monotonic queues.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RollingExtremes {
+    pub period: usize,
+    pub count: usize,
+    pub highs: VecDeque<(usize, f64)>,
+    pub lows: VecDeque<(usize, f64)>,
+}
+
+impl RollingExtremes {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            count: 0,
+            highs: VecDeque::new(),
+            lows: VecDeque::new(),
+        }
+    }
+
+    /// Returns `(highest, lowest)` once `period` candles have been seen.
+    pub fn update(&mut self, high: f64, low: f64) -> Option<(f64, f64)> {
+        let i = self.count;
+        self.count += 1;
+        while self.highs.back().is_some_and(|&(_, h)| h <= high) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((i, high));
+        while self.lows.back().is_some_and(|&(_, l)| l >= low) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((i, low));
+        while self
+            .highs
+            .front()
+            .is_some_and(|&(j, _)| j + self.period <= i)
+        {
+            self.highs.pop_front();
+        }
+        while self
+            .lows
+            .front()
+            .is_some_and(|&(j, _)| j + self.period <= i)
+        {
+            self.lows.pop_front();
+        }
+        if self.count < self.period {
+            return None;
+        }
+        Some((self.highs.front()?.1, self.lows.front()?.1))
+    }
+}
+
+/// ATR with the same warm-up as the generator's: running mean, then Wilder smoothing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingAtr {
+    pub period: usize,
+    pub count: usize,
+    pub value: f64,
+    pub prev_close: Option<f64>,
+}
+
+impl StreamingAtr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            count: 0,
+            value: 0.0,
+            prev_close: None,
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> Option<f64> {
+        let tr = match self.prev_close {
+            Some(pc) => (candle.high - candle.low)
+                .max((candle.high - pc).abs())
+                .max((candle.low - pc).abs()),
+            None => candle.high - candle.low,
+        };
+        let n = self.count as f64;
+        self.value = if self.count < self.period {
+            (self.value * n + tr) / (n + 1.0)
+        } else {
+            (self.value * (self.period as f64 - 1.0) + tr) / self.period as f64
+        };
+        self.count += 1;
+        self.prev_close = Some(candle.close);
+        (self.count >= self.period).then_some(self.value)
+    }
+}
+
+/// Stochastic oscillator: %K over `k_period`, %D = SMA(%K, `d_period`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingStochastic {
+    pub range: RollingExtremes,
+    pub d_period: usize,
+    pub k_window: VecDeque<f64>,
+    pub k_sum: f64,
+}
+
+impl StreamingStochastic {
+    pub fn new(k_period: usize, d_period: usize) -> Self {
+        let d_period = d_period.max(1);
+        Self {
+            range: RollingExtremes::new(k_period),
+            d_period,
+            k_window: VecDeque::with_capacity(d_period + 1),
+            k_sum: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> StochasticValues {
+        let Some((highest, lowest)) = self.range.update(candle.high, candle.low) else {
+            return StochasticValues::default();
+        };
+        let range = highest - lowest;
+        // A flat window has no position inside the range; report the midpoint
+        let k = if range > 0.0 {
+            (candle.close - lowest) / range * 100.0
+        } else {
+            50.0
+        };
+        self.k_window.push_back(k);
+        self.k_sum += k;
+        if self.k_window.len() > self.d_period {
+            self.k_sum -= self.k_window.pop_front().unwrap_or(0.0);
+        }
+        let d = (self.k_window.len() == self.d_period).then(|| self.k_sum / self.d_period as f64);
+        StochasticValues { k: Some(k), d }
+    }
+}
+
+/// Williams %R over `period`, in [-100, 0].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingWilliamsR {
+    pub range: RollingExtremes,
+}
+
+impl StreamingWilliamsR {
+    pub fn new(period: usize) -> Self {
+        Self {
+            range: RollingExtremes::new(period),
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> Option<f64> {
+        let (highest, lowest) = self.range.update(candle.high, candle.low)?;
+        let range = highest - lowest;
+        Some(if range > 0.0 {
+            (highest - candle.close) / range * -100.0
+        } else {
+            -50.0
+        })
+    }
+}
+
+/// MACD line = EMA(fast) - EMA(slow), signal = EMA(MACD, signal), histogram = MACD - signal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingMacd {
+    pub fast: StreamingEma,
+    pub slow: StreamingEma,
+    pub signal: StreamingEma,
+}
+
+impl StreamingMacd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            fast: StreamingEma::new(fast),
+            slow: StreamingEma::new(slow),
+            signal: StreamingEma::new(signal),
+        }
+    }
+
+    pub fn update(&mut self, close: f64) -> MacdValues {
+        let fast = self.fast.update(close);
+        let slow = self.slow.update(close);
+        let (Some(f), Some(s)) = (fast, slow) else {
+            return MacdValues::default();
+        };
+        let macd = f - s;
+        let signal = self.signal.update(macd);
+        MacdValues {
+            macd: Some(macd),
+            signal,
+            histogram: signal.map(|sig| macd - sig),
+        }
+    }
+}
+
+/// SuperTrend on ATR(`period`) bands around (high + low) / 2.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingSuperTrend {
+    pub atr: StreamingAtr,
+    pub multiplier: f64,
+    pub final_upper: Option<f64>,
+    pub final_lower: Option<f64>,
+    pub up_trend: bool,
+    pub prev_close: Option<f64>,
+}
+
+impl StreamingSuperTrend {
+    pub fn new(period: usize, multiplier: f64) -> Self {
+        Self {
+            atr: StreamingAtr::new(period),
+            multiplier,
+            final_upper: None,
+            final_lower: None,
+            up_trend: true,
+            prev_close: None,
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> TrendLevel {
+        let atr = self.atr.update(candle);
+        let prev_close = self.prev_close.replace(candle.close);
+        let Some(atr) = atr else {
+            return TrendLevel::default();
+        };
+        let mid = (candle.high + candle.low) / 2.0;
+        let basic_upper = mid + self.multiplier * atr;
+        let basic_lower = mid - self.multiplier * atr;
+
+        // Bands only tighten while price stays on their side
+        let upper = match (self.final_upper, prev_close) {
+            (Some(fu), Some(pc)) if basic_upper > fu && pc <= fu => fu,
+            _ => basic_upper,
+        };
+        let lower = match (self.final_lower, prev_close) {
+            (Some(fl), Some(pc)) if basic_lower < fl && pc >= fl => fl,
+            _ => basic_lower,
+        };
+
+        self.up_trend = match (self.final_upper, self.final_lower) {
+            (Some(_), Some(_)) if self.up_trend => candle.close >= lower,
+            (Some(_), Some(_)) => candle.close > upper,
+            _ => candle.close >= mid,
+        };
+        self.final_upper = Some(upper);
+        self.final_lower = Some(lower);
+
+        TrendLevel {
+            value: Some(if self.up_trend { lower } else { upper }),
+            trend: Some(trend_name(self.up_trend)),
+        }
+    }
+}
+
+/// Wilder's Parabolic SAR. The reported value is the stop for the current candle.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingPsar {
+    pub step: f64,
+    pub max_step: f64,
+    pub af: f64,
+    pub sar: Option<f64>,
+    pub extreme: f64,
+    pub up_trend: bool,
+    /// The last two candles, most recent last
+    pub recent: VecDeque<Candle>,
+}
+
+impl StreamingPsar {
+    pub fn new(step: f64, max_step: f64) -> Self {
+        Self {
+            step,
+            max_step,
+            af: step,
+            sar: None,
+            extreme: 0.0,
+            up_trend: true,
+            recent: VecDeque::with_capacity(3),
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> TrendLevel {
+        let result = match (self.sar, self.recent.back().copied()) {
+            (_, None) => None,
+            (None, Some(prev)) => {
+                // Second candle: the initial trend follows the close, the SAR starts
+                // at the opposite extreme of the first candle
+                self.up_trend = candle.close >= prev.close;
+                self.af = self.step;
+                let sar = if self.up_trend { prev.low } else { prev.high };
+                self.extreme = if self.up_trend {
+                    candle.high
+                } else {
+                    candle.low
+                };
+                Some(sar)
+            }
+            (Some(sar), Some(_)) => Some(self.advance(sar, candle)),
+        };
+        self.sar = result;
+        self.recent.push_back(*candle);
+        if self.recent.len() > 2 {
+            self.recent.pop_front();
+        }
+        TrendLevel {
+            value: result,
+            trend: result.map(|_| trend_name(self.up_trend)),
+        }
+    }
+
+    fn advance(&mut self, sar: f64, candle: &Candle) -> f64 {
+        let mut next = sar + self.af * (self.extreme - sar);
+        // The SAR never moves into the previous two candles' range
+        for c in &self.recent {
+            next = if self.up_trend {
+                next.min(c.low)
+            } else {
+                next.max(c.high)
+            };
+        }
+
+        if self.up_trend && candle.low < next {
+            self.up_trend = false;
+            self.af = self.step;
+            next = self.extreme;
+            self.extreme = candle.low;
+        } else if !self.up_trend && candle.high > next {
+            self.up_trend = true;
+            self.af = self.step;
+            next = self.extreme;
+            self.extreme = candle.high;
+        } else if self.up_trend && candle.high > self.extreme {
+            self.extreme = candle.high;
+            self.af = (self.af + self.step).min(self.max_step);
+        } else if !self.up_trend && candle.low < self.extreme {
+            self.extreme = candle.low;
+            self.af = (self.af + self.step).min(self.max_step);
+        }
+        next
+    }
+}
+
+/// Donchian channel: highest high / lowest low over `period` and their midpoint.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingDonchian {
+    pub range: RollingExtremes,
+}
+
+impl StreamingDonchian {
+    pub fn new(period: usize) -> Self {
+        Self {
+            range: RollingExtremes::new(period),
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> ChannelValues {
+        match self.range.update(candle.high, candle.low) {
+            Some((upper, lower)) => ChannelValues {
+                upper: Some(upper),
+                middle: Some((upper + lower) / 2.0),
+                lower: Some(lower),
+            },
+            None => ChannelValues::default(),
+        }
+    }
+}
+
+/// Keltner channel: EMA(close, `period`) +/- `multiplier` * ATR(`atr_period`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingKeltner {
+    pub ema: StreamingEma,
+    pub atr: StreamingAtr,
+    pub multiplier: f64,
+}
+
+impl StreamingKeltner {
+    pub fn new(period: usize, atr_period: usize, multiplier: f64) -> Self {
+        Self {
+            ema: StreamingEma::new(period),
+            atr: StreamingAtr::new(atr_period),
+            multiplier,
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> ChannelValues {
+        let middle = self.ema.update(candle.close);
+        let atr = self.atr.update(candle);
+        match (middle, atr) {
+            (Some(m), Some(a)) => ChannelValues {
+                upper: Some(m + self.multiplier * a),
+                middle: Some(m),
+                lower: Some(m - self.multiplier * a),
+            },
+            _ => ChannelValues::default(),
+        }
+    }
+}
+
+fn trend_name(up: bool) -> String {
+    if up { "Up" } else { "Down" }.to_string()
+}
+
+/// All additional indicators of one generator.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndicatorState {
+    pub stochastic: StreamingStochastic,
+    pub macd: StreamingMacd,
+    pub supertrend: StreamingSuperTrend,
+    pub psar: StreamingPsar,
+    pub donchian: StreamingDonchian,
+    pub keltner: StreamingKeltner,
+    pub williams_r: StreamingWilliamsR,
+}
+
+/// One candle's worth of additional indicator values.
+#[derive(Debug, Clone, Default)]
+pub struct IndicatorValues {
+    pub stochastic: StochasticValues,
+    pub macd: MacdValues,
+    pub supertrend: TrendLevel,
+    pub psar: TrendLevel,
+    pub donchian: ChannelValues,
+    pub keltner: ChannelValues,
+    pub williams_r: Option<f64>,
+}
+
+impl IndicatorState {
+    pub fn new(options: &IndicatorOptions) -> Self {
+        Self {
+            stochastic: StreamingStochastic::new(options.stoch_k_period, options.stoch_d_period),
+            macd: StreamingMacd::new(options.macd_fast, options.macd_slow, options.macd_signal),
+            supertrend: StreamingSuperTrend::new(
+                options.supertrend_period,
+                options.supertrend_multiplier,
+            ),
+            psar: StreamingPsar::new(options.psar_step, options.psar_max_step),
+            donchian: StreamingDonchian::new(options.donchian_period),
+            keltner: StreamingKeltner::new(
+                options.keltner_period,
+                options.keltner_atr_period,
+                options.keltner_multiplier,
+            ),
+            williams_r: StreamingWilliamsR::new(options.williams_period),
+        }
+    }
+
+    pub fn update(&mut self, candle: &Candle) -> IndicatorValues {
+        IndicatorValues {
+            stochastic: self.stochastic.update(candle),
+            macd: self.macd.update(candle.close),
+            supertrend: self.supertrend.update(candle),
+            psar: self.psar.update(candle),
+            donchian: self.donchian.update(candle),
+            keltner: self.keltner.update(candle),
+            williams_r: self.williams_r.update(candle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicator_math_v1::{atr as v1_atr, ema as v1_ema, Candle as V1Candle};
+
+    fn candles(n: usize) -> Vec<Candle> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64;
+                let close = 100.0 + (t * 0.11).sin() * 6.0 + (t * 0.027).cos() * 10.0;
+                let open = close - (t * 0.7).sin();
+                Candle {
+                    time: i as u64 * 60,
+                    open,
+                    high: open.max(close) + 0.3 + (t * 0.5).cos().abs(),
+                    low: open.min(close) - 0.3 - (t * 0.3).sin().abs(),
+                    close,
+                }
+            })
+            .collect()
+    }
+
+    fn v1(c: &[Candle]) -> Vec<V1Candle> {
+        c.iter()
+            .map(|c| V1Candle {
+                time: c.time,
+                open: c.open,
+                high: c.high,
+                low: c.low,
+                close: c.close,
+            })
+            .collect()
+    }
+
+    fn window_range(c: &[Candle], end: usize, period: usize) -> (f64, f64) {
+        let w = &c[end + 1 - period..=end];
+        (
+            w.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max),
+            w.iter().map(|c| c.low).fold(f64::INFINITY, f64::min),
+        )
+    }
+
+    #[test]
+    fn test_stochastic_williams_donchian_match_naive_window() {
+        let c = candles(300);
+        let mut stoch = StreamingStochastic::new(14, 3);
+        let mut wr = StreamingWilliamsR::new(14);
+        let mut dc = StreamingDonchian::new(20);
+        let mut ks = Vec::new();
+        for (i, candle) in c.iter().enumerate() {
+            let s = stoch.update(candle);
+            let w = wr.update(candle);
+            let d = dc.update(candle);
+            if i < 13 {
+                assert!(s.k.is_none() && w.is_none());
+                continue;
+            }
+            let (hh, ll) = window_range(&c, i, 14);
+            let k = (candle.close - ll) / (hh - ll) * 100.0;
+            assert!((s.k.unwrap() - k).abs() < 1e-9);
+            assert!((w.unwrap() - (k - 100.0)).abs() < 1e-9);
+            ks.push(k);
+            if ks.len() >= 3 {
+                let d_expected = ks[ks.len() - 3..].iter().sum::<f64>() / 3.0;
+                assert!((s.d.unwrap() - d_expected).abs() < 1e-9);
+            } else {
+                assert!(s.d.is_none());
+            }
+            if i >= 19 {
+                let (hh, ll) = window_range(&c, i, 20);
+                assert_eq!(d.upper, Some(hh));
+                assert_eq!(d.lower, Some(ll));
+            } else {
+                assert!(d.upper.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_macd_and_keltner_match_v1_batch() {
+        let c = candles(400);
+        let fast = v1_ema(&v1(&c), 12);
+        let slow = v1_ema(&v1(&c), 26);
+        let atr = v1_atr(&v1(&c), 10);
+        let ema20 = v1_ema(&v1(&c), 20);
+        let mut macd = StreamingMacd::new(12, 26, 9);
+        let mut keltner = StreamingKeltner::new(20, 10, 2.0);
+        let mut atr_stream = StreamingAtr::new(10);
+        let mut lines = Vec::new();
+        for (i, candle) in c.iter().enumerate() {
+            let m = macd.update(candle.close);
+            let k = keltner.update(candle);
+            let a = atr_stream.update(candle);
+            if let Some(v) = m.macd {
+                assert!((v - (fast[i].value - slow[i].value)).abs() < 1e-9);
+                lines.push(v);
+            } else {
+                assert!(slow[i].value.is_nan());
+            }
+            if let Some(a) = a {
+                assert!(!atr[i].value.is_nan());
+                assert!((a - atr[i].value).abs() < 1e-9, "atr at {}", i);
+            }
+            if let Some(mid) = k.middle {
+                assert!((mid - ema20[i].value).abs() < 1e-9);
+            }
+        }
+        // Signal line is the v1 EMA of the MACD line itself
+        let line_candles: Vec<V1Candle> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| V1Candle {
+                time: i as u64,
+                open: v,
+                high: v,
+                low: v,
+                close: v,
+            })
+            .collect();
+        let signal = v1_ema(&line_candles, 9);
+        let mut macd = StreamingMacd::new(12, 26, 9);
+        let results: Vec<MacdValues> = c.iter().map(|x| macd.update(x.close)).collect();
+        let offset = c.len() - lines.len();
+        for (j, s) in signal.iter().enumerate() {
+            let r = &results[offset + j];
+            match r.signal {
+                Some(v) => {
+                    assert!((v - s.value).abs() < 1e-9);
+                    assert!((r.histogram.unwrap() - (lines[j] - v)).abs() < 1e-12);
+                }
+                None => assert!(s.value.is_nan()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_supertrend_and_psar_follow_trend() {
+        let up: Vec<Candle> = (0..60)
+            .map(|i| {
+                let p = 100.0 + i as f64;
+                Candle {
+                    time: i * 60,
+                    open: p - 0.5,
+                    high: p + 0.5,
+                    low: p - 1.0,
+                    close: p,
+                }
+            })
+            .collect();
+        let down: Vec<Candle> = (0..60)
+            .map(|i| {
+                let p = 159.0 - 2.0 * i as f64;
+                Candle {
+                    time: (60 + i) * 60,
+                    open: p + 0.5,
+                    high: p + 1.0,
+                    low: p - 0.5,
+                    close: p,
+                }
+            })
+            .collect();
+        let mut st = StreamingSuperTrend::new(10, 3.0);
+        let mut psar = StreamingPsar::new(0.02, 0.2);
+        let mut last = (TrendLevel::default(), TrendLevel::default());
+        for c in &up {
+            last = (st.update(c), psar.update(c));
+            if let Some(v) = last.1.value {
+                assert!(v <= c.low, "PSAR above an up candle");
+            }
+        }
+        assert_eq!(last.0.trend.as_deref(), Some("Up"));
+        assert!(last.0.value.unwrap() < up.last().unwrap().low);
+        assert_eq!(last.1.trend.as_deref(), Some("Up"));
+        for c in &down {
+            last = (st.update(c), psar.update(c));
+        }
+        assert_eq!(last.0.trend.as_deref(), Some("Down"));
+        assert!(last.0.value.unwrap() > down.last().unwrap().high);
+        assert_eq!(last.1.trend.as_deref(), Some("Down"));
+        assert!(last.1.value.unwrap() >= down.last().unwrap().high);
+    }
+}
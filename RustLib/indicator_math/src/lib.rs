@@ -1,7 +1,18 @@
 pub mod deriv_api;
+pub mod divergence;
 pub mod generator;
+pub mod indicators;
 pub mod manager;
+pub mod master_codes;
+pub mod patterns;
+pub mod streaming;
 pub mod structs;
+pub mod timeframe;
 
-pub use generator::AnalysisGenerator;
-pub use structs::{AnalysisOptions, AnalysisResult, Candle, CandleMasterCode};
+pub use divergence::Divergence;
+pub use generator::{AnalysisGenerator, RetentionPolicy};
+pub use manager::{AnalysisManager, MultiTimeframeManager};
+pub use master_codes::MasterCodeTable;
+pub use patterns::CandlePattern;
+pub use structs::{AnalysisOptions, AnalysisResult, Candle, CandleMasterCode, IndicatorOptions};
+pub use timeframe::MultiTimeframeGenerator;
@@ -1,35 +1,79 @@
-use crate::generator::AnalysisGenerator;
-use crate::structs::{AnalysisOptions, AnalysisResult, CandleMasterCode};
-use crate::deriv_api::fetch_candles;
+use crate::generator::{AnalysisGenerator, GeneratorSnapshot, RetentionPolicy};
+use crate::structs::{AnalysisOptions, AnalysisResult, Candle, CandleMasterCode};
+use crate::deriv_api::{fetch_candles, parse_live_message, subscription_request, LiveFeed};
+use crate::timeframe::MultiTimeframeGenerator;
 use dashmap::DashMap;
+use futures_util::{SinkExt, StreamExt};
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 use tokio::task;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+fn write_snapshot(dir: &Path, asset: &str, gen: &AnalysisGenerator) -> anyhow::Result<()> {
+    let json = serde_json::to_string(&gen.snapshot())?;
+    let tmp = dir.join(format!("{}.json.tmp", asset));
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, dir.join(format!("{}.json", asset)))?;
+    Ok(())
+}
 
 pub struct AnalysisManager {
     pub generators: Arc<DashMap<String, AnalysisGenerator>>,
     pub options: AnalysisOptions,
     pub master_codes: Arc<Vec<CandleMasterCode>>,
+    pub retention: RetentionPolicy,
+    /// Closed-candle results published by `run_live` (and `process_tick`/`process_ohlc`)
+    pub updates: broadcast::Sender<(String, AnalysisResult)>,
 }
 
 impl AnalysisManager {
     pub fn new(options: AnalysisOptions, master_codes: Vec<CandleMasterCode>) -> Self {
+        let (updates, _) = broadcast::channel(1024);
         Self {
             generators: Arc::new(DashMap::new()),
             options,
             master_codes: Arc::new(master_codes),
+            retention: RetentionPolicy::Unbounded,
+            updates,
+        }
+    }
+
+    /// Receiver for `(asset, AnalysisResult)` of every candle closed from live data.
+    pub fn subscribe(&self) -> broadcast::Receiver<(String, AnalysisResult)> {
+        self.updates.subscribe()
+    }
+
+    /// History retention applied to every generator the manager creates or restores.
+    /// For `SpillToDisk`, `path` is a directory and each asset spills to `<path>/<asset>.jsonl`.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    fn retention_for(&self, asset: &str) -> RetentionPolicy {
+        match &self.retention {
+            RetentionPolicy::SpillToDisk { keep_last, path } => RetentionPolicy::SpillToDisk {
+                keep_last: *keep_last,
+                path: path.join(format!("{}.jsonl", asset)),
+            },
+            other => other.clone(),
         }
     }
 
     pub async fn initialize(&self, ws_url: &str, assets: Vec<String>) -> Vec<(String, Result<AnalysisResult, String>)> {
         let mut tasks = Vec::new();
 
+        let granularity = self.options.granularity;
         for asset in assets {
             let url = ws_url.to_string();
             let asset_clone = asset.clone();
             // Spawn task for each asset
             tasks.push(task::spawn(async move {
                 // Fetch history
-                match fetch_candles(&url, &asset_clone, 1000).await {
+                match fetch_candles(&url, &asset_clone, 1000, granularity).await {
                     Ok(candles) => (asset_clone, Ok(candles)),
                     Err(e) => (asset_clone, Err(e.to_string())),
                 }
@@ -42,7 +86,8 @@ impl AnalysisManager {
             if let Ok((asset, res)) = task.await {
                 match res {
                     Ok(candles) => {
-                        let mut gen = AnalysisGenerator::new(self.options.clone(), self.master_codes.clone());
+                        let mut gen = AnalysisGenerator::new(self.options.clone(), self.master_codes.clone())
+                            .with_retention(self.retention_for(&asset));
                         let mut last_result = None;
                         
                         // Process existing history
@@ -71,16 +116,182 @@ impl AnalysisManager {
         results
     }
 
+    /// Write one `<asset>.json` snapshot per generator into `dir`.
+    /// Each file is written to a temp file first and renamed, so a crash never leaves a half-written snapshot.
+    pub fn save_snapshots(&self, dir: &Path) -> anyhow::Result<usize> {
+        fs::create_dir_all(dir)?;
+        let mut saved = 0;
+        for entry in self.generators.iter() {
+            write_snapshot(dir, entry.key(), entry.value())?;
+            saved += 1;
+        }
+        Ok(saved)
+    }
+
+    /// Write the snapshot of `asset` only. Returns false when the manager has no such generator.
+    pub fn save_snapshot(&self, dir: &Path, asset: &str) -> anyhow::Result<bool> {
+        let Some(gen) = self.generators.get(asset) else {
+            return Ok(false);
+        };
+        fs::create_dir_all(dir)?;
+        write_snapshot(dir, asset, &gen)?;
+        Ok(true)
+    }
+
+    /// Load the snapshot of `asset` from `dir`. Fails if missing, from another
+    /// snapshot version, or taken with different `AnalysisOptions`.
+    pub fn load_snapshot(&self, dir: &Path, asset: &str) -> anyhow::Result<AnalysisGenerator> {
+        let content = fs::read_to_string(dir.join(format!("{}.json", asset)))?;
+        let snapshot: GeneratorSnapshot = serde_json::from_str(&content)?;
+        if snapshot.options != self.options {
+            return Err(anyhow::anyhow!("Snapshot options differ from current options"));
+        }
+        Ok(AnalysisGenerator::restore(snapshot, self.master_codes.clone())?
+            .with_retention(self.retention_for(asset)))
+    }
+
+    /// Load every `<asset>.json` snapshot found in `dir` into the manager.
+    /// Returns the assets that were restored.
+    pub fn load_snapshots(&self, dir: &Path) -> Vec<String> {
+        let mut restored = Vec::new();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return restored;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(asset) = path.file_stem().and_then(|s| s.to_str()).map(String::from) else {
+                continue;
+            };
+            if let Ok(gen) = self.load_snapshot(dir, &asset) {
+                self.generators.insert(asset.clone(), gen);
+                restored.push(asset);
+            }
+        }
+        restored
+    }
+
+    /// Like `initialize`, but resumes each asset from its snapshot in `dir` when one is usable
+    /// and only fetches the candles closed since the last saved one.
+    /// Assets without a usable snapshot (or with a gap longer than 1000 candles) are fully refetched.
+    pub async fn initialize_from_snapshots(&self, ws_url: &str, assets: Vec<String>, dir: &Path) -> Vec<(String, Result<AnalysisResult, String>)> {
+        let granularity = self.options.granularity.max(1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut fresh = Vec::new();
+        let mut tasks = Vec::new();
+
+        for asset in assets {
+            let resumable = self.load_snapshot(dir, &asset).ok().and_then(|gen| {
+                let last_time = gen.last_candle_time()?;
+                let gap = now.saturating_sub(last_time) / granularity;
+                (gap <= 1000).then_some((gen, last_time, gap as usize + 2))
+            });
+
+            match resumable {
+                Some((gen, last_time, count)) => {
+                    let url = ws_url.to_string();
+                    tasks.push(task::spawn(async move {
+                        let res = fetch_candles(&url, &asset, count, granularity).await;
+                        (asset, gen, last_time, res.map_err(|e| e.to_string()))
+                    }));
+                }
+                None => fresh.push(asset),
+            }
+        }
+
+        let mut results = Vec::new();
+
+        for task in tasks {
+            if let Ok((asset, mut gen, last_time, res)) = task.await {
+                match res {
+                    Ok(candles) => {
+                        // Skip what the snapshot already has and the candle that is still forming
+                        for candle in candles {
+                            if candle.time > last_time && candle.time + granularity <= now {
+                                gen.append_candle(candle);
+                            }
+                        }
+                        gen.current_candle = None;
+                        let latest = gen.state.last_analysis.clone();
+                        self.generators.insert(asset.clone(), gen);
+                        match latest {
+                            Some(r) => results.push((asset, Ok(r))),
+                            None => results.push((asset, Err("No candles".to_string()))),
+                        }
+                    }
+                    Err(e) => results.push((asset, Err(e))),
+                }
+            }
+        }
+
+        if !fresh.is_empty() {
+            results.extend(self.initialize(ws_url, fresh).await);
+        }
+
+        results
+    }
+
+    /// Subscribe to `feed` for all `assets` over a single Deriv connection and feed every
+    /// update into the asset's generator. Closed candles are published on `updates`.
+    /// Assets not initialized yet start with an empty generator.
+    /// Runs until the connection closes; callers wanting reconnects loop around it.
+    pub async fn run_live(&self, ws_url: &str, assets: Vec<String>, feed: LiveFeed) -> anyhow::Result<()> {
+        let url = url::Url::parse(ws_url)?;
+        let (ws_stream, _) = connect_async(url).await?;
+        let (mut write, mut read) = ws_stream.split();
+
+        for asset in &assets {
+            self.generators.entry(asset.clone()).or_insert_with(|| {
+                AnalysisGenerator::new(self.options.clone(), self.master_codes.clone())
+                    .with_retention(self.retention_for(asset))
+            });
+            let req = subscription_request(feed, asset, self.options.granularity);
+            write.send(Message::Text(req.to_string())).await?;
+        }
+
+        while let Some(msg) = read.next().await {
+            let Message::Text(text) = msg? else {
+                continue;
+            };
+            let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
+                continue;
+            };
+            if let Some(error) = v.get("error") {
+                log::warn!("Deriv API Error: {:?}", error);
+                continue;
+            }
+            if let Some((asset, part)) = parse_live_message(&v) {
+                match feed {
+                    LiveFeed::Ticks => self.process_tick(&asset, part.close, part.time),
+                    LiveFeed::Ohlc => self.process_ohlc(&asset, part),
+                };
+            }
+        }
+
+        Err(anyhow::anyhow!("Connection closed"))
+    }
+
     /// Process a new tick for a specific asset.
     /// Returns Some((Asset, AnalysisResult)) if a candle closed.
     pub fn process_tick(&self, asset: &str, price: f64, time: u64) -> Option<(String, AnalysisResult)> {
-        if let Some(mut gen) = self.generators.get_mut(asset) {
-            if let Some(result) = gen.append_tick(price, time) {
-                // Candle closed
-                return Some((asset.to_string(), result));
-            }
-        }
-        None
+        let result = self.generators.get_mut(asset)?.append_tick(price, time)?;
+        // Candle closed
+        let _ = self.updates.send((asset.to_string(), result.clone()));
+        Some((asset.to_string(), result))
+    }
+
+    /// Process an `ohlc` update of the forming candle for a specific asset.
+    /// Returns Some((Asset, AnalysisResult)) when the update starts a new candle and the previous one closed.
+    pub fn process_ohlc(&self, asset: &str, candle: Candle) -> Option<(String, AnalysisResult)> {
+        let result = self.generators.get_mut(asset)?.fold_candle(candle)?;
+        let _ = self.updates.send((asset.to_string(), result.clone()));
+        Some((asset.to_string(), result))
     }
     
     /// Returns the current known status for an asset (based on latest analysis)
@@ -101,3 +312,184 @@ impl AnalysisManager {
             .collect()
     }
 }
+
+/// Per-asset 1m/5m/15m (or any set of) generators kept in sync from one tick feed.
+pub struct MultiTimeframeManager {
+    pub generators: Arc<DashMap<String, MultiTimeframeGenerator>>,
+    pub options: AnalysisOptions,
+    pub master_codes: Arc<Vec<CandleMasterCode>>,
+    pub granularities: Vec<u64>,
+}
+
+impl MultiTimeframeManager {
+    pub fn new(options: AnalysisOptions, master_codes: Vec<CandleMasterCode>, granularities: Vec<u64>) -> Self {
+        Self {
+            generators: Arc::new(DashMap::new()),
+            options,
+            master_codes: Arc::new(master_codes),
+            granularities,
+        }
+    }
+
+    /// Fetches history for every (asset, granularity) pair in parallel and seeds each timeframe.
+    /// Returns, per asset, the latest result of each timeframe or the first fetch error.
+    pub async fn initialize(&self, ws_url: &str, assets: Vec<String>) -> Vec<(String, Result<Vec<(u64, AnalysisResult)>, String>)> {
+        let mut tasks = Vec::new();
+
+        for asset in &assets {
+            for &granularity in &self.granularities {
+                let url = ws_url.to_string();
+                let asset_clone = asset.clone();
+                tasks.push(task::spawn(async move {
+                    let res = fetch_candles(&url, &asset_clone, 1000, granularity)
+                        .await
+                        .map_err(|e| e.to_string());
+                    (asset_clone, granularity, res)
+                }));
+            }
+        }
+
+        let mut fetched = Vec::new();
+        for task in tasks {
+            if let Ok(r) = task.await {
+                fetched.push(r);
+            }
+        }
+
+        let mut results = Vec::new();
+        for asset in assets {
+            let mut mtf = MultiTimeframeGenerator::new(
+                self.options.clone(),
+                self.master_codes.clone(),
+                &self.granularities,
+            );
+            let mut latest = Vec::new();
+            let mut error = None;
+
+            for (a, granularity, res) in fetched.iter_mut().filter(|(a, _, _)| *a == asset) {
+                match std::mem::replace(res, Ok(Vec::new())) {
+                    Ok(candles) => {
+                        if let Some(r) = mtf.seed_history(*granularity, candles) {
+                            latest.push((*granularity, r));
+                        }
+                    }
+                    Err(e) => {
+                        error.get_or_insert(format!("{} {}s: {}", a, granularity, e));
+                    }
+                }
+            }
+
+            self.generators.insert(asset.clone(), mtf);
+            latest.sort_by_key(|(g, _)| *g);
+
+            match error {
+                Some(e) => results.push((asset, Err(e))),
+                None => results.push((asset, Ok(latest))),
+            }
+        }
+
+        results
+    }
+
+    /// Feed a tick to every timeframe of `asset`.
+    /// Returns the `(granularity, AnalysisResult)` of each timeframe whose candle closed.
+    pub fn process_tick(&self, asset: &str, price: f64, time: u64) -> Vec<(u64, AnalysisResult)> {
+        match self.generators.get_mut(asset) {
+            Some(mut mtf) => mtf.append_tick(price, time),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_latest_analysis(&self, asset: &str, granularity: u64) -> Option<AnalysisResult> {
+        self.generators
+            .get(asset)
+            .and_then(|mtf| mtf.latest(granularity).cloned())
+    }
+
+    /// "Up"/"Down" when all timeframes of `asset` agree on the trend.
+    pub fn trend_agreement(&self, asset: &str) -> Option<&'static str> {
+        self.generators.get(asset).and_then(|mtf| mtf.trend_agreement())
+    }
+
+    /// True when every timeframe above the base one trends in `direction`.
+    pub fn higher_timeframes_agree(&self, asset: &str, direction: &str) -> bool {
+        self.generators
+            .get(asset)
+            .is_some_and(|mtf| mtf.higher_timeframes_agree(direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip_continues_identically() {
+        let manager = AnalysisManager::new(AnalysisOptions::default(), Vec::new());
+        let mut gen = AnalysisGenerator::new(manager.options.clone(), manager.master_codes.clone());
+        let candle = |i: u64| Candle {
+            time: i * 60,
+            open: 100.0 + (i as f64 * 0.3).sin(),
+            high: 101.0 + (i as f64 * 0.3).sin(),
+            low: 99.0 + (i as f64 * 0.3).sin(),
+            close: 100.0 + (i as f64 * 0.3).cos(),
+        };
+        for i in 0..300 {
+            gen.append_candle(candle(i));
+        }
+        manager.generators.insert("R_10".to_string(), gen.clone());
+
+        let dir = std::env::temp_dir().join(format!("indicator_math_snap_{}", std::process::id()));
+        assert_eq!(manager.save_snapshots(&dir).unwrap(), 1);
+        assert!(manager.save_snapshot(&dir, "R_10").unwrap());
+        assert!(!manager.save_snapshot(&dir, "R_25").unwrap());
+        assert!(!dir.join("R_25.json").exists());
+
+        let restored = AnalysisManager::new(AnalysisOptions::default(), Vec::new());
+        assert_eq!(restored.load_snapshots(&dir), vec!["R_10".to_string()]);
+        let mut resumed = restored.generators.get("R_10").unwrap().clone();
+        assert_eq!(resumed.last_candle_time(), Some(299 * 60));
+
+        let a = gen.append_candle(candle(300));
+        let b = resumed.append_candle(candle(300));
+        assert_eq!(serde_json::to_string(&a).unwrap(), serde_json::to_string(&b).unwrap());
+
+        let other = AnalysisOptions {
+            ema1_period: 9,
+            ..Default::default()
+        };
+        let mismatched = AnalysisManager::new(other, Vec::new());
+        assert!(mismatched.load_snapshot(&dir, "R_10").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_closed_candles_are_published() {
+        let manager = AnalysisManager::new(AnalysisOptions::default(), Vec::new());
+        manager.generators.insert(
+            "R_10".to_string(),
+            AnalysisGenerator::new(manager.options.clone(), manager.master_codes.clone()),
+        );
+        let mut rx = manager.subscribe();
+
+        assert!(manager.process_tick("R_10", 1.0, 0).is_none());
+        assert!(manager.process_tick("R_10", 2.0, 59).is_none());
+        assert!(manager.process_tick("R_99", 2.0, 59).is_none());
+        let (asset, closed) = manager.process_tick("R_10", 3.0, 60).unwrap();
+        assert_eq!(asset, "R_10");
+
+        let (published_asset, published) = rx.try_recv().unwrap();
+        assert_eq!(published_asset, "R_10");
+        assert_eq!(published.candletime, closed.candletime);
+        assert_eq!(published.close, 2.0);
+
+        // ohlc updates for the same bucket are merged, a new open_time closes the candle
+        let ohlc = |time, close| Candle { time, open: 3.0, high: close, low: 3.0, close };
+        assert!(manager.process_ohlc("R_10", ohlc(60, 4.0)).is_none());
+        assert!(manager.process_ohlc("R_10", ohlc(60, 5.0)).is_none());
+        let (_, closed) = manager.process_ohlc("R_10", ohlc(120, 5.5)).unwrap();
+        assert_eq!((closed.open, closed.high, closed.close), (3.0, 5.0, 5.0));
+        assert!(rx.try_recv().is_ok());
+    }
+}
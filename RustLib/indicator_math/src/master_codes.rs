@@ -0,0 +1,224 @@
+use crate::structs::CandleMasterCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+// Status descriptor: {emaLongAbove}-{emaMediumDir}-{emaLongDir}-{color}-{emaLongConvergence}
+// e.g. "L-D-D-G-C". The table is shared with the v1 crate, which uses the compact
+// "L-DD-G-C" spelling of the same descriptors.
+
+/// Versioned master-code table shared by both analysis libraries.
+pub const MASTER_CODES_JSON: &str = include_str!("../../../indicator_math/candle_master_codes.json");
+
+pub const EMA_LONG_ABOVE: [&str; 2] = ["L", "M"];
+pub const DIRECTIONS: [&str; 3] = ["D", "F", "U"];
+pub const COLORS: [&str; 3] = ["E", "G", "R"];
+pub const CONVERGENCE: [&str; 3] = ["C", "D", "N"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MasterCodeTable {
+    pub version: u32,
+    pub codes: Vec<CandleMasterCode>,
+}
+
+impl MasterCodeTable {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let table: MasterCodeTable = serde_json::from_str(json)?;
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// The table compiled into the library.
+    pub fn embedded() -> Self {
+        Self::from_json(MASTER_CODES_JSON).expect("embedded candle_master_codes.json is invalid")
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Load from `path`, falling back to the embedded table when the file is missing or invalid.
+    pub fn load_or_embedded(path: &Path) -> Self {
+        match Self::load(path) {
+            Ok(table) => table,
+            Err(e) => {
+                log::warn!("Using embedded master codes ({:?}: {})", path, e);
+                Self::embedded()
+            }
+        }
+    }
+
+    /// Rejects malformed descriptors and duplicate codes or descriptors.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut codes = HashSet::new();
+        let mut descs = HashSet::new();
+        for c in &self.codes {
+            if !is_valid_descriptor(&c.status_desc) {
+                return Err(anyhow::anyhow!("Invalid descriptor '{}'", c.status_desc));
+            }
+            if !codes.insert(c.status_code.as_str()) {
+                return Err(anyhow::anyhow!("Duplicate status code {}", c.status_code));
+            }
+            if !descs.insert(c.status_desc.as_str()) {
+                return Err(anyhow::anyhow!("Duplicate descriptor '{}'", c.status_desc));
+            }
+        }
+        Ok(())
+    }
+
+    /// Valid descriptors that have no code in this table.
+    pub fn missing_descriptors(&self) -> Vec<String> {
+        let mapped: HashSet<&str> = self.codes.iter().map(|c| c.status_desc.as_str()).collect();
+        all_descriptors()
+            .into_iter()
+            .filter(|d| !mapped.contains(d.as_str()))
+            .collect()
+    }
+
+    pub fn to_pretty_json(&self) -> String {
+        let mut out = format!("{{\n  \"version\": {},\n  \"codes\": [\n", self.version);
+        for (i, c) in self.codes.iter().enumerate() {
+            let sep = if i + 1 < self.codes.len() { "," } else { "" };
+            out.push_str(&format!(
+                "    {{ \"status_code\": \"{}\", \"status_desc\": \"{}\" }}{}\n",
+                c.status_code, c.status_desc, sep
+            ));
+        }
+        out.push_str("  ]\n}\n");
+        out
+    }
+}
+
+/// Every descriptor the generators can produce (2 x 3 x 3 x 3 x 3 = 162), in table order.
+pub fn all_descriptors() -> Vec<String> {
+    let mut out = Vec::with_capacity(162);
+    for above in EMA_LONG_ABOVE {
+        for medium in DIRECTIONS {
+            for long in DIRECTIONS {
+                for color in COLORS {
+                    for conv in CONVERGENCE {
+                        out.push(format!("{}-{}-{}-{}-{}", above, medium, long, color, conv));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+pub fn is_valid_descriptor(desc: &str) -> bool {
+    let parts: Vec<&str> = desc.split('-').collect();
+    parts.len() == 5
+        && EMA_LONG_ABOVE.contains(&parts[0])
+        && DIRECTIONS.contains(&parts[1])
+        && DIRECTIONS.contains(&parts[2])
+        && COLORS.contains(&parts[3])
+        && CONVERGENCE.contains(&parts[4])
+}
+
+/// Complete `base` so every valid descriptor has a code. Existing codes are kept as-is;
+/// missing descriptors get the next free numbers in `all_descriptors` order.
+pub fn generate_complete_table(base: &MasterCodeTable) -> MasterCodeTable {
+    let mut codes = base.codes.clone();
+    let next = codes
+        .iter()
+        .filter_map(|c| c.status_code.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    for (code, desc) in (next..).zip(base.missing_descriptors()) {
+        codes.push(CandleMasterCode {
+            status_code: code.to_string(),
+            status_desc: desc,
+        });
+    }
+    codes.sort_by_key(|c| c.status_code.parse::<u32>().unwrap_or(u32::MAX));
+    MasterCodeTable {
+        version: base.version + 1,
+        codes,
+    }
+}
+
+// Descriptors produced live that had no code, with how often they were seen
+static UNMAPPED: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+/// Called by the generator when a descriptor has no status code.
+pub fn record_unmapped(desc: &str) {
+    if let Ok(mut map) = UNMAPPED.lock() {
+        *map.entry(desc.to_string()).or_insert(0) += 1;
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UnmappedDescriptor {
+    pub status_desc: String,
+    pub count: usize,
+}
+
+/// Descriptors seen live but not mapped since process start, most frequent first.
+pub fn unmapped_report() -> Vec<UnmappedDescriptor> {
+    let mut report: Vec<UnmappedDescriptor> = UNMAPPED
+        .lock()
+        .map(|map| {
+            map.iter()
+                .map(|(d, c)| UnmappedDescriptor {
+                    status_desc: d.clone(),
+                    count: *c,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    report.sort_by_key(|r| std::cmp::Reverse(r.count));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_table_is_complete() {
+        let table = MasterCodeTable::embedded();
+        assert_eq!(table.codes.len(), all_descriptors().len());
+        assert!(table.missing_descriptors().is_empty());
+        let find = |d: &str| table.codes.iter().find(|c| c.status_desc == d).map(|c| c.status_code.as_str());
+        assert_eq!(find("L-D-D-G-C"), Some("2"));
+        assert_eq!(find("M-U-D-E-C"), Some("66"));
+        assert_eq!(find("M-U-U-E-D"), Some("77"));
+        assert_eq!(find("M-U-U-G-N"), Some("81"));
+    }
+
+    #[test]
+    fn test_generator_keeps_existing_codes_and_reproduces_file() {
+        let table = MasterCodeTable::embedded();
+        let legacy = MasterCodeTable {
+            version: 1,
+            codes: table.codes.iter().take(84).cloned().collect(),
+        };
+        let generated = generate_complete_table(&legacy);
+        assert_eq!(generated.version, table.version);
+        assert_eq!(
+            generated.to_pretty_json().replace("\r\n", "\n"),
+            MASTER_CODES_JSON.replace("\r\n", "\n")
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicates_and_bad_descriptors() {
+        let code = |c: &str, d: &str| CandleMasterCode {
+            status_code: c.to_string(),
+            status_desc: d.to_string(),
+        };
+        let dup = MasterCodeTable {
+            version: 1,
+            codes: vec![code("1", "L-D-D-G-C"), code("1", "L-D-D-G-D")],
+        };
+        assert!(dup.validate().is_err());
+        let bad = MasterCodeTable {
+            version: 1,
+            codes: vec![code("1", "L-DD-G-C")],
+        };
+        assert!(bad.validate().is_err());
+    }
+}
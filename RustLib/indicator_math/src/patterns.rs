@@ -0,0 +1,249 @@
+use crate::structs::Candle;
+use serde::{Deserialize, Serialize};
+
+// Candlestick pattern recognition over the last three closed candles.
+// Thresholds are fractions of the candle's high-low range, so they work on any symbol.
+
+/// Candles the generator keeps for pattern detection.
+pub const PATTERN_LOOKBACK: usize = 3;
+
+const DOJI_BODY: f64 = 0.1;
+const LONG_BODY: f64 = 0.5;
+const SMALL_BODY: f64 = 0.3;
+
+/// A recognized pattern ending at the current candle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CandlePattern {
+    /// "Doji", "Hammer", "ShootingStar", "BullishEngulfing", "BearishEngulfing",
+    /// "BullishHarami", "BearishHarami", "MorningStar", "EveningStar",
+    /// "ThreeWhiteSoldiers", "ThreeBlackCrows"
+    pub name: String,
+    /// "Bullish", "Bearish" or "Neutral"
+    pub direction: String,
+    /// Number of candles the pattern spans, including the current one
+    pub lookback: usize,
+    /// Time of the first candle of the pattern
+    pub start_time: u64,
+}
+
+fn body(c: &Candle) -> f64 {
+    (c.close - c.open).abs()
+}
+
+fn range(c: &Candle) -> f64 {
+    c.high - c.low
+}
+
+fn upper_wick(c: &Candle) -> f64 {
+    c.high - c.open.max(c.close)
+}
+
+fn lower_wick(c: &Candle) -> f64 {
+    c.open.min(c.close) - c.low
+}
+
+fn is_green(c: &Candle) -> bool {
+    c.close > c.open
+}
+
+fn is_red(c: &Candle) -> bool {
+    c.close < c.open
+}
+
+fn is_long(c: &Candle) -> bool {
+    range(c) > 0.0 && body(c) >= range(c) * LONG_BODY
+}
+
+fn is_doji(c: &Candle) -> bool {
+    range(c) > 0.0 && body(c) <= range(c) * DOJI_BODY
+}
+
+fn tag(name: &str, direction: &str, window: &[Candle]) -> CandlePattern {
+    CandlePattern {
+        name: name.to_string(),
+        direction: direction.to_string(),
+        lookback: window.len(),
+        start_time: window[0].time,
+    }
+}
+
+/// Patterns completed by the last candle of `recent` (oldest first, up to
+/// `PATTERN_LOOKBACK` candles). Single-candle shapes that need a prior move
+/// (hammer, shooting star) only fire when the previous candle moved the other way.
+pub fn detect(recent: &[Candle]) -> Vec<CandlePattern> {
+    let mut found = Vec::new();
+    let Some(c) = recent.last() else {
+        return found;
+    };
+    let n = recent.len();
+
+    if is_doji(c) {
+        found.push(tag("Doji", "Neutral", &recent[n - 1..]));
+    }
+
+    if n >= 2 {
+        let p = &recent[n - 2];
+        let w = &recent[n - 2..];
+        let b = body(c);
+        let r = range(c);
+        let small_upper = upper_wick(c) <= r * DOJI_BODY;
+        let small_lower = lower_wick(c) <= r * DOJI_BODY;
+        if r > 0.0 && !is_doji(c) {
+            if is_red(p) && lower_wick(c) >= 2.0 * b && small_upper {
+                found.push(tag("Hammer", "Bullish", w));
+            }
+            if is_green(p) && upper_wick(c) >= 2.0 * b && small_lower {
+                found.push(tag("ShootingStar", "Bearish", w));
+            }
+        }
+
+        if is_red(p) && is_green(c) && c.open <= p.close && c.close >= p.open && b > body(p) {
+            found.push(tag("BullishEngulfing", "Bullish", w));
+        }
+        if is_green(p) && is_red(c) && c.open >= p.close && c.close <= p.open && b > body(p) {
+            found.push(tag("BearishEngulfing", "Bearish", w));
+        }
+
+        // Current body sits inside a long previous body of the opposite colour
+        let inside =
+            c.open.max(c.close) < p.open.max(p.close) && c.open.min(c.close) > p.open.min(p.close);
+        if is_long(p) && inside {
+            if is_red(p) && is_green(c) {
+                found.push(tag("BullishHarami", "Bullish", w));
+            } else if is_green(p) && is_red(c) {
+                found.push(tag("BearishHarami", "Bearish", w));
+            }
+        }
+    }
+
+    if n >= 3 {
+        let w = &recent[n - 3..];
+        let (a, m) = (&w[0], &w[1]);
+        let star = body(m) <= body(a) * SMALL_BODY;
+        let a_mid = (a.open + a.close) / 2.0;
+        if is_long(a) && star {
+            if is_red(a) && is_green(c) && c.close > a_mid {
+                found.push(tag("MorningStar", "Bullish", w));
+            }
+            if is_green(a) && is_red(c) && c.close < a_mid {
+                found.push(tag("EveningStar", "Bearish", w));
+            }
+        }
+
+        let opens_in_body = |prev: &Candle, cur: &Candle| {
+            cur.open >= prev.open.min(prev.close) && cur.open <= prev.open.max(prev.close)
+        };
+        if w.iter().all(|x| is_green(x) && is_long(x))
+            && w.windows(2)
+                .all(|p| p[1].close > p[0].close && opens_in_body(&p[0], &p[1]))
+        {
+            found.push(tag("ThreeWhiteSoldiers", "Bullish", w));
+        }
+        if w.iter().all(|x| is_red(x) && is_long(x))
+            && w.windows(2)
+                .all(|p| p[1].close < p[0].close && opens_in_body(&p[0], &p[1]))
+        {
+            found.push(tag("ThreeBlackCrows", "Bearish", w));
+        }
+    }
+
+    found
+}
+
+/// Strategy filter over a comma-separated list of pattern names. An empty filter
+/// always passes; otherwise one of the listed patterns must be present and must not
+/// point against the trade (`bullish` = call side).
+pub fn passes_filter(patterns: &[CandlePattern], filter: &str, bullish: bool) -> bool {
+    let wanted: Vec<&str> = filter
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if wanted.is_empty() {
+        return true;
+    }
+    let against = if bullish { "Bearish" } else { "Bullish" };
+    patterns
+        .iter()
+        .any(|p| wanted.contains(&p.name.as_str()) && p.direction != against)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(time: u64, open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle {
+            time,
+            open,
+            high,
+            low,
+            close,
+        }
+    }
+
+    fn names(recent: &[Candle]) -> Vec<String> {
+        detect(recent).into_iter().map(|p| p.name).collect()
+    }
+
+    #[test]
+    fn test_single_and_two_candle_patterns() {
+        assert_eq!(names(&[c(0, 10.0, 11.0, 9.0, 10.05)]), vec!["Doji"]);
+
+        let down = c(0, 12.0, 12.2, 10.8, 11.0);
+        let hammer = c(60, 10.6, 10.85, 9.0, 10.8);
+        assert_eq!(names(&[down, hammer]), vec!["Hammer"]);
+
+        let up = c(0, 10.0, 11.2, 9.9, 11.0);
+        let star = c(60, 11.2, 13.0, 11.18, 11.4);
+        assert_eq!(names(&[up, star]), vec!["ShootingStar"]);
+
+        let engulf = c(60, 10.9, 12.3, 10.8, 12.2);
+        let found = detect(&[down, engulf]);
+        assert_eq!(found[0].name, "BullishEngulfing");
+        assert_eq!(found[0].lookback, 2);
+        assert_eq!(found[0].start_time, 0);
+
+        let harami = c(60, 11.2, 11.6, 11.1, 11.5);
+        assert_eq!(names(&[down, harami]), vec!["BullishHarami"]);
+    }
+
+    #[test]
+    fn test_three_candle_patterns() {
+        let a = c(0, 12.0, 12.1, 10.9, 11.0);
+        let m = c(60, 10.9, 11.0, 10.6, 10.8);
+        let b = c(120, 10.9, 11.9, 10.85, 11.8);
+        let found = detect(&[a, m, b]);
+        let star = found.iter().find(|p| p.name == "MorningStar").unwrap();
+        assert_eq!((star.lookback, star.start_time), (3, 0));
+
+        let soldiers = [
+            c(0, 10.0, 11.1, 9.95, 11.0),
+            c(60, 10.8, 12.1, 10.75, 12.0),
+            c(120, 11.7, 13.1, 11.65, 13.0),
+        ];
+        assert!(names(&soldiers).contains(&"ThreeWhiteSoldiers".to_string()));
+        let crows: Vec<Candle> = soldiers
+            .iter()
+            .map(|s| {
+                c(
+                    s.time,
+                    30.0 - s.open,
+                    30.0 - s.low,
+                    30.0 - s.high,
+                    30.0 - s.close,
+                )
+            })
+            .collect();
+        assert!(names(&crows).contains(&"ThreeBlackCrows".to_string()));
+    }
+
+    #[test]
+    fn test_passes_filter() {
+        let found = detect(&[c(0, 12.0, 12.2, 10.8, 11.0), c(60, 10.9, 12.3, 10.8, 12.2)]);
+        assert!(passes_filter(&found, "", false));
+        assert!(passes_filter(&found, "Hammer, BullishEngulfing", true));
+        assert!(!passes_filter(&found, "BullishEngulfing", false));
+        assert!(!passes_filter(&found, "Doji", true));
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// O(1) streaming moving averages used by the incremental generator.
+// Warm-up semantics follow the batch `wma`/`ema`/`hma`/`ehma` in the v1 crate:
+// a value is only produced once every stage has a full window.
+
+/// WMA over the last `period` values, kept as a plain sum and a weighted sum.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RollingWma {
+    pub period: usize,
+    pub window: VecDeque<f64>,
+    pub sum: f64,
+    pub weighted_sum: f64,
+}
+
+impl RollingWma {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            weighted_sum: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, value: f64) -> Option<f64> {
+        if self.window.len() == self.period {
+            // Every weight drops by one, the oldest falls to zero and leaves
+            self.weighted_sum += self.period as f64 * value - self.sum;
+            let oldest = self.window.pop_front().unwrap_or(0.0);
+            self.sum += value - oldest;
+        } else {
+            self.weighted_sum += (self.window.len() + 1) as f64 * value;
+            self.sum += value;
+        }
+        self.window.push_back(value);
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        if self.window.len() < self.period {
+            return None;
+        }
+        let denom = (self.period * (self.period + 1) / 2) as f64;
+        Some(self.weighted_sum / denom)
+    }
+}
+
+/// EMA seeded with the SMA of its first `period` values.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingEma {
+    pub period: usize,
+    pub k: f64,
+    pub count: usize,
+    pub seed_sum: f64,
+    pub value: Option<f64>,
+}
+
+impl StreamingEma {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            k: 2.0 / (period as f64 + 1.0),
+            count: 0,
+            seed_sum: 0.0,
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, value: f64) -> Option<f64> {
+        self.count += 1;
+        self.value = match self.value {
+            Some(prev) => Some(value * self.k + prev * (1.0 - self.k)),
+            None => {
+                self.seed_sum += value;
+                if self.count == self.period {
+                    Some(self.seed_sum / self.period as f64)
+                } else {
+                    None
+                }
+            }
+        };
+        self.value
+    }
+}
+
+fn hull_periods(period: usize) -> (usize, usize, usize) {
+    let period = period.max(1);
+    let half = (period / 2).max(1);
+    let sqrt = ((period as f64).sqrt().round() as usize).max(1);
+    (half, period, sqrt)
+}
+
+/// HMA(n) = WMA(2 * WMA(n/2) - WMA(n), round(sqrt(n)))
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingHma {
+    pub half: RollingWma,
+    pub full: RollingWma,
+    pub signal: RollingWma,
+}
+
+impl StreamingHma {
+    pub fn new(period: usize) -> Self {
+        let (half, full, sqrt) = hull_periods(period);
+        Self {
+            half: RollingWma::new(half),
+            full: RollingWma::new(full),
+            signal: RollingWma::new(sqrt),
+        }
+    }
+
+    pub fn update(&mut self, price: f64) -> Option<f64> {
+        let half = self.half.update(price);
+        let full = self.full.update(price);
+        match (half, full) {
+            (Some(h), Some(f)) => self.signal.update(2.0 * h - f),
+            _ => None,
+        }
+    }
+}
+
+/// EHMA(n) = EMA(2 * EMA(n/2) - EMA(n), round(sqrt(n)))
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamingEhma {
+    pub half: StreamingEma,
+    pub full: StreamingEma,
+    pub signal: StreamingEma,
+}
+
+impl StreamingEhma {
+    pub fn new(period: usize) -> Self {
+        let (half, full, sqrt) = hull_periods(period);
+        Self {
+            half: StreamingEma::new(half),
+            full: StreamingEma::new(full),
+            signal: StreamingEma::new(sqrt),
+        }
+    }
+
+    pub fn update(&mut self, price: f64) -> Option<f64> {
+        let half = self.half.update(price);
+        let full = self.full.update(price);
+        match (half, full) {
+            (Some(h), Some(f)) => self.signal.update(2.0 * h - f),
+            _ => None,
+        }
+    }
+}
+
+/// Per-line moving average state, selected by the "EMA"/"HMA"/"EHMA" type string.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum MaState {
+    /// Plain EMA, carried by the generator's `last_ema_N` / `ema_N_k`.
+    Ema,
+    Hma(StreamingHma),
+    Ehma(StreamingEhma),
+}
+
+impl MaState {
+    pub fn new(ma_type: &str, period: usize) -> Self {
+        match ma_type {
+            "HMA" => MaState::Hma(StreamingHma::new(period)),
+            "EHMA" => MaState::Ehma(StreamingEhma::new(period)),
+            _ => MaState::Ema,
+        }
+    }
+
+    /// Advance by one close. Hull types fall back to the price until warmed up.
+    pub fn update(&mut self, price: f64, last_ema: f64, ema_k: f64) -> f64 {
+        match self {
+            MaState::Ema => price * ema_k + last_ema * (1.0 - ema_k),
+            MaState::Hma(h) => h.update(price).unwrap_or(price),
+            MaState::Ehma(h) => h.update(price).unwrap_or(price),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicator_math_v1::{hma, wma, Candle as V1Candle};
+
+    fn prices(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64;
+                100.0 + (t * 0.13).sin() * 5.0 + (t * 0.031).cos() * 12.0 + t * 0.01
+            })
+            .collect()
+    }
+
+    fn candles(prices: &[f64]) -> Vec<V1Candle> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| V1Candle {
+                time: i as u64 * 60,
+                open: p,
+                high: p,
+                low: p,
+                close: p,
+            })
+            .collect()
+    }
+
+    fn assert_series(streamed: &[Option<f64>], batch: &[f64]) {
+        assert_eq!(streamed.len(), batch.len());
+        for (i, (s, b)) in streamed.iter().zip(batch).enumerate() {
+            match s {
+                Some(v) => assert!((v - b).abs() < 1e-9, "index {}: {} vs {}", i, v, b),
+                None => assert!(b.is_nan(), "index {}: warm-up mismatch ({})", i, b),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rolling_wma_matches_batch() {
+        let p = prices(400);
+        let batch: Vec<f64> = wma(&candles(&p), 20).iter().map(|v| v.value).collect();
+        let mut w = RollingWma::new(20);
+        let streamed: Vec<Option<f64>> = p.iter().map(|&x| w.update(x)).collect();
+        assert_series(&streamed, &batch);
+    }
+
+    #[test]
+    fn test_streaming_hma_matches_batch() {
+        let p = prices(1000);
+        for period in [9, 20, 50, 200] {
+            let batch: Vec<f64> = hma(&candles(&p), period).iter().map(|v| v.value).collect();
+            let mut h = StreamingHma::new(period);
+            let streamed: Vec<Option<f64>> = p.iter().map(|&x| h.update(x)).collect();
+            assert_series(&streamed, &batch);
+        }
+    }
+
+    #[test]
+    fn test_streaming_ehma_matches_warm_batch() {
+        // v1 `ehma` seeds its final EMA over the NaN warm-up of the raw series, so it
+        // never produces a value for period > 1. Compare against the same v1 EMA chain
+        // applied to the valid part of the raw series instead.
+        let p = prices(1000);
+        for period in [9, 20, 50, 200] {
+            let mut h = StreamingEhma::new(period);
+            let streamed: Vec<Option<f64>> = p.iter().map(|&x| h.update(x)).collect();
+            let (half, full, sqrt) = hull_periods(period);
+            let e_half = indicator_math_v1::ema(&candles(&p), half);
+            let e_full = indicator_math_v1::ema(&candles(&p), full);
+            let raw: Vec<f64> = e_half
+                .iter()
+                .zip(&e_full)
+                .map(|(h, f)| 2.0 * h.value - f.value)
+                .filter(|v| !v.is_nan())
+                .collect();
+            let valid_from = p.len() - raw.len();
+            let signal = indicator_math_v1::ema(&candles(&raw), sqrt);
+
+            let mut batch = vec![f64::NAN; valid_from];
+            batch.extend(signal.iter().map(|v| v.value));
+            assert_series(&streamed, &batch);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::divergence::Divergence;
+use crate::patterns::CandlePattern;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -9,7 +11,7 @@ pub struct Candle {
     pub close: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AnalysisOptions {
     pub ema1_period: usize,
     pub ema1_type: String, // "EMA", "HMA", "EHMA"
@@ -25,6 +27,62 @@ pub struct AnalysisOptions {
     pub rsi_period: usize,
     pub flat_threshold: f64,
     pub macd_narrow: f64,
+    /// Candle period in seconds (60 = 1m, 300 = 5m, 900 = 15m, 3600 = 1h)
+    #[serde(default = "default_granularity")]
+    pub granularity: u64,
+    /// Stochastic / MACD / SuperTrend / PSAR / channel settings
+    #[serde(default)]
+    pub indicators: IndicatorOptions,
+}
+
+fn default_granularity() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct IndicatorOptions {
+    pub stoch_k_period: usize,
+    pub stoch_d_period: usize,
+    pub macd_fast: usize,
+    pub macd_slow: usize,
+    pub macd_signal: usize,
+    pub supertrend_period: usize,
+    pub supertrend_multiplier: f64,
+    pub psar_step: f64,
+    pub psar_max_step: f64,
+    pub donchian_period: usize,
+    pub keltner_period: usize,
+    pub keltner_atr_period: usize,
+    pub keltner_multiplier: f64,
+    pub williams_period: usize,
+    /// Candles on each side a swing high/low must exceed
+    pub divergence_swing_strength: usize,
+    /// Swings further apart than this are not compared
+    pub divergence_max_distance: usize,
+}
+
+impl Default for IndicatorOptions {
+    fn default() -> Self {
+        Self {
+            stoch_k_period: 14,
+            stoch_d_period: 3,
+            macd_fast: 12,
+            macd_slow: 26,
+            macd_signal: 9,
+            supertrend_period: 10,
+            supertrend_multiplier: 3.0,
+            psar_step: 0.02,
+            psar_max_step: 0.2,
+            donchian_period: 20,
+            keltner_period: 20,
+            keltner_atr_period: 10,
+            keltner_multiplier: 2.0,
+            williams_period: 14,
+            divergence_swing_strength: 3,
+            divergence_max_distance: 60,
+        }
+    }
 }
 
 impl Default for AnalysisOptions {
@@ -44,6 +102,8 @@ impl Default for AnalysisOptions {
             rsi_period: 14,
             flat_threshold: 0.2, // Adjust scaling if needed (JS uses raw values usually)
             macd_narrow: 0.15,
+            granularity: default_granularity(),
+            indicators: IndicatorOptions::default(),
         }
     }
 }
@@ -64,7 +124,7 @@ impl AnalysisOptions {
         let configs: Vec<MaConfigHelper> = serde_json::from_str(json_str)?;
         let mut opts = base_options.unwrap_or_default();
 
-        if configs.len() >= 1 {
+        if !configs.is_empty() {
             opts.ema1_type = configs[0].ma_type.clone();
             opts.ema1_period = configs[0].period;
         }
@@ -157,6 +217,30 @@ pub struct AnalysisResult {
     pub up_con_long_ema: usize,
     pub down_con_long_ema: usize,
 
+    // Additional indicators (None until each has warmed up)
+    #[serde(default)]
+    pub stochastic: StochasticValues,
+    #[serde(default)]
+    pub macd: MacdValues,
+    #[serde(default)]
+    pub supertrend: TrendLevel,
+    #[serde(default)]
+    pub psar: TrendLevel,
+    #[serde(default)]
+    pub donchian: ChannelValues,
+    #[serde(default)]
+    pub keltner: ChannelValues,
+    #[serde(default)]
+    pub williams_r: Option<f64>,
+
+    // Candlestick patterns completed by this candle
+    #[serde(default)]
+    pub patterns: Vec<CandlePattern>,
+
+    // RSI / MACD divergences confirmed by this candle (swings lie `divergence_swing_strength` back)
+    #[serde(default)]
+    pub divergences: Vec<Divergence>,
+
     // Status
     pub is_mark: String, // "n"
     pub status_code: String,
@@ -169,6 +253,17 @@ pub struct AnalysisResult {
     pub loss_con: usize,
 }
 
+impl AnalysisResult {
+    pub fn has_pattern(&self, name: &str) -> bool {
+        self.patterns.iter().any(|p| p.name == name)
+    }
+
+    /// First divergence confirmed on this candle in `direction` ("Bullish"/"Bearish").
+    pub fn divergence(&self, direction: &str) -> Option<&Divergence> {
+        self.divergences.iter().find(|d| d.direction == direction)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BBValues {
     pub upper: Option<f64>,
@@ -176,6 +271,34 @@ pub struct BBValues {
     pub lower: Option<f64>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StochasticValues {
+    pub k: Option<f64>,
+    pub d: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MacdValues {
+    pub macd: Option<f64>,
+    pub signal: Option<f64>,
+    pub histogram: Option<f64>,
+}
+
+/// A stop/trail level with the trend it belongs to ("Up", "Down").
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TrendLevel {
+    pub value: Option<f64>,
+    pub trend: Option<String>,
+}
+
+/// Donchian / Keltner style upper-middle-lower channel.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ChannelValues {
+    pub upper: Option<f64>,
+    pub middle: Option<f64>,
+    pub lower: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CandleMasterCode {
     pub status_code: String, // Using String instead of number to match flexibility, though prompt said 1, 2
@@ -0,0 +1,189 @@
+use crate::generator::AnalysisGenerator;
+use crate::structs::{AnalysisOptions, AnalysisResult, Candle, CandleMasterCode};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// 1m / 5m / 15m
+pub const STANDARD_GRANULARITIES: [u64; 3] = [60, 300, 900];
+
+/// Trend of a single result, taken from the medium/long EMA relation.
+/// "MediumAbove" => "Up", "LongAbove" => "Down".
+pub fn trend_of(result: &AnalysisResult) -> Option<&'static str> {
+    match result.ema_long_above.as_deref() {
+        Some("MediumAbove") => Some("Up"),
+        Some("LongAbove") => Some("Down"),
+        _ => None,
+    }
+}
+
+/// One generator per candle period for the same asset, fed from the same tick stream
+/// so every timeframe stays aligned on the same bucket boundaries.
+#[derive(Clone)]
+pub struct MultiTimeframeGenerator {
+    pub generators: BTreeMap<u64, AnalysisGenerator>,
+}
+
+impl MultiTimeframeGenerator {
+    pub fn new(
+        options: AnalysisOptions,
+        master_codes: Arc<Vec<CandleMasterCode>>,
+        granularities: &[u64],
+    ) -> Self {
+        let mut generators = BTreeMap::new();
+        for &granularity in granularities {
+            let mut opts = options.clone();
+            opts.granularity = granularity.max(1);
+            generators.insert(
+                opts.granularity,
+                AnalysisGenerator::new(opts, master_codes.clone()),
+            );
+        }
+        Self { generators }
+    }
+
+    /// 1m / 5m / 15m set.
+    pub fn standard(options: AnalysisOptions, master_codes: Arc<Vec<CandleMasterCode>>) -> Self {
+        Self::new(options, master_codes, &STANDARD_GRANULARITIES)
+    }
+
+    pub fn granularities(&self) -> Vec<u64> {
+        self.generators.keys().copied().collect()
+    }
+
+    /// Smallest candle period (the one closed candles are usually fed at).
+    pub fn base_granularity(&self) -> Option<u64> {
+        self.generators.keys().next().copied()
+    }
+
+    pub fn generator(&self, granularity: u64) -> Option<&AnalysisGenerator> {
+        self.generators.get(&granularity)
+    }
+
+    /// Replay history fetched at `granularity` into that timeframe's generator.
+    pub fn seed_history(&mut self, granularity: u64, candles: Vec<Candle>) -> Option<AnalysisResult> {
+        let gen = self.generators.get_mut(&granularity)?;
+        let mut last_result = None;
+        for candle in candles {
+            last_result = Some(gen.append_candle(candle));
+        }
+        last_result
+    }
+
+    /// Feed a tick to every timeframe. Returns `(granularity, result)` for each
+    /// timeframe whose candle closed on this tick, smallest period first.
+    pub fn append_tick(&mut self, price: f64, time: u64) -> Vec<(u64, AnalysisResult)> {
+        self.generators
+            .iter_mut()
+            .filter_map(|(&g, gen)| gen.append_tick(price, time).map(|r| (g, r)))
+            .collect()
+    }
+
+    /// Feed a closed candle of the base timeframe. The base generator analyses it
+    /// directly; higher timeframes aggregate it and close as soon as the candle
+    /// completes their bucket, without waiting for the next bucket's first input.
+    pub fn append_candle(&mut self, candle: Candle) -> Vec<(u64, AnalysisResult)> {
+        let Some(base) = self.base_granularity() else {
+            return Vec::new();
+        };
+        let mut closed = Vec::new();
+        for (&g, gen) in self.generators.iter_mut() {
+            if g == base {
+                closed.push((g, gen.append_candle(candle)));
+                continue;
+            }
+            if let Some(r) = gen.fold_candle(candle) {
+                closed.push((g, r));
+            }
+            let bucket_end = (candle.time / g) * g + g;
+            if candle.time + base >= bucket_end {
+                if let Some(r) = gen.close_current_candle() {
+                    closed.push((g, r));
+                }
+            }
+        }
+        closed
+    }
+
+    pub fn latest(&self, granularity: u64) -> Option<&AnalysisResult> {
+        self.generators
+            .get(&granularity)
+            .and_then(|g| g.state.last_analysis.as_ref())
+    }
+
+    /// "Up"/"Down" when every timeframe has a closed candle and all of them agree.
+    pub fn trend_agreement(&self) -> Option<&'static str> {
+        let mut agreed = None;
+        for gen in self.generators.values() {
+            let trend = trend_of(gen.state.last_analysis.as_ref()?)?;
+            match agreed {
+                None => agreed = Some(trend),
+                Some(t) if t != trend => return None,
+                _ => {}
+            }
+        }
+        agreed
+    }
+
+    /// True when every timeframe above the base one trends in `direction` ("Up"/"Down").
+    /// Lets a strategy trading the base timeframe require higher-timeframe confirmation.
+    pub fn higher_timeframes_agree(&self, direction: &str) -> bool {
+        self.generators.values().skip(1).all(|gen| {
+            gen.state
+                .last_analysis
+                .as_ref()
+                .and_then(trend_of)
+                .is_some_and(|t| t == direction)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(time: u64, close: f64) -> Candle {
+        Candle {
+            time,
+            open: close - 0.5,
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+        }
+    }
+
+    #[test]
+    fn test_higher_timeframe_closes_with_last_base_candle() {
+        let mut mtf =
+            MultiTimeframeGenerator::standard(AnalysisOptions::default(), Arc::new(Vec::new()));
+        let mut closed_5m = Vec::new();
+        for i in 0..10u64 {
+            for (g, r) in mtf.append_candle(candle(i * 60, 100.0 + i as f64)) {
+                if g == 300 {
+                    closed_5m.push(r);
+                }
+            }
+        }
+        assert_eq!(closed_5m.len(), 2);
+        assert_eq!(closed_5m[0].candletime, 0);
+        assert_eq!(closed_5m[0].open, 99.5);
+        assert_eq!(closed_5m[0].close, 104.0);
+        assert_eq!(closed_5m[0].high, 105.0);
+        assert_eq!(closed_5m[1].candletime, 300);
+        assert!(mtf.latest(900).is_none());
+    }
+
+    #[test]
+    fn test_ticks_bucket_by_granularity() {
+        let mut mtf = MultiTimeframeGenerator::new(
+            AnalysisOptions::default(),
+            Arc::new(Vec::new()),
+            &[60, 300],
+        );
+        assert!(mtf.append_tick(1.0, 10).is_empty());
+        assert_eq!(mtf.append_tick(2.0, 290).len(), 1);
+        let closed = mtf.append_tick(3.0, 300);
+        let periods: Vec<u64> = closed.iter().map(|(g, _)| *g).collect();
+        assert_eq!(periods, vec![60, 300]);
+        assert_eq!(closed[1].1.close, 2.0);
+    }
+}
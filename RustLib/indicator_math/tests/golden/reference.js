@@ -0,0 +1,80 @@
+// Runs indicator_math/clsAnalysisGenerator.js (the reference implementation) over recorded
+// candles and writes the normalized fields compared by tests/parity.rs.
+//
+//   node tests/golden/reference.js ../../tickhistory/R_10/R_10_2026-01-06_to_2026-01-07.json 2000 \
+//       > tests/golden/R_10.js.json
+
+const fs = require('fs');
+const path = require('path');
+const AnalysisGenerator = require(path.join(__dirname, '../../../../indicator_math/clsAnalysisGenerator.js'));
+
+const [source, countArg] = process.argv.slice(2);
+const count = parseInt(countArg || '600', 10);
+
+const num = (v) => (typeof v === 'string' ? parseFloat(v) : v);
+const candles = JSON.parse(fs.readFileSync(source, 'utf8'))
+    .slice(0, count)
+    .map((c) => ({
+        time: c.epoch,
+        open: num(c.open),
+        high: num(c.high),
+        low: num(c.low),
+        close: num(c.close),
+    }));
+
+const console_warn = console.warn;
+console.warn = () => {};
+const analysis = new AnalysisGenerator(candles).generate();
+console.warn = console_warn;
+
+// "L-DD-G-C" -> "L-D-D-G-C"
+const fullDesc = (d) => {
+    const [above, dirs, color, conv] = d.split('-');
+    return [above, dirs[0], dirs[1], color, conv].join('-');
+};
+const float = (v) => (v === null || v === undefined || Number.isNaN(Number(v)) ? null : Number(v));
+
+const rows = analysis.map((a) => ({
+    candletime: a.candletime,
+    color: a.color,
+    ema_short_value: float(a.emaShortValue),
+    ema_medium_value: float(a.emaMediumValue),
+    ema_long_value: float(a.emaLongValue),
+    ema_short_direction: a.emaShortDirection,
+    ema_medium_direction: a.emaMediumDirection,
+    ema_long_direction: a.emaLongDirection,
+    ema_short_turn_type: a.emaShortTurnType,
+    ema_above: a.emaAbove,
+    ema_long_above: a.emaLongAbove,
+    macd_12: float(a.macd12),
+    macd_23: float(a.macd23),
+    ema_long_convergence_type: a.emaLongConvergenceType,
+    ema_cut_position: a.emaCutPosition,
+    ema_cut_long_type: a.emaCutLongType,
+    candles_since_ema_cut: a.candlesSinceEmaCut,
+    up_con_medium_ema: a.UpConMediumEMA,
+    down_con_medium_ema: a.DownConMediumEMA,
+    up_con_long_ema: a.UpConLongEMA,
+    down_con_long_ema: a.DownConLongEMA,
+    atr: float(a.atr),
+    rsi_value: float(a.rsiValue),
+    adx_value: float(a.adxValue),
+    choppy_indicator: float(a.choppyIndicator),
+    bb_upper: float(a.bbValues.upper),
+    bb_middle: float(a.bbValues.middle),
+    bb_lower: float(a.bbValues.lower),
+    bb_position: a.bbPosition,
+    is_abnormal_candle: a.isAbnormalCandle,
+    is_abnormal_atr: a.isAbnormalATR,
+    body_percent: float(a.bodyPercent),
+    u_wick_percent: float(a.uWickPercent),
+    l_wick_percent: float(a.lWickPercent),
+    status_desc: fullDesc(a.StatusDesc),
+}));
+
+const lines = rows.map((r) => '    ' + JSON.stringify(r));
+process.stdout.write(
+    '{\n  "source": ' + JSON.stringify(path.basename(source)) +
+    ',\n  "candles": ' + rows.length +
+    ',\n  "rows": [\n' + lines.join(',\n') + '\n  ]\n}\n'
+);
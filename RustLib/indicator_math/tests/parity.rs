@@ -0,0 +1,365 @@
+// Parity harness: runs the v1 batch engine, the v2 streaming engine and the JS reference
+// (pre-computed by tests/golden/reference.js) over the same recorded candles and compares
+// them row by row, field by field.
+//
+// Known differences are recorded in tests/golden/R_10.known_differences.json as the exact
+// rows where a field may differ, with the reason. The test fails when a field differs on a
+// row that is not listed, and when a listed row no longer differs. Run with UPDATE_GOLDEN=1
+// to rewrite the row lists after an intentional change; reasons are kept, and a new field
+// gets an empty reason that has to be filled in before the test passes.
+
+use indicator_math::{AnalysisGenerator, AnalysisOptions, AnalysisResult, Candle};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type Row = BTreeMap<String, Value>;
+
+// JS rounds these to 2 decimals, everything else to 5
+const TWO_DECIMAL_FIELDS: [&str; 6] = [
+    "rsi_value",
+    "adx_value",
+    "choppy_indicator",
+    "body_percent",
+    "u_wick_percent",
+    "l_wick_percent",
+];
+
+#[derive(Debug)]
+struct Mismatch {
+    index: usize,
+    expected: Value,
+    actual: Value,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct KnownDifference {
+    rows: Vec<String>,
+    reason: String,
+}
+
+type Known = BTreeMap<String, BTreeMap<String, KnownDifference>>;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn read_json(path: &Path) -> Value {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{:?}: {}", path, e))
+}
+
+fn load_candles(source: &str, count: usize) -> Vec<Candle> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../tickhistory/R_10")
+        .join(source);
+    let num = |v: &Value| v.as_f64().or_else(|| v.as_str()?.parse().ok()).unwrap_or(f64::NAN);
+    read_json(&path)
+        .as_array()
+        .expect("candle file must be an array")
+        .iter()
+        .take(count)
+        .map(|c| Candle {
+            time: c["epoch"].as_u64().unwrap_or(0),
+            open: num(&c["open"]),
+            high: num(&c["high"]),
+            low: num(&c["low"]),
+            close: num(&c["close"]),
+        })
+        .collect()
+}
+
+fn float(v: Option<f64>) -> Value {
+    match v {
+        Some(x) if x.is_finite() => json!(x),
+        _ => Value::Null,
+    }
+}
+
+// "L-DD-G-C" -> "L-D-D-G-C"
+fn full_desc(desc: &str) -> String {
+    let parts: Vec<&str> = desc.split('-').collect();
+    if parts.len() != 4 || parts[1].len() != 2 {
+        return desc.to_string();
+    }
+    let (medium, long) = parts[1].split_at(1);
+    format!("{}-{}-{}-{}-{}", parts[0], medium, long, parts[2], parts[3])
+}
+
+fn v2_row(r: &AnalysisResult) -> Row {
+    let row = json!({
+        "candletime": r.candletime,
+        "color": r.color,
+        "ema_short_value": float(r.ema_short_value),
+        "ema_medium_value": float(r.ema_medium_value),
+        "ema_long_value": float(r.ema_long_value),
+        "ema_short_direction": r.ema_short_direction,
+        "ema_medium_direction": r.ema_medium_direction,
+        "ema_long_direction": r.ema_long_direction,
+        "ema_short_turn_type": r.ema_short_turn_type,
+        "ema_above": r.ema_above,
+        "ema_long_above": r.ema_long_above,
+        "macd_12": float(r.macd_12),
+        "macd_23": float(r.macd_23),
+        "ema_long_convergence_type": r.ema_long_convergence_type,
+        "ema_cut_position": r.ema_cut_position,
+        "ema_cut_long_type": r.ema_cut_long_type,
+        "candles_since_ema_cut": r.candles_since_ema_cut,
+        "up_con_medium_ema": r.up_con_medium_ema,
+        "down_con_medium_ema": r.down_con_medium_ema,
+        "up_con_long_ema": r.up_con_long_ema,
+        "down_con_long_ema": r.down_con_long_ema,
+        "atr": float(r.atr),
+        "rsi_value": float(r.rsi_value),
+        "adx_value": float(r.adx_value),
+        "choppy_indicator": float(r.choppy_indicator),
+        "bb_upper": float(r.bb_values.upper),
+        "bb_middle": float(r.bb_values.middle),
+        "bb_lower": float(r.bb_values.lower),
+        "bb_position": r.bb_position,
+        "is_abnormal_candle": r.is_abnormal_candle,
+        "is_abnormal_atr": r.is_abnormal_atr,
+        "body_percent": r.body_percent,
+        "u_wick_percent": r.u_wick_percent,
+        "l_wick_percent": r.l_wick_percent,
+        "status_desc": r.status_desc,
+    });
+    serde_json::from_value(row).unwrap()
+}
+
+fn v1_row(a: &indicator_math_v1::FullAnalysis) -> Row {
+    let row = json!({
+        "candletime": a.candle_time,
+        "color": a.color,
+        "ema_short_value": float(a.ema_short_value),
+        "ema_medium_value": float(a.ema_medium_value),
+        "ema_long_value": float(a.ema_long_value),
+        "ema_short_direction": a.ema_short_direction,
+        "ema_medium_direction": a.ema_medium_direction,
+        "ema_long_direction": a.ema_long_direction,
+        "ema_short_turn_type": a.ema_short_turn_type,
+        "ema_above": a.ema_above,
+        "ema_long_above": a.ema_long_above,
+        "macd_12": float(a.macd_12),
+        "macd_23": float(a.macd_23),
+        "ema_long_convergence_type": a.ema_long_convergence_type,
+        "ema_cut_position": a.ema_cut_position,
+        "ema_cut_long_type": a.ema_cut_long_type,
+        "candles_since_ema_cut": a.candles_since_ema_cut,
+        "up_con_medium_ema": a.up_con_medium_ema,
+        "down_con_medium_ema": a.down_con_medium_ema,
+        "up_con_long_ema": a.up_con_long_ema,
+        "down_con_long_ema": a.down_con_long_ema,
+        "atr": float(a.atr),
+        "rsi_value": float(a.rsi_value),
+        "adx_value": float(a.adx_value),
+        "choppy_indicator": float(a.choppy_indicator),
+        "bb_upper": float(a.bb_upper),
+        "bb_middle": float(a.bb_middle),
+        "bb_lower": float(a.bb_lower),
+        "bb_position": a.bb_position.as_str(),
+        "is_abnormal_candle": a.is_abnormal_candle,
+        "is_abnormal_atr": a.is_abnormal_atr,
+        "body_percent": a.body_percent,
+        "u_wick_percent": a.upper_wick_percent,
+        "l_wick_percent": a.lower_wick_percent,
+        "status_desc": full_desc(&a.status_desc),
+    });
+    serde_json::from_value(row).unwrap()
+}
+
+fn values_match(field: &str, expected: &Value, actual: &Value) -> bool {
+    match (expected.as_f64(), actual.as_f64()) {
+        (Some(e), Some(a)) => {
+            let tolerance = if TWO_DECIMAL_FIELDS.contains(&field) {
+                0.005
+            } else {
+                0.000_005
+            };
+            (e - a).abs() <= tolerance + 1e-9
+        }
+        _ => expected == actual,
+    }
+}
+
+fn compare(expected: &[Row], actual: &[Row]) -> BTreeMap<String, Vec<Mismatch>> {
+    let mut report: BTreeMap<String, Vec<Mismatch>> = BTreeMap::new();
+    if expected.len() != actual.len() {
+        report.entry("row_count".to_string()).or_default().push(Mismatch {
+            index: 0,
+            expected: json!(expected.len()),
+            actual: json!(actual.len()),
+        });
+    }
+    for (index, (e, a)) in expected.iter().zip(actual).enumerate() {
+        for (field, ev) in e {
+            let av = a.get(field).unwrap_or(&Value::Null);
+            if !values_match(field, ev, av) {
+                report.entry(field.clone()).or_default().push(Mismatch {
+                    index,
+                    expected: ev.clone(),
+                    actual: av.clone(),
+                });
+            }
+        }
+    }
+    report
+}
+
+// [0, 1, 2, 5] -> ["0-2", "5"]
+fn to_ranges(rows: &BTreeSet<usize>) -> Vec<String> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &row in rows {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == row => *end = row,
+            _ => ranges.push((row, row)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect()
+}
+
+fn from_ranges(ranges: &[String]) -> BTreeSet<usize> {
+    let mut rows = BTreeSet::new();
+    for range in ranges {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start.parse().unwrap_or_else(|_| panic!("bad row range {:?}", range));
+        let end: usize = end.parse().unwrap_or_else(|_| panic!("bad row range {:?}", range));
+        rows.extend(start..=end);
+    }
+    rows
+}
+
+fn print_report(name: &str, report: &BTreeMap<String, Vec<Mismatch>>) {
+    println!("== {} ==", name);
+    if report.is_empty() {
+        println!("  no mismatches");
+    }
+    for (field, mismatches) in report {
+        let first = &mismatches[0];
+        println!(
+            "  {:<28} {:>5} mismatches, first at row {} (expected {}, got {})",
+            field,
+            mismatches.len(),
+            first.index,
+            first.expected,
+            first.actual
+        );
+    }
+}
+
+#[test]
+fn test_v1_v2_js_parity() {
+    let golden = read_json(&golden_dir().join("R_10.js.json"));
+    let source = golden["source"].as_str().unwrap();
+    let count = golden["candles"].as_u64().unwrap() as usize;
+    let js: Vec<Row> = serde_json::from_value(golden["rows"].clone()).unwrap();
+
+    let candles = load_candles(source, count);
+    assert_eq!(candles.len(), count, "recorded candle file is shorter than the golden data");
+
+    let v1_candles: Vec<indicator_math_v1::Candle> = candles
+        .iter()
+        .map(|c| indicator_math_v1::Candle {
+            time: c.time,
+            open: c.open,
+            high: c.high,
+            low: c.low,
+            close: c.close,
+        })
+        .collect();
+    let mut v1 = indicator_math_v1::AnalysisGenerator::new(
+        v1_candles,
+        indicator_math_v1::AnalysisOptions::default(),
+    );
+    let v1_rows: Vec<Row> = v1.generate().iter().map(v1_row).collect();
+
+    let mut v2 = AnalysisGenerator::new(AnalysisOptions::default(), Arc::new(Vec::new()));
+    let v2_rows: Vec<Row> = candles.iter().map(|&c| v2_row(&v2.append_candle(c))).collect();
+
+    let reports = [
+        ("v1_vs_js", compare(&js, &v1_rows)),
+        ("v2_vs_js", compare(&js, &v2_rows)),
+        ("v1_vs_v2", compare(&v1_rows, &v2_rows)),
+    ];
+    for (name, report) in &reports {
+        print_report(name, report);
+    }
+
+    let known_path = golden_dir().join("R_10.known_differences.json");
+    let mut known: Known = serde_json::from_value(read_json(&known_path)).unwrap();
+
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|v| v == "1") {
+        let mut updated = Known::new();
+        for (name, report) in &reports {
+            let old = known.get_mut(*name);
+            let fields = updated.entry(name.to_string()).or_default();
+            for (field, mismatches) in report {
+                let reason = old
+                    .as_ref()
+                    .and_then(|o| o.get(field))
+                    .map(|k| k.reason.clone())
+                    .unwrap_or_default();
+                let rows = mismatches.iter().map(|m| m.index).collect();
+                fields.insert(
+                    field.clone(),
+                    KnownDifference {
+                        rows: to_ranges(&rows),
+                        reason,
+                    },
+                );
+            }
+        }
+        let text = serde_json::to_string_pretty(&updated).unwrap() + "\n";
+        std::fs::write(&known_path, text).unwrap();
+        return;
+    }
+
+    let mut failures = Vec::new();
+    for (name, report) in &reports {
+        let known_fields = known.remove(*name).unwrap_or_default();
+        for (field, mismatches) in report {
+            let allowed = known_fields
+                .get(field)
+                .map(|k| from_ranges(&k.rows))
+                .unwrap_or_default();
+            for m in mismatches.iter().filter(|m| !allowed.contains(&m.index)) {
+                failures.push(format!(
+                    "{} {} row {}: expected {}, got {}",
+                    name, field, m.index, m.expected, m.actual
+                ));
+            }
+        }
+        for (field, k) in &known_fields {
+            if k.reason.trim().is_empty() {
+                failures.push(format!("{} {}: known difference has no reason", name, field));
+            }
+            let differing: BTreeSet<usize> = report
+                .get(field)
+                .map(|ms| ms.iter().map(|m| m.index).collect())
+                .unwrap_or_default();
+            let fixed: BTreeSet<usize> = from_ranges(&k.rows).difference(&differing).copied().collect();
+            if !fixed.is_empty() {
+                failures.push(format!(
+                    "{} {}: rows {:?} match now, rerun with UPDATE_GOLDEN=1",
+                    name,
+                    field,
+                    to_ranges(&fixed)
+                ));
+            }
+        }
+    }
+    for name in known.keys() {
+        failures.push(format!("{}: unknown comparison in known differences", name));
+    }
+    assert!(failures.is_empty(), "parity differences:\n{}", failures.join("\n"));
+}
@@ -11,99 +11,39 @@ use std::collections::HashMap;
 // Code Candle Master — StatusDesc to SeriesCode mapping
 // ============================================================
 
-/// Embedded mapping from StatusDesc to SeriesCode
-/// Based on CodeCandleMaster.json
-fn build_status_code_map() -> HashMap<&'static str, u32> {
+/// Master-code table shared with the v2 crate (RustLib/indicator_math).
+/// Descriptors there use the "L-D-D-G-C" spelling; this crate uses "L-DD-G-C".
+pub const MASTER_CODES_JSON: &str = include_str!("../candle_master_codes.json");
+
+/// "L-D-D-G-C" -> "L-DD-G-C"
+pub fn to_series_desc(status_desc: &str) -> Option<String> {
+    let parts: Vec<&str> = status_desc.split('-').collect();
+    if parts.len() != 5 {
+        return None;
+    }
+    Some(format!(
+        "{}-{}{}-{}-{}",
+        parts[0], parts[1], parts[2], parts[3], parts[4]
+    ))
+}
+
+/// Mapping from StatusDesc to SeriesCode, built from the embedded table
+fn build_status_code_map() -> HashMap<String, u32> {
+    let table: serde_json::Value =
+        serde_json::from_str(MASTER_CODES_JSON).expect("embedded candle_master_codes.json is invalid");
     let mut map = HashMap::new();
-    map.insert("L-DD-E-D", 1);
-    map.insert("L-DD-G-C", 2);
-    map.insert("L-DD-G-D", 3);
-    map.insert("L-DD-G-N", 4);
-    map.insert("L-DD-R-C", 5);
-    map.insert("L-DD-R-D", 6);
-    map.insert("L-DD-R-N", 7);
-    map.insert("L-DF-G-C", 8);
-    map.insert("L-DF-G-D", 9);
-    map.insert("L-DF-G-N", 10);
-    map.insert("L-DF-R-C", 11);
-    map.insert("L-DF-R-D", 12);
-    map.insert("L-DF-R-N", 13);
-    map.insert("L-DU-G-C", 14);
-    map.insert("L-DU-G-D", 15);
-    map.insert("L-DU-G-N", 16);
-    map.insert("L-DU-R-C", 17);
-    map.insert("L-DU-R-D", 18);
-    map.insert("L-DU-R-N", 19);
-    map.insert("L-FD-G-C", 20);
-    map.insert("L-FD-G-N", 21);
-    map.insert("L-FD-R-C", 22);
-    map.insert("L-FD-R-N", 23);
-    map.insert("L-FF-G-C", 24);
-    map.insert("L-FF-G-N", 25);
-    map.insert("L-FF-R-N", 26);
-    map.insert("L-FU-G-C", 27);
-    map.insert("L-FU-G-D", 28);
-    map.insert("L-FU-G-N", 29);
-    map.insert("L-FU-R-D", 30);
-    map.insert("L-FU-R-N", 31);
-    map.insert("L-UD-G-C", 32);
-    map.insert("L-UD-G-N", 33);
-    map.insert("L-UD-R-C", 34);
-    map.insert("L-UD-R-N", 35);
-    map.insert("L-UF-G-C", 36);
-    map.insert("L-UF-G-N", 37);
-    map.insert("L-UU-G-C", 38);
-    map.insert("L-UU-G-D", 39);
-    map.insert("L-UU-G-N", 40);
-    map.insert("L-UU-R-D", 41);
-    map.insert("L-UU-R-N", 42);
-    map.insert("M-DD-G-C", 43);
-    map.insert("M-DD-G-D", 44);
-    map.insert("M-DD-G-N", 45);
-    map.insert("M-DD-R-C", 46);
-    map.insert("M-DD-R-D", 47);
-    map.insert("M-DD-R-N", 48);
-    map.insert("M-DF-G-C", 49);
-    map.insert("M-DF-G-N", 50);
-    map.insert("M-DF-R-C", 51);
-    map.insert("M-DF-R-N", 52);
-    map.insert("M-DU-G-C", 53);
-    map.insert("M-DU-G-N", 54);
-    map.insert("M-DU-R-C", 55);
-    map.insert("M-DU-R-N", 56);
-    map.insert("M-FD-G-C", 57);
-    map.insert("M-FD-G-D", 58);
-    map.insert("M-FD-G-N", 59);
-    map.insert("M-FD-R-D", 60);
-    map.insert("M-FD-R-N", 61);
-    map.insert("M-FU-G-C", 62);
-    map.insert("M-FU-G-N", 63);
-    map.insert("M-FU-R-C", 64);
-    map.insert("M-FU-R-N", 65);
-    map.insert("M-UD-E-C", 66);
-    map.insert("M-UD-G-C", 67);
-    map.insert("M-UD-G-D", 68);
-    map.insert("M-UD-G-N", 69);
-    map.insert("M-UD-R-C", 70);
-    map.insert("M-UD-R-D", 71);
-    map.insert("M-UD-R-N", 72);
-    map.insert("M-UF-G-C", 73);
-    map.insert("M-UF-G-D", 74);
-    map.insert("M-UF-G-N", 75);
-    map.insert("M-UF-R-D", 76);
-    map.insert("M-UU-E-D", 77);
-    map.insert("M-UU-E-N", 78);
-    map.insert("M-UU-G-C", 79);
-    map.insert("M-UU-G-D", 80);
-    map.insert("M-UU-G-N", 81);
-    map.insert("M-UU-R-C", 82);
-    map.insert("M-UU-R-D", 83);
-    map.insert("M-UU-R-N", 84);
+    for entry in table["codes"].as_array().into_iter().flatten() {
+        let desc = entry["status_desc"].as_str().and_then(to_series_desc);
+        let code = entry["status_code"].as_str().and_then(|c| c.parse().ok());
+        if let (Some(desc), Some(code)) = (desc, code) {
+            map.insert(desc, code);
+        }
+    }
     map
 }
 
 lazy_static::lazy_static! {
-    static ref STATUS_CODE_MAP: HashMap<&'static str, u32> = build_status_code_map();
+    static ref STATUS_CODE_MAP: HashMap<String, u32> = build_status_code_map();
 }
 
 /// Lookup SeriesCode from StatusDesc
@@ -1077,6 +1017,10 @@ mod tests {
         assert_eq!(lookup_series_code("L-DD-G-C"), Some(2));
         assert_eq!(lookup_series_code("M-UU-G-N"), Some(81));
         assert_eq!(lookup_series_code("INVALID"), None);
+        // Equal-colour descriptors that the relay's hand-typed table used to miss
+        assert_eq!(lookup_series_code("M-UD-E-C"), Some(66));
+        assert_eq!(lookup_series_code("M-UU-E-N"), Some(78));
+        assert_eq!(STATUS_CODE_MAP.len(), 162);
     }
 
     #[test]
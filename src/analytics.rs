@@ -0,0 +1,245 @@
+use crate::trade_export::TradeHistoryRow;
+use chrono::{Local, TimeZone, Timelike};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Performance report over trade history rows for `GET /api/analytics/performance`.
+// A trade counts as a win when its profit is positive. Martingale depth is the step of the
+// stake ladder the bot bought the trade at, as recorded with the trade; trades without one
+// (fixed-stake mode, or only known from the day files) are left out of the depth figures.
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Breakdown {
+    pub trades: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub win_rate: f64,
+    pub net_profit: f64,
+    pub gross_profit: f64,
+    pub gross_loss: f64,
+    /// Gross profit over gross loss; null when there are no losing trades
+    pub profit_factor: Option<f64>,
+    /// Average profit per trade
+    pub expectancy: f64,
+}
+
+impl Breakdown {
+    fn add(&mut self, profit: f64) {
+        self.trades += 1;
+        self.net_profit += profit;
+        if profit > 0.0 {
+            self.wins += 1;
+            self.gross_profit += profit;
+        } else {
+            self.losses += 1;
+            self.gross_loss += -profit;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.trades > 0 {
+            self.win_rate = self.wins as f64 / self.trades as f64 * 100.0;
+            self.expectancy = self.net_profit / self.trades as f64;
+        }
+        self.profit_factor = (self.gross_loss > 0.0).then(|| self.gross_profit / self.gross_loss);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EquityPoint {
+    pub trade_date: String,
+    pub contract_id: String,
+    /// Unix seconds of the buy
+    pub buy_time: u64,
+    pub profit: f64,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PerformanceReport {
+    pub summary: Breakdown,
+    /// Largest peak-to-trough fall of the equity curve (starting from 0)
+    pub max_drawdown: f64,
+    /// Over the trades that have a martingale step
+    pub avg_martingale_depth: f64,
+    pub max_martingale_depth: u32,
+    pub longest_win_streak: u32,
+    pub longest_loss_streak: u32,
+    pub equity_curve: Vec<EquityPoint>,
+    pub by_asset: BTreeMap<String, Breakdown>,
+    pub by_status_code: BTreeMap<String, Breakdown>,
+    /// Local hour of the buy, "00".."23"
+    pub by_hour: BTreeMap<String, Breakdown>,
+    /// CALL / PUT
+    pub by_direction: BTreeMap<String, Breakdown>,
+}
+
+fn buy_epoch(row: &TradeHistoryRow) -> u64 {
+    row.buy_time.trim().parse().unwrap_or(0)
+}
+
+pub fn build_report(rows: &[TradeHistoryRow]) -> PerformanceReport {
+    let mut ordered: Vec<&TradeHistoryRow> = rows.iter().collect();
+    ordered.sort_by(|a, b| {
+        (a.trade_date.as_str(), buy_epoch(a), a.no).cmp(&(
+            b.trade_date.as_str(),
+            buy_epoch(b),
+            b.no,
+        ))
+    });
+
+    let mut report = PerformanceReport::default();
+    let (mut equity, mut peak) = (0.0_f64, 0.0_f64);
+    let (mut win_streak, mut loss_streak) = (0u32, 0u32);
+    let (mut depth_total, mut depth_trades) = (0u64, 0u32);
+
+    for row in &ordered {
+        let profit = row.profit;
+        let is_win = profit > 0.0;
+        let buy_time = buy_epoch(row);
+
+        report.summary.add(profit);
+        let code = if row.status_code.is_empty() {
+            "unknown"
+        } else {
+            row.status_code.as_str()
+        };
+        let hour = Local
+            .timestamp_opt(buy_time as i64, 0)
+            .single()
+            .map(|t| format!("{:02}", t.hour()))
+            .unwrap_or_else(|| "unknown".to_string());
+        for (map, key) in [
+            (&mut report.by_asset, row.symbol.clone()),
+            (&mut report.by_status_code, code.to_string()),
+            (&mut report.by_hour, hour),
+            (&mut report.by_direction, row.trade_type.to_uppercase()),
+        ] {
+            map.entry(key).or_default().add(profit);
+        }
+
+        equity += profit;
+        peak = peak.max(equity);
+        report.max_drawdown = report.max_drawdown.max(peak - equity);
+        report.equity_curve.push(EquityPoint {
+            trade_date: row.trade_date.clone(),
+            contract_id: row.contract_id.clone(),
+            buy_time,
+            profit,
+            equity,
+        });
+
+        if is_win {
+            win_streak += 1;
+            loss_streak = 0;
+        } else {
+            loss_streak += 1;
+            win_streak = 0;
+        }
+        report.longest_win_streak = report.longest_win_streak.max(win_streak);
+        report.longest_loss_streak = report.longest_loss_streak.max(loss_streak);
+
+        if let Some(step) = row.martingale_step {
+            depth_total += u64::from(step);
+            depth_trades += 1;
+            report.max_martingale_depth = report.max_martingale_depth.max(step);
+        }
+    }
+
+    if depth_trades > 0 {
+        report.avg_martingale_depth = depth_total as f64 / f64::from(depth_trades);
+    }
+    report.summary.finish();
+    for map in [
+        &mut report.by_asset,
+        &mut report.by_status_code,
+        &mut report.by_hour,
+        &mut report.by_direction,
+    ] {
+        map.values_mut().for_each(Breakdown::finish);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(
+        no: u32,
+        symbol: &str,
+        buy_time: u64,
+        profit: f64,
+        step: Option<u32>,
+    ) -> TradeHistoryRow {
+        TradeHistoryRow {
+            trade_date: "2026-10-11".to_string(),
+            lot_no: 1,
+            no,
+            contract_id: format!("c{}", no),
+            symbol: symbol.to_string(),
+            status_code: String::new(),
+            trade_type: "CALL".to_string(),
+            buy_price: 1.0,
+            martingale_step: step,
+            payout: 0.0,
+            buy_time: buy_time.to_string(),
+            expiry: String::new(),
+            remaining: String::new(),
+            min_profit: 0.0,
+            max_profit: 0.0,
+            profit,
+            action: String::new(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_build_report_by_hand() {
+        // Given out of order; the report sorts by buy time
+        let rows = [
+            row(4, "R_10", 400, 5.7, Some(2)),
+            row(1, "R_10", 100, 0.95, Some(0)),
+            row(3, "R_25", 300, -2.0, Some(1)),
+            row(2, "R_10", 200, -1.0, Some(0)),
+            // Fixed-stake trade: no martingale step
+            row(5, "R_25", 500, -1.0, None),
+        ];
+        let report = build_report(&rows);
+
+        let equity: Vec<f64> = report.equity_curve.iter().map(|p| p.equity).collect();
+        for (actual, expected) in equity.iter().zip([0.95, -0.05, -2.05, 3.65, 2.65]) {
+            assert_close(*actual, expected);
+        }
+        // Peak 0.95 down to -2.05
+        assert_close(report.max_drawdown, 3.0);
+
+        let summary = &report.summary;
+        assert_eq!((summary.trades, summary.wins, summary.losses), (5, 2, 3));
+        assert_close(summary.win_rate, 40.0);
+        assert_close(summary.gross_profit, 6.65);
+        assert_close(summary.gross_loss, 4.0);
+        assert_close(summary.profit_factor.unwrap(), 6.65 / 4.0);
+        assert_close(summary.expectancy, 2.65 / 5.0);
+        assert_eq!(
+            (report.longest_win_streak, report.longest_loss_streak),
+            (1, 2)
+        );
+
+        // The R_25 loss was bought at step 1 after the R_10 loss; the fixed trade is left out
+        assert_eq!(report.max_martingale_depth, 2);
+        assert_close(report.avg_martingale_depth, 0.75);
+
+        let r25 = &report.by_asset["R_25"];
+        assert_eq!((r25.trades, r25.wins), (2, 0));
+        assert_eq!(r25.profit_factor, Some(0.0));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::users::UserStore;
+use axum::extract::{Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tower_sessions::Session;
+use tracing::{error, info, warn};
+
+// Session roles and the middleware guarding the API.
+//
+// viewer: dashboards and read-only endpoints
+// trader: also starts/stops bots, scanners and streams, sells contracts, saves trades
+// admin:  also changes the trading configuration and log filters
+//
+// Every request under the API router needs a logged-in session; GET/HEAD need `viewer`,
+// writes need `trader`; the routes in `ADMIN_ROUTES` and everything under `/api/admin/`
+// need `admin`. Sessions follow the account: the role is looked up again on every request,
+// and a session whose account was removed is ended.
+
+pub const SESSION_USER_KEY: &str = "user";
+pub const SESSION_ROLE_KEY: &str = "role";
+
+/// Write routes reserved for admins
+const ADMIN_ROUTES: &[&str] = &["/api/trading-config"];
+
+/// Routes under this prefix need `admin` for reads too
+const ADMIN_PREFIX: &str = "/api/admin/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Trader,
+    Admin,
+}
+
+impl Role {
+    pub fn parse(s: &str) -> Option<Role> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Some(Role::Viewer),
+            "trader" => Some(Role::Trader),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Trader => "trader",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// Username and role of the logged-in user, `None` when not logged in. Sessions without
+/// a stored role get `viewer`.
+pub async fn session_user(session: &Session) -> Option<(String, Role)> {
+    let username = session
+        .get::<String>(SESSION_USER_KEY)
+        .await
+        .unwrap_or(None)?;
+    let role = session
+        .get::<String>(SESSION_ROLE_KEY)
+        .await
+        .unwrap_or(None)
+        .and_then(|r| Role::parse(&r));
+    Some((username, role.unwrap_or(Role::Viewer)))
+}
+
+/// Like `session_user`, but with the role the account has now in `users`. A session whose
+/// account no longer exists is ended; a changed role is written back to the session.
+/// `None` also when the users file cannot be read.
+pub async fn current_user(session: &Session, users: &Arc<UserStore>) -> Option<(String, Role)> {
+    let (username, session_role) = session_user(session).await?;
+    match account_role(users, &username).await {
+        Ok(Some(role)) => {
+            if role != session_role {
+                info!(
+                    "🔄 {}'s session role {} -> {}",
+                    username,
+                    session_role.as_str(),
+                    role.as_str()
+                );
+                let _ = session.insert(SESSION_ROLE_KEY, role.as_str()).await;
+            }
+            Some((username, role))
+        }
+        Ok(None) => {
+            warn!(
+                "🚫 Ending {}'s session: the account no longer exists",
+                username
+            );
+            let _ = session.flush().await;
+            None
+        }
+        Err(e) => {
+            error!("❌ Cannot check {}'s account: {:#}", username, e);
+            None
+        }
+    }
+}
+
+/// `UserStore::role_of` off the async workers
+pub async fn account_role(users: &Arc<UserStore>, username: &str) -> anyhow::Result<Option<Role>> {
+    let store = users.clone();
+    let name = username.to_string();
+    tokio::task::spawn_blocking(move || store.role_of(&name)).await?
+}
+
+pub fn required_role(method: &Method, path: &str) -> Role {
+    if path.starts_with(ADMIN_PREFIX) {
+        Role::Admin
+    } else if method == Method::GET || method == Method::HEAD {
+        Role::Viewer
+    } else if ADMIN_ROUTES.contains(&path) {
+        Role::Admin
+    } else {
+        Role::Trader
+    }
+}
+
+/// Role needed for a browser command on `/ws`. Viewers only receive broadcasts and may
+/// ask for a status sync; `UPDATE_PARAMS` saves the trading configuration, like
+/// `/api/trading-config`; everything else controls a live connection or bot.
+pub fn ws_command_role(command: &str) -> Role {
+    match command {
+        "SYNC_STATUS" => Role::Viewer,
+        "UPDATE_PARAMS" => Role::Admin,
+        _ => Role::Trader,
+    }
+}
+
+/// Compare a presented secret with the expected one in time independent of where they
+/// differ
+pub fn secret_matches(presented: &str, expected: &str) -> bool {
+    let (a, b) = (presented.as_bytes(), expected.as_bytes());
+    a.len() == b.len()
+        && std::hint::black_box(a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y))) == 0
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(
+            serde_json::json!({ "success": false, "error": error })
+                .to_string()
+                .into(),
+        )
+        .unwrap()
+}
+
+/// Middleware for the API router: 401 without a session (or account), 403 when the role
+/// is too low.
+pub async fn require_role(
+    State(users): State<Arc<UserStore>>,
+    session: Session,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some((_, role)) = current_user(&session, &users).await else {
+        return error_response(StatusCode::UNAUTHORIZED, "not logged in");
+    };
+    let needed = required_role(req.method(), req.uri().path());
+    if role < needed {
+        warn!(
+            "🚫 {} {} denied: role {} needs {}",
+            req.method(),
+            req.uri().path(),
+            role.as_str(),
+            needed.as_str()
+        );
+        return error_response(
+            StatusCode::FORBIDDEN,
+            &format!("requires {} role", needed.as_str()),
+        );
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role(&Method::GET, "/api/trades"), Role::Viewer);
+        assert_eq!(required_role(&Method::HEAD, "/api/trades"), Role::Viewer);
+        assert_eq!(
+            required_role(&Method::POST, "/api/save_trade"),
+            Role::Trader
+        );
+        assert_eq!(
+            required_role(&Method::DELETE, "/api/save_trade"),
+            Role::Trader
+        );
+        assert_eq!(
+            required_role(&Method::GET, "/api/trading-config"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/trading-config"),
+            Role::Admin
+        );
+        // Everything under the admin prefix, reads included
+        assert_eq!(required_role(&Method::GET, "/api/admin/users"), Role::Admin);
+        assert_eq!(
+            required_role(&Method::POST, "/api/admin/log-filter"),
+            Role::Admin
+        );
+        // Only exact matches of the admin routes
+        assert_eq!(
+            required_role(&Method::POST, "/api/trading-config/x"),
+            Role::Trader
+        );
+        assert_eq!(required_role(&Method::GET, "/api/admin"), Role::Viewer);
+    }
+
+    #[test]
+    fn test_ws_command_role() {
+        assert_eq!(ws_command_role("SYNC_STATUS"), Role::Viewer);
+        assert_eq!(ws_command_role("UPDATE_PARAMS"), Role::Admin);
+        for command in [
+            "START_DERIV",
+            "START_AUTO_MULTI",
+            "UPDATE_MODE",
+            "STOP_STREAMS",
+            "",
+        ] {
+            assert_eq!(ws_command_role(command), Role::Trader, "{}", command);
+        }
+        // Commands are case sensitive
+        assert_eq!(ws_command_role("sync_status"), Role::Trader);
+    }
+
+    #[test]
+    fn test_secret_matches() {
+        assert!(secret_matches("s3cret-token", "s3cret-token"));
+        assert!(!secret_matches("s3cret-tokem", "s3cret-token"));
+        assert!(!secret_matches("s3cret", "s3cret-token"));
+        assert!(!secret_matches("", "s3cret-token"));
+    }
+
+    #[test]
+    fn test_role_order_and_parse() {
+        assert!(Role::Viewer < Role::Trader && Role::Trader < Role::Admin);
+        assert_eq!(Role::parse(" Admin "), Some(Role::Admin));
+        assert_eq!(Role::parse("root"), None);
+        for role in [Role::Viewer, Role::Trader, Role::Admin] {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+// Crash-safe file writes for logs and config files.
+//
+// Every write goes to a temp file in the same directory, is fsynced and then renamed over
+// the target, so readers only ever see the old or the new content. The previous version is
+// kept as `<file>.bak` and the loaders fall back to it when the main file does not parse.
+// Writers in the same directory are serialized with an exclusive lock on `<dir>/.write.lock`,
+// which also covers other relay processes sharing the folder.
+
+const LOCK_FILE: &str = ".write.lock";
+const LOT_SEQ_FILE: &str = "lot_seq";
+
+/// Exclusive lock on a directory; released when dropped.
+pub struct DirLock {
+    _file: File,
+}
+
+pub fn lock_dir(dir: &Path) -> io::Result<DirLock> {
+    fs::create_dir_all(dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(DirLock { _file: file })
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    path.with_file_name(format!("{}{}{}", prefix, name, suffix))
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, "", ".bak")
+}
+
+// Caller must hold the directory lock
+fn write_locked(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = sibling(path, ".", ".tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    if path.exists() {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(&tmp, path)?;
+    // Persist the rename itself; not supported for directories on Windows
+    #[cfg(unix)]
+    File::open(parent_dir(path))?.sync_all()?;
+    Ok(())
+}
+
+/// Replace `path` with `contents` atomically, keeping the previous version as `.bak`.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let _lock = lock_dir(parent_dir(path))?;
+    write_locked(path, contents.as_ref())
+}
+
+/// Lock on the directory holding `path`, for a read-modify-write of that file
+pub fn lock_parent(path: &Path) -> io::Result<DirLock> {
+    lock_dir(parent_dir(path))
+}
+
+/// `write_atomic` for a caller that already holds the lock from `lock_parent`
+pub fn write_atomic_locked(
+    _lock: &DirLock,
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> io::Result<()> {
+    write_locked(path, contents.as_ref())
+}
+
+/// Read and parse `path`, falling back to its `.bak` when the file is missing or corrupt.
+/// A recovered backup is written back in place and the bad file is kept as `.corrupt`.
+/// Returns `None` when neither version can be used.
+pub fn read_recovering<T, E: Display>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Option<T> {
+    recover(path, parse, None)
+}
+
+/// `read_recovering` for files whose loss must not look like "no entries" (accounts,
+/// tokens): `Ok(None)` only when neither `path` nor its `.bak` exists, an error when
+/// neither can be used. `held` is the caller's lock from `lock_parent`, if it has one.
+pub fn read_existing<T, E: Display>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, E>,
+    held: Option<&DirLock>,
+) -> io::Result<Option<T>> {
+    if !path.try_exists()? && !backup_path(path).try_exists()? {
+        return Ok(None);
+    }
+    recover(path, parse, held).map(Some).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} and its backup are unreadable", path),
+        )
+    })
+}
+
+fn recover<T, E: Display>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, E>,
+    held: Option<&DirLock>,
+) -> Option<T> {
+    let main_err = match fs::read_to_string(path) {
+        Ok(content) => match parse(&content) {
+            Ok(value) => return Some(value),
+            Err(e) => e.to_string(),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => e.to_string(),
+    };
+
+    let backup = backup_path(path);
+    let Ok(content) = fs::read_to_string(&backup) else {
+        if !main_err.is_empty() {
+            error!(
+                "❌ {:?} is unreadable and has no backup: {}",
+                path, main_err
+            );
+        }
+        return None;
+    };
+    let value = match parse(&content) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("❌ Backup {:?} is unusable too: {}", backup, e);
+            return None;
+        }
+    };
+
+    info!("♻️ Recovered {:?} from backup ({})", path, main_err);
+    let restore = || -> io::Result<()> {
+        if path.exists() {
+            fs::rename(path, sibling(path, "", ".corrupt"))?;
+        }
+        write_locked(path, content.as_bytes())
+    };
+    let restored = match held {
+        Some(_) => restore(),
+        None => lock_dir(parent_dir(path)).and_then(|_lock| restore()),
+    };
+    if let Err(e) = restored {
+        warn!("⚠️ Could not restore {:?}: {}", path, e);
+    }
+    Some(value)
+}
+
+/// Read and parse `path`, or its `.bak` when the file is missing or corrupt. Unlike
+/// `read_recovering` nothing is written, so request handlers can use it freely.
+pub fn read_fallback<T, E>(path: &Path, parse: impl Fn(&str) -> Result<T, E>) -> Option<T> {
+    let read = |p: &Path| fs::read_to_string(p).ok().and_then(|c| parse(&c).ok());
+    read(path).or_else(|| read(&backup_path(path)))
+}
+
+fn highest_lot_file(folder: &Path) -> u32 {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            name.to_str()?
+                .strip_prefix("lot_")?
+                .strip_suffix(".json")?
+                .parse::<u32>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn read_seq(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+}
+
+/// Reserve the next lot number in a daily log folder. The counter lives in `lot_seq` and
+/// is advanced under the folder lock, so concurrent tasks and processes never share a
+/// number; existing `lot_N.json` files are honoured for folders created before the counter.
+pub fn allocate_lot_no(folder_path: &str) -> u32 {
+    let folder = Path::new(folder_path);
+    let seq_path = folder.join(LOT_SEQ_FILE);
+    let reserve = || -> io::Result<u32> {
+        let _lock = lock_dir(folder)?;
+        let highest = highest_lot_file(folder);
+        let last = match read_seq(&seq_path) {
+            Some(seq) => seq.max(highest),
+            // The backup is one reservation behind the lost counter; skip a number
+            // rather than hand out the one that may already be in use
+            None => read_seq(&backup_path(&seq_path)).map_or(highest, |bak| bak.max(highest) + 1),
+        };
+        let next = last + 1;
+        write_locked(&seq_path, next.to_string().as_bytes())?;
+        Ok(next)
+    };
+    reserve().unwrap_or_else(|e| {
+        warn!(
+            "⚠️ Lot counter unavailable in {} ({}), scanning files",
+            folder_path, e
+        );
+        highest_lot_file(folder) + 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file_store_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn parse_u32(s: &str) -> Result<u32, std::num::ParseIntError> {
+        s.trim().parse()
+    }
+
+    #[test]
+    fn test_read_recovering_falls_back_to_backup() {
+        let dir = temp_dir("recover");
+        let path = dir.join("config.json");
+        write_atomic(&path, "1").unwrap();
+        write_atomic(&path, "2").unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "1");
+        assert_eq!(read_recovering(&path, parse_u32), Some(2));
+
+        // Corrupt main file: the backup is returned and written back, the bad file kept
+        fs::write(&path, "{ torn").unwrap();
+        assert_eq!(read_recovering(&path, parse_u32), Some(1));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+        assert_eq!(
+            fs::read_to_string(dir.join("config.json.corrupt")).unwrap(),
+            "{ torn"
+        );
+
+        // Missing main file recovers too
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_recovering(&path, parse_u32), Some(1));
+        assert!(path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_recovering_without_usable_backup() {
+        let dir = temp_dir("no_backup");
+        let path = dir.join("config.json");
+        assert_eq!(read_recovering(&path, parse_u32), None);
+
+        fs::write(&path, "x").unwrap();
+        fs::write(backup_path(&path), "y").unwrap();
+        assert_eq!(read_recovering(&path, parse_u32), None);
+        // Nothing is touched when nothing could be recovered
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_allocate_lot_no() {
+        let dir = temp_dir("lots");
+        let folder = dir.to_str().unwrap().to_string();
+        assert_eq!(allocate_lot_no(&folder), 1);
+        assert_eq!(allocate_lot_no(&folder), 2);
+
+        // Lot files written before the counter existed are honoured
+        fs::write(dir.join("lot_7.json"), "{}").unwrap();
+        assert_eq!(allocate_lot_no(&folder), 8);
+
+        // A lost counter never reuses the number it may have reserved last
+        fs::remove_file(dir.join(LOT_SEQ_FILE)).unwrap();
+        assert_eq!(allocate_lot_no(&folder), 9);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let folder = folder.clone();
+                std::thread::spawn(move || allocate_lot_no(&folder))
+            })
+            .collect();
+        let numbers: HashSet<u32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(numbers, (10..=17).collect());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,9 @@
+use crate::firestore_outbox::{OutboxRecord, SCAN_COLLECTION, TRADE_COLLECTION};
+use crate::trade_store::TradeFilter;
 use firestore::*;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::{info, warn};
 
 /// Custom error types for Firestore operations
 #[derive(Error, Debug)]
@@ -10,11 +13,14 @@ pub enum FirestoreError {
 
     #[error("Operation failed: {0}")]
     OperationFailed(String),
+
+    #[error("Firestore not initialized")]
+    NotInitialized,
 }
 
 /// Trade Record structure for saving to Firestore
 /// Matches the Active Trades table columns in dashboard_old
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TradeRecord {
     /// ลำดับ (Order/Index)
     pub order_no: u32,
@@ -42,6 +48,12 @@ pub struct TradeRecord {
     pub max_profit: f64,
     /// Trade Result (win/loss/sold)
     pub status: String,
+    /// Candle status code at entry (empty when unknown)
+    #[serde(default)]
+    pub status_code: String,
+    /// Index into the martingale stake ladder (0 = first stake); None in fixed-stake mode
+    #[serde(default)]
+    pub martingale_step: Option<u32>,
     /// Entry Spot Price
     pub entry_spot: f64,
     /// Exit/Current Spot Price
@@ -83,6 +95,33 @@ pub struct ScanRecord {
     pub rank: u32,
 }
 
+/// Filter for `FirestoreManager::query_scans`. `from`/`to` are compared with `scan_time`
+/// (ISO strings sort chronologically). Also the query string of `GET /api/firestore/scans`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScanFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub symbol: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Default and maximum number of documents returned by a query
+const QUERY_LIMIT: u32 = 500;
+
+/// Document id of a trade: `<trade_date>_<contract_id>`
+pub fn trade_doc_id(record: &TradeRecord) -> String {
+    format!("{}_{}", record.trade_date, record.contract_id)
+}
+
+/// Document id of a scan row: scan time (with `:` and `.` replaced) and symbol
+pub fn scan_doc_id(record: &ScanRecord) -> String {
+    format!(
+        "{}_{}",
+        record.scan_time.replace(":", "-").replace(".", "-"),
+        record.symbol
+    )
+}
+
 /// Main Firestore Manager struct
 pub struct FirestoreManager {
     db: FirestoreDb,
@@ -93,126 +132,312 @@ impl FirestoreManager {
     ///
     /// # Arguments
     /// * `project_id` - Your Google Cloud Project ID
+    ///   When `FIRESTORE_EMULATOR_HOST` is set (e.g. `localhost:8080`) the local emulator
+    ///   is used instead; with a `demo-` project id it needs no credentials.
     pub async fn new(project_id: &str) -> Result<Self, FirestoreError> {
-        let db = FirestoreDb::new(project_id)
+        let mut options = FirestoreDbOptions::new(project_id.to_string());
+        if let Some(host) = emulator_host() {
+            info!("🧪 Using Firestore emulator at {}", host);
+            options = options.with_firebase_api_url(format!("http://{}", host));
+        }
+        let db = FirestoreDb::with_options(options)
             .await
             .map_err(|e| FirestoreError::ConnectionError(e.to_string()))?;
 
         Ok(Self { db })
     }
 
-    /// Add a new trade record to the collection
-    /// If the collection doesn't exist, Firestore will create it automatically
-    ///
-    /// # Arguments
-    /// * `collection_name` - Name of the collection (e.g., "trade_records")
-    /// * `record` - The TradeRecord to save
-    ///
-    /// # Returns
-    /// The document ID (auto-generated)
-    pub async fn save_trade_record(
+    /// Trades matching `filter`, newest first. Date ranges order by `trade_date`, which
+    /// needs a composite index in production Firestore (the emulator does not).
+    pub async fn query_trades(
         &self,
-        collection_name: &str,
-        record: &TradeRecord,
-    ) -> Result<String, FirestoreError> {
-        // Use contract_id as document_id for easy lookup
-        let doc_id = format!("{}_{}", record.trade_date, record.contract_id);
-
+        filter: &TradeFilter,
+    ) -> Result<Vec<TradeRecord>, FirestoreError> {
         self.db
             .fluent()
-            .insert()
-            .into(collection_name)
-            .document_id(&doc_id)
-            .object(record)
-            .execute::<()>()
+            .select()
+            .from(TRADE_COLLECTION)
+            .filter(|q| {
+                q.for_all([
+                    filter
+                        .from
+                        .as_ref()
+                        .and_then(|v| q.field("trade_date").greater_than_or_equal(v)),
+                    filter
+                        .to
+                        .as_ref()
+                        .and_then(|v| q.field("trade_date").less_than_or_equal(v)),
+                    filter.symbol.as_ref().and_then(|v| q.field("symbol").eq(v)),
+                    filter.lot_no.and_then(|v| q.field("lot_no").eq(v)),
+                    filter
+                        .status_code
+                        .as_ref()
+                        .and_then(|v| q.field("status_code").eq(v)),
+                    filter
+                        .status
+                        .as_ref()
+                        .and_then(|v| q.field("status").eq(v.to_lowercase())),
+                ])
+            })
+            .order_by([
+                ("trade_date", FirestoreQueryDirection::Descending),
+                ("buy_time", FirestoreQueryDirection::Descending),
+            ])
+            .limit(query_limit(filter.limit))
+            .obj::<TradeRecord>()
+            .query()
             .await
-            .map_err(|e| FirestoreError::OperationFailed(e.to_string()))?;
-
-        println!("🔥 Trade record saved to Firestore: {}", doc_id);
-        Ok(doc_id)
+            .map_err(|e| FirestoreError::OperationFailed(e.to_string()))
     }
 
-    /// Check if Firestore connection is healthy
-    pub async fn health_check(&self) -> bool {
-        // Try to list collections as a health check
-        match self
-            .db
-            .list_collection_ids(FirestoreListCollectionIdsParams::new())
+    /// Scan rows matching `filter`, newest first
+    pub async fn query_scans(
+        &self,
+        filter: &ScanFilter,
+    ) -> Result<Vec<ScanRecord>, FirestoreError> {
+        self.db
+            .fluent()
+            .select()
+            .from(SCAN_COLLECTION)
+            .filter(|q| {
+                q.for_all([
+                    filter
+                        .from
+                        .as_ref()
+                        .and_then(|v| q.field("scan_time").greater_than_or_equal(v)),
+                    filter
+                        .to
+                        .as_ref()
+                        .and_then(|v| q.field("scan_time").less_than_or_equal(v)),
+                    filter.symbol.as_ref().and_then(|v| q.field("symbol").eq(v)),
+                ])
+            })
+            .order_by([("scan_time", FirestoreQueryDirection::Descending)])
+            .limit(query_limit(filter.limit))
+            .obj::<ScanRecord>()
+            .query()
             .await
-        {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+            .map_err(|e| FirestoreError::OperationFailed(e.to_string()))
     }
 
-    /// Save a scan record to the collection
-    /// If the collection doesn't exist, Firestore will create it automatically
-    pub async fn save_scan_record(
+    /// Best-ranked assets of one scan (the latest when `scan_time` is `None`), rank 1 first
+    pub async fn top_ranked(
         &self,
-        collection_name: &str,
-        record: &ScanRecord,
-    ) -> Result<String, FirestoreError> {
-        // Generate unique doc ID with timestamp and symbol
-        let doc_id = format!(
-            "{}_{}",
-            record.scan_time.replace(":", "-").replace(".", "-"),
-            record.symbol
-        );
+        scan_time: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ScanRecord>, FirestoreError> {
+        let scan_time = match scan_time {
+            Some(t) => t.to_string(),
+            None => {
+                let latest = ScanFilter {
+                    limit: Some(1),
+                    ..Default::default()
+                };
+                match self.query_scans(&latest).await?.into_iter().next() {
+                    Some(record) => record.scan_time,
+                    None => return Ok(Vec::new()),
+                }
+            }
+        };
 
         self.db
             .fluent()
-            .insert()
-            .into(collection_name)
-            .document_id(&doc_id)
-            .object(record)
-            .execute::<()>()
+            .select()
+            .from(SCAN_COLLECTION)
+            .filter(|q| q.for_all([q.field("scan_time").eq(&scan_time)]))
+            .order_by([("rank", FirestoreQueryDirection::Ascending)])
+            .limit(query_limit(Some(limit)))
+            .obj::<ScanRecord>()
+            .query()
             .await
-            .map_err(|e| FirestoreError::OperationFailed(e.to_string()))?;
+            .map_err(|e| FirestoreError::OperationFailed(e.to_string()))
+    }
 
-        println!("🔥 Scan record saved to Firestore: {}", doc_id);
-        Ok(doc_id)
+    /// Upsert several records in one BatchWrite call.
+    /// Documents are overwritten rather than inserted, so a retried batch is harmless.
+    pub async fn write_batch(&self, records: &[&OutboxRecord]) -> Result<(), FirestoreError> {
+        let op_err =
+            |e: firestore::errors::FirestoreError| FirestoreError::OperationFailed(e.to_string());
+        let writer = self.db.create_simple_batch_writer().await.map_err(op_err)?;
+        let mut batch = writer.new_batch();
+        for record in records {
+            let update = self
+                .db
+                .fluent()
+                .update()
+                .in_col(record.collection())
+                .document_id(record.doc_id());
+            let added = match record {
+                OutboxRecord::Trade(r) => update.object(r).add_to_batch(&mut batch),
+                OutboxRecord::Scan(r) => update.object(r).add_to_batch(&mut batch),
+            };
+            added.map_err(op_err)?;
+        }
+        batch.write().await.map_err(op_err)?;
+        Ok(())
     }
 }
 
-/// Global Firestore instance wrapper
+fn query_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(QUERY_LIMIT).clamp(1, QUERY_LIMIT)
+}
+
+/// `FIRESTORE_EMULATOR_HOST` when set and non-empty
+pub fn emulator_host() -> Option<String> {
+    std::env::var("FIRESTORE_EMULATOR_HOST")
+        .ok()
+        .filter(|h| !h.trim().is_empty())
+}
+
+/// Global Firestore instance wrapper. The connection is made by `initialize`, which can be
+/// retried after a failure (the outbox does so with backoff) and does nothing once connected.
 pub struct GlobalFirestore {
-    manager: Option<FirestoreManager>,
+    project_id: Option<String>,
+    manager: tokio::sync::OnceCell<FirestoreManager>,
 }
 
 impl GlobalFirestore {
-    pub fn new() -> Self {
-        Self { manager: None }
+    /// `None` leaves Firestore disabled
+    pub fn new(project_id: Option<String>) -> Self {
+        Self {
+            project_id,
+            manager: tokio::sync::OnceCell::new(),
+        }
     }
 
-    pub async fn initialize(&mut self, project_id: &str) -> Result<(), FirestoreError> {
-        match FirestoreManager::new(project_id).await {
-            Ok(manager) => {
-                println!("✅ Firestore connected successfully");
-                self.manager = Some(manager);
-                Ok(())
-            }
+    pub fn is_configured(&self) -> bool {
+        self.project_id.is_some()
+    }
+
+    pub async fn initialize(&self) -> Result<(), FirestoreError> {
+        let project_id = self
+            .project_id
+            .as_deref()
+            .ok_or(FirestoreError::NotInitialized)?;
+        let connected = self
+            .manager
+            .get_or_try_init(|| async {
+                let manager = FirestoreManager::new(project_id).await?;
+                info!("✅ Firestore connected successfully");
+                Ok(manager)
+            })
+            .await;
+        match connected {
+            Ok(_) => Ok(()),
             Err(e) => {
-                println!("⚠️ Firestore connection failed: {}", e);
+                warn!("⚠️ Firestore connection failed: {}", e);
                 Err(e)
             }
         }
     }
 
-    pub async fn save_trade(&self, record: &TradeRecord) -> Result<String, FirestoreError> {
-        match &self.manager {
-            Some(manager) => manager.save_trade_record("trade_records", record).await,
-            None => Err(FirestoreError::OperationFailed(
-                "Firestore not initialized".to_string(),
-            )),
-        }
+    pub fn is_initialized(&self) -> bool {
+        self.manager.initialized()
+    }
+
+    pub async fn write_batch(&self, records: &[&OutboxRecord]) -> Result<(), FirestoreError> {
+        self.manager()?.write_batch(records).await
+    }
+
+    fn manager(&self) -> Result<&FirestoreManager, FirestoreError> {
+        self.manager.get().ok_or(FirestoreError::NotInitialized)
+    }
+
+    pub async fn query_trades(
+        &self,
+        filter: &TradeFilter,
+    ) -> Result<Vec<TradeRecord>, FirestoreError> {
+        self.manager()?.query_trades(filter).await
     }
 
-    pub async fn save_scan(&self, record: &ScanRecord) -> Result<String, FirestoreError> {
-        match &self.manager {
-            Some(manager) => manager.save_scan_record("market_scans", record).await,
-            None => Err(FirestoreError::OperationFailed(
-                "Firestore not initialized".to_string(),
-            )),
+    pub async fn query_scans(
+        &self,
+        filter: &ScanFilter,
+    ) -> Result<Vec<ScanRecord>, FirestoreError> {
+        self.manager()?.query_scans(filter).await
+    }
+
+    pub async fn top_ranked(
+        &self,
+        scan_time: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ScanRecord>, FirestoreError> {
+        self.manager()?.top_ranked(scan_time, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Round trip against the emulator, e.g.
+    //   gcloud emulators firestore start --host-port=localhost:8086
+    //   FIRESTORE_EMULATOR_HOST=localhost:8086 cargo test firestore_manager
+    // Skipped when FIRESTORE_EMULATOR_HOST is not set.
+    #[tokio::test]
+    async fn test_write_batch_then_query_on_emulator() {
+        if emulator_host().is_none() {
+            eprintln!("FIRESTORE_EMULATOR_HOST not set, skipping");
+            return;
         }
+        let firestore = GlobalFirestore::new(Some("demo-deriv-relay-test".to_string()));
+        firestore.initialize().await.expect("emulator connection");
+
+        // Unique names, so reruns against a long-lived emulator do not see old documents
+        let run = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let symbol = format!("TEST_{}", run);
+        let scan_time = format!("2099-01-01T00:00:00.{}", run);
+        let trade = |contract_id: &str, date: &str, buy_time: u64| {
+            OutboxRecord::Trade(TradeRecord {
+                contract_id: contract_id.to_string(),
+                symbol: symbol.clone(),
+                status: "win".to_string(),
+                trade_date: date.to_string(),
+                buy_time,
+                ..Default::default()
+            })
+        };
+        let scan = |name: &str, rank: u32| {
+            OutboxRecord::Scan(ScanRecord {
+                scan_time: scan_time.clone(),
+                timeframe: "60".to_string(),
+                period: "14".to_string(),
+                symbol: format!("{}_{}", symbol, name),
+                price: 1.0,
+                ci: 50.0,
+                adx: 25.0,
+                score: 75.0,
+                is_bullish: true,
+                recent_candles: String::new(),
+                rank,
+            })
+        };
+        let records = [
+            trade("c1", "2099-01-01", 10),
+            trade("c2", "2099-01-02", 5),
+            trade("c3", "2099-01-02", 20),
+            scan("b", 2),
+            scan("c", 3),
+            scan("a", 1),
+        ];
+        let refs: Vec<&OutboxRecord> = records.iter().collect();
+        firestore.write_batch(&refs).await.unwrap();
+        // Writing the same batch again is an upsert, not a duplicate
+        firestore.write_batch(&refs).await.unwrap();
+
+        let trades = firestore
+            .query_trades(&TradeFilter {
+                symbol: Some(symbol.clone()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let ids: Vec<&str> = trades.iter().map(|t| t.contract_id.as_str()).collect();
+        assert_eq!(ids, ["c3", "c2", "c1"]);
+
+        let top = firestore.top_ranked(Some(&scan_time), 2).await.unwrap();
+        let ranks: Vec<u32> = top.iter().map(|s| s.rank).collect();
+        assert_eq!(ranks, [1, 2]);
+        assert_eq!(top[0].symbol, format!("{}_a", symbol));
     }
 }
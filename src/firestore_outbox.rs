@@ -0,0 +1,382 @@
+use crate::firestore_manager::{GlobalFirestore, ScanRecord, TradeRecord};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// Background writer for Firestore. Records are queued in the local database (table
+// `firestore_outbox`) and a single task pushes them in batches, so trading code never
+// awaits the cloud. Failed batches are retried with exponential backoff; the queue is
+// keyed by document id, so a newer version of a record replaces the queued one.
+
+pub const TRADE_COLLECTION: &str = "trade_records";
+pub const SCAN_COLLECTION: &str = "market_scans";
+
+/// Queue bound; new documents are rejected (and stay in the local store only) when full
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+/// Documents per Firestore BatchWrite call
+const BATCH_SIZE: usize = 100;
+const IDLE_POLL: Duration = Duration::from_secs(5);
+const RETRY_BASE_MS: u64 = 2_000;
+const RETRY_MAX_MS: u64 = 10 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "record")]
+pub enum OutboxRecord {
+    Trade(TradeRecord),
+    Scan(ScanRecord),
+}
+
+impl OutboxRecord {
+    pub fn collection(&self) -> &'static str {
+        match self {
+            OutboxRecord::Trade(_) => TRADE_COLLECTION,
+            OutboxRecord::Scan(_) => SCAN_COLLECTION,
+        }
+    }
+
+    pub fn doc_id(&self) -> String {
+        match self {
+            OutboxRecord::Trade(r) => crate::firestore_manager::trade_doc_id(r),
+            OutboxRecord::Scan(r) => crate::firestore_manager::scan_doc_id(r),
+        }
+    }
+}
+
+/// A queued document
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub seq: i64,
+    pub record: OutboxRecord,
+    pub attempts: u32,
+}
+
+/// Persistent queue behind the outbox
+pub trait OutboxStore: Send + Sync {
+    /// Queue a document, replacing a queued one with the same collection and id.
+    /// Returns false when the queue already holds `max_depth` other documents.
+    fn enqueue(&self, record: &OutboxRecord, max_depth: usize) -> anyhow::Result<bool>;
+    /// Oldest entries whose retry time has passed
+    fn due(&self, now_ms: u64, limit: usize) -> anyhow::Result<Vec<OutboxEntry>>;
+    fn complete(&self, seqs: &[i64]) -> anyhow::Result<()>;
+    fn reschedule(&self, seq: i64, next_attempt_ms: u64, error: &str) -> anyhow::Result<()>;
+    fn depth(&self) -> anyhow::Result<usize>;
+}
+
+/// Counters reported by `GET /api/firestore/outbox`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OutboxStatus {
+    pub enabled: bool,
+    pub connected: bool,
+    pub depth: usize,
+    pub max_depth: usize,
+    pub sent_total: u64,
+    pub failed_batches: u64,
+    pub consecutive_failures: u32,
+    pub dropped_total: u64,
+    pub last_error: Option<String>,
+    /// Unix ms of the last successful batch
+    pub last_success_at: Option<u64>,
+}
+
+pub struct FirestoreOutbox {
+    store: Arc<dyn OutboxStore>,
+    max_depth: usize,
+    status: Mutex<OutboxStatus>,
+    wake: tokio::sync::Notify,
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+fn backoff_ms(attempts: u32) -> u64 {
+    RETRY_BASE_MS
+        .saturating_mul(1u64 << attempts.min(20))
+        .min(RETRY_MAX_MS)
+}
+
+impl FirestoreOutbox {
+    pub fn new(store: Arc<dyn OutboxStore>, max_depth: usize, enabled: bool) -> Self {
+        Self {
+            store,
+            max_depth,
+            status: Mutex::new(OutboxStatus {
+                enabled,
+                max_depth,
+                ..Default::default()
+            }),
+            wake: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Queue a record for upload. Never waits on the network.
+    pub fn enqueue(&self, record: OutboxRecord) {
+        match self.store.enqueue(&record, self.max_depth) {
+            Ok(true) => self.wake.notify_one(),
+            Ok(false) => {
+                warn!(
+                    "⚠️ Firestore outbox full ({}), dropped {}",
+                    self.max_depth,
+                    record.doc_id()
+                );
+                if let Ok(mut s) = self.status.lock() {
+                    s.dropped_total += 1;
+                }
+            }
+            Err(e) => error!("❌ Firestore outbox enqueue error: {}", e),
+        }
+    }
+
+    pub fn status(&self) -> OutboxStatus {
+        let mut status = self.status.lock().map(|s| s.clone()).unwrap_or_default();
+        status.depth = self.store.depth().unwrap_or(0);
+        status
+    }
+
+    fn update_status(&self, f: impl FnOnce(&mut OutboxStatus)) {
+        if let Ok(mut s) = self.status.lock() {
+            f(&mut s);
+        }
+    }
+
+    /// Drain the queue forever. Until Firestore connects, the connection is retried with
+    /// the same backoff as failed batches and entries wait on disk. Without a configured
+    /// project the task ends; the queue is kept for a run that has one.
+    pub async fn run(self: Arc<Self>, firestore: Arc<GlobalFirestore>) {
+        if !firestore.is_configured() {
+            warn!(
+                "⚠️ Firestore outbox idle: no project configured ({} queued)",
+                self.depth().await
+            );
+            return;
+        }
+
+        let mut attempts = 0;
+        while let Err(e) = firestore.initialize().await {
+            let delay = backoff_ms(attempts);
+            attempts += 1;
+            warn!(
+                "⚠️ Firestore outbox not connected ({} queued), retrying in {}s",
+                self.depth().await,
+                delay / 1000
+            );
+            self.update_status(|s| {
+                s.consecutive_failures = attempts;
+                s.last_error = Some(e.to_string());
+            });
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+        self.update_status(|s| {
+            s.connected = true;
+            s.consecutive_failures = 0;
+        });
+
+        loop {
+            let batch = match self
+                .with_store(|store| store.due(now_ms(), BATCH_SIZE))
+                .await
+            {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!("❌ Firestore outbox read error: {}", e);
+                    Vec::new()
+                }
+            };
+            if batch.is_empty() {
+                let _ = tokio::time::timeout(IDLE_POLL, self.wake.notified()).await;
+                continue;
+            }
+
+            self.send(&firestore, &batch).await;
+        }
+    }
+
+    /// Run a queue call on the blocking pool
+    async fn with_store<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn OutboxStore) -> anyhow::Result<T> + Send + 'static,
+    {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || f(store.as_ref())).await?
+    }
+
+    async fn depth(&self) -> usize {
+        self.with_store(|store| store.depth()).await.unwrap_or(0)
+    }
+
+    /// Write one batch; failed entries are rescheduled. Returns whether the batch was sent.
+    async fn send(&self, firestore: &GlobalFirestore, batch: &[OutboxEntry]) -> bool {
+        let records: Vec<&OutboxRecord> = batch.iter().map(|e| &e.record).collect();
+        match firestore.write_batch(&records).await {
+            Ok(()) => {
+                let seqs: Vec<i64> = batch.iter().map(|e| e.seq).collect();
+                if let Err(e) = self.with_store(move |store| store.complete(&seqs)).await {
+                    error!("❌ Firestore outbox complete error: {}", e);
+                }
+                info!("🔥 Firestore outbox: sent {} documents", batch.len());
+                self.update_status(|s| {
+                    s.sent_total += batch.len() as u64;
+                    s.consecutive_failures = 0;
+                    s.last_success_at = Some(now_ms());
+                });
+                true
+            }
+            Err(e) => {
+                let error = e.to_string();
+                warn!(
+                    "⚠️ Firestore outbox: batch of {} failed: {}",
+                    batch.len(),
+                    error
+                );
+                let retries: Vec<(i64, u64)> = batch
+                    .iter()
+                    .map(|entry| (entry.seq, now_ms() + backoff_ms(entry.attempts)))
+                    .collect();
+                let message = error.clone();
+                let rescheduled = self
+                    .with_store(move |store| {
+                        for (seq, next) in retries {
+                            store.reschedule(seq, next, &message)?;
+                        }
+                        Ok(())
+                    })
+                    .await;
+                if let Err(e) = rescheduled {
+                    error!("❌ Firestore outbox reschedule error: {}", e);
+                }
+                self.update_status(|s| {
+                    s.failed_batches += 1;
+                    s.consecutive_failures += 1;
+                    s.last_error = Some(error);
+                });
+                false
+            }
+        }
+    }
+
+    /// Last attempt at shutdown: send everything queued, including entries waiting for a
+    /// retry, until the queue is empty, a batch fails or `limit` passes. Whatever is left
+    /// stays on disk for the next run.
+    pub async fn flush(&self, firestore: &GlobalFirestore, limit: Duration) {
+        if !firestore.is_initialized() {
+            return;
+        }
+        let flushed = tokio::time::timeout(limit, async {
+            loop {
+                let batch = match self
+                    .with_store(|store| store.due(i64::MAX as u64, BATCH_SIZE))
+                    .await
+                {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        error!("❌ Firestore outbox read error: {}", e);
+                        return;
+                    }
+                };
+                if batch.is_empty() || !self.send(firestore, &batch).await {
+                    return;
+                }
+            }
+        })
+        .await;
+        let depth = self.depth().await;
+        if flushed.is_err() || depth > 0 {
+            warn!(
+                "⚠️ Firestore outbox: {} documents left for the next run",
+                depth
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trade_store::SqliteTradeStore;
+
+    fn trade(contract_id: &str, status: &str) -> OutboxRecord {
+        OutboxRecord::Trade(TradeRecord {
+            contract_id: contract_id.to_string(),
+            status: status.to_string(),
+            trade_date: "2026-10-18".to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn status_of(entry: &OutboxEntry) -> &str {
+        match &entry.record {
+            OutboxRecord::Trade(r) => &r.status,
+            OutboxRecord::Scan(_) => "",
+        }
+    }
+
+    #[test]
+    fn test_enqueue_replaces_queued_document() {
+        let store = Arc::new(SqliteTradeStore::open_in_memory().unwrap());
+        let outbox = FirestoreOutbox::new(store.clone(), 10, true);
+        outbox.enqueue(trade("c1", "open"));
+        let first = store.due(now_ms(), BATCH_SIZE).unwrap();
+        outbox.enqueue(trade("c1", "win"));
+        outbox.enqueue(trade("c2", "loss"));
+
+        let due = store.due(now_ms(), BATCH_SIZE).unwrap();
+        assert_eq!(due.len(), 2);
+        assert_eq!(status_of(&due[0]), "win");
+        assert!(due[0].seq > first[0].seq);
+
+        // Completing the batch that carried the old version keeps the new one queued
+        store.complete(&[first[0].seq]).unwrap();
+        assert_eq!(store.depth().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_full_queue_drops_new_documents_only() {
+        let store = Arc::new(SqliteTradeStore::open_in_memory().unwrap());
+        let outbox = FirestoreOutbox::new(store.clone(), 2, true);
+        outbox.enqueue(trade("c1", "open"));
+        outbox.enqueue(trade("c2", "open"));
+        outbox.enqueue(trade("c3", "open"));
+        assert_eq!(outbox.status().dropped_total, 1);
+        assert_eq!(outbox.status().depth, 2);
+
+        // An update of a queued document still fits
+        outbox.enqueue(trade("c2", "win"));
+        assert_eq!(outbox.status().dropped_total, 1);
+        let due = store.due(now_ms(), BATCH_SIZE).unwrap();
+        assert_eq!(status_of(&due[1]), "win");
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff_ms(0), RETRY_BASE_MS);
+        assert_eq!(backoff_ms(1), 2 * RETRY_BASE_MS);
+        assert_eq!(backoff_ms(5), 32 * RETRY_BASE_MS);
+        assert_eq!(backoff_ms(9), RETRY_MAX_MS);
+        assert_eq!(backoff_ms(u32::MAX), RETRY_MAX_MS);
+
+        // A rescheduled entry is not due before its retry time and counts the attempt
+        let store = SqliteTradeStore::open_in_memory().unwrap();
+        store.enqueue(&trade("c1", "win"), 10).unwrap();
+        let seq = store.due(now_ms(), BATCH_SIZE).unwrap()[0].seq;
+        let retry_at = now_ms() + backoff_ms(0);
+        store.reschedule(seq, retry_at, "unavailable").unwrap();
+        assert!(store.due(retry_at - 1, BATCH_SIZE).unwrap().is_empty());
+        let due = store.due(retry_at, BATCH_SIZE).unwrap();
+        assert_eq!(due[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_without_project_keeps_the_queue() {
+        let store = Arc::new(SqliteTradeStore::open_in_memory().unwrap());
+        let outbox = Arc::new(FirestoreOutbox::new(store.clone(), 10, true));
+        outbox.enqueue(trade("c1", "win"));
+        outbox
+            .clone()
+            .run(Arc::new(GlobalFirestore::new(None)))
+            .await;
+        assert_eq!(store.depth().unwrap(), 1);
+        assert!(!outbox.status().connected);
+    }
+}
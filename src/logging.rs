@@ -0,0 +1,132 @@
+use std::sync::Mutex;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+// Logging setup for the relay.
+//
+// Levels and targets come from `RUST_LOG` (default `info`), e.g.
+// `RUST_LOG=info,rust_deriv_relay=debug`. Stdout is text unless `LOG_FORMAT=json`. Log files
+// are always JSON, one object per line, rotated daily as `relay.YYYY-MM-DD.log` in `LOG_DIR`
+// (default `logs/relay/`, next to the lot logs) with the last `LOG_KEEP_DAYS` (default 14)
+// kept; `LOG_DIR=off` disables them. The filter can be changed while running with
+// `PUT /api/admin/log-filter`.
+//
+// Bots run inside `bot` spans carrying the asset (and user), and contract events carry a
+// `contract_id` field, so one contract's history is a grep for its id.
+
+const DEFAULT_FILTER: &str = "info";
+const DEFAULT_LOG_DIR: &str = "logs/relay";
+const DEFAULT_KEEP_DAYS: usize = 14;
+
+pub struct Logging {
+    filter: reload::Handle<EnvFilter, Registry>,
+    current: Mutex<String>,
+    /// Flushes the file writer when dropped
+    file_guard: Mutex<Option<WorkerGuard>>,
+}
+
+fn parse_filter(directives: &str) -> Result<EnvFilter, String> {
+    EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| format!("invalid filter: {}", e))
+}
+
+impl Logging {
+    /// Install the global subscriber. Call once, early in `main`.
+    pub fn init() -> Logging {
+        let directives = std::env::var("RUST_LOG")
+            .ok()
+            .filter(|d| parse_filter(d).is_ok())
+            .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+        let (filter, handle) = reload::Layer::new(
+            parse_filter(&directives).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER)),
+        );
+        let json = std::env::var("LOG_FORMAT").is_ok_and(|f| f.eq_ignore_ascii_case("json"));
+
+        let log_dir = std::env::var("LOG_DIR").unwrap_or_else(|_| DEFAULT_LOG_DIR.to_string());
+        let keep_days = std::env::var("LOG_KEEP_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_KEEP_DAYS);
+        let mut file_error = None;
+        let file_writer = if log_dir == "off" {
+            None
+        } else {
+            let appender = std::fs::create_dir_all(&log_dir)
+                .map_err(|e| e.to_string())
+                .and_then(|_| {
+                    RollingFileAppender::builder()
+                        .rotation(Rotation::DAILY)
+                        .filename_prefix("relay")
+                        .filename_suffix("log")
+                        .max_log_files(keep_days)
+                        .build(&log_dir)
+                        .map_err(|e| e.to_string())
+                });
+            match appender {
+                Ok(appender) => Some(tracing_appender::non_blocking(appender)),
+                Err(e) => {
+                    file_error = Some(e);
+                    None
+                }
+            }
+        };
+        let (file_writer, file_guard) = match file_writer {
+            Some((writer, guard)) => (Some(writer), Some(guard)),
+            None => (None, None),
+        };
+
+        let stdout_layer = if json {
+            fmt::layer().json().boxed()
+        } else {
+            fmt::layer().boxed()
+        };
+        let file_layer = file_writer.map(|w| fmt::layer().json().with_writer(w));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(stdout_layer)
+            .with(file_layer)
+            .init();
+
+        if let Some(e) = file_error {
+            tracing::warn!("⚠️ Log files disabled, cannot write to {}: {}", log_dir, e);
+        }
+        Logging {
+            filter: handle,
+            current: Mutex::new(directives),
+            file_guard: Mutex::new(file_guard),
+        }
+    }
+
+    /// The active filter directives
+    pub fn filter(&self) -> String {
+        self.current
+            .lock()
+            .map(|c| c.clone())
+            .unwrap_or_else(|_| DEFAULT_FILTER.to_string())
+    }
+
+    /// Replace the filter, e.g. `info,rust_deriv_relay=debug`
+    pub fn set_filter(&self, directives: &str) -> Result<(), String> {
+        let directives = directives.trim();
+        let filter = parse_filter(directives)?;
+        self.filter
+            .reload(filter)
+            .map_err(|e| format!("cannot apply filter: {}", e))?;
+        if let Ok(mut current) = self.current.lock() {
+            *current = directives.to_string();
+        }
+        Ok(())
+    }
+
+    /// Write out buffered file logs and close the file writer. Called last on shutdown;
+    /// later events only reach stdout.
+    pub fn flush(&self) {
+        if let Ok(mut guard) = self.file_guard.lock() {
+            guard.take();
+        }
+    }
+}
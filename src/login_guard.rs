@@ -0,0 +1,276 @@
+use axum::http::HeaderMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// Login throttling and audit trail.
+//
+// Failed logins are counted per client IP and per username within a sliding window. Once
+// either count reaches its limit, that IP or username is locked out for the lockout period,
+// even for the right password. Each attempt is counted as a failure before the password is
+// checked, so a burst of concurrent requests cannot get past the limit, and a successful
+// login clears both counters. Every attempt is appended to `data/login_audit.jsonl`.
+
+pub const LOGIN_AUDIT_FILE: &str = "data/login_audit.jsonl";
+
+const DEFAULT_MAX_FAILURES_PER_USER: u32 = 5;
+const DEFAULT_MAX_FAILURES_PER_IP: u32 = 20;
+const DEFAULT_LOCKOUT_SECS: u64 = 15 * 60;
+
+#[derive(Default)]
+struct Attempts {
+    failures: Vec<Instant>,
+    locked_until: Option<Instant>,
+}
+
+/// A login attempt reserved by `begin_attempt`. It already counts as a failure; pass it to
+/// `record_success` when the password was right.
+pub struct Attempt {
+    ip: String,
+    username: String,
+}
+
+pub struct LoginGuard {
+    max_failures_per_user: u32,
+    max_failures_per_ip: u32,
+    /// Both the counting window and the lockout length
+    lockout: Duration,
+    attempts: Mutex<HashMap<String, Attempts>>,
+    audit_path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    time: String,
+    username: &'a str,
+    ip: &'a str,
+    outcome: &'a str,
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Client address for throttling. Behind a reverse proxy set `TRUST_FORWARDED_FOR=1` to
+/// use the first `X-Forwarded-For` entry instead of the proxy's address.
+pub fn client_ip(peer: SocketAddr, headers: &HeaderMap) -> String {
+    let trust_proxy = std::env::var("TRUST_FORWARDED_FOR").is_ok_and(|v| v == "1" || v == "true");
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if let Some(ip) = forwarded {
+            return ip.to_string();
+        }
+    }
+    peer.ip().to_string()
+}
+
+fn user_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+impl LoginGuard {
+    /// Limits from `LOGIN_MAX_FAILURES_PER_USER`, `LOGIN_MAX_FAILURES_PER_IP` and
+    /// `LOGIN_LOCKOUT_SECS`
+    pub fn from_env() -> Self {
+        Self {
+            max_failures_per_user: env_or(
+                "LOGIN_MAX_FAILURES_PER_USER",
+                DEFAULT_MAX_FAILURES_PER_USER,
+            ),
+            max_failures_per_ip: env_or("LOGIN_MAX_FAILURES_PER_IP", DEFAULT_MAX_FAILURES_PER_IP),
+            lockout: Duration::from_secs(env_or("LOGIN_LOCKOUT_SECS", DEFAULT_LOCKOUT_SECS)),
+            attempts: Mutex::new(HashMap::new()),
+            audit_path: PathBuf::from(LOGIN_AUDIT_FILE),
+        }
+    }
+
+    /// Remaining lockout for this IP or username, `None` when a login may be tried
+    fn remaining_lockout(
+        &self,
+        attempts: &mut HashMap<String, Attempts>,
+        ip: &str,
+        username: &str,
+        now: Instant,
+    ) -> Option<Duration> {
+        // Forget counters that have aged out so the map does not grow without bound
+        attempts.retain(|_, a| {
+            a.failures.retain(|t| now.duration_since(*t) < self.lockout);
+            a.locked_until.is_some_and(|until| until > now) || !a.failures.is_empty()
+        });
+        [ip_key(ip), user_key(username)]
+            .iter()
+            .filter_map(|key| attempts.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    #[cfg(test)]
+    fn locked_for(&self, ip: &str, username: &str) -> Option<Duration> {
+        let mut attempts = self.attempts.lock().unwrap();
+        self.remaining_lockout(&mut attempts, ip, username, Instant::now())
+    }
+
+    /// Check the lockout and count the attempt as a failure in one step. `Err` holds the
+    /// remaining lockout.
+    pub fn begin_attempt(&self, ip: &str, username: &str) -> Result<Attempt, Duration> {
+        let attempt = Attempt {
+            ip: ip.to_string(),
+            username: username.to_string(),
+        };
+        let now = Instant::now();
+        let Ok(mut attempts) = self.attempts.lock() else {
+            return Ok(attempt);
+        };
+        if let Some(remaining) = self.remaining_lockout(&mut attempts, ip, username, now) {
+            return Err(remaining);
+        }
+        for (key, limit) in [
+            (ip_key(ip), self.max_failures_per_ip),
+            (user_key(username), self.max_failures_per_user),
+        ] {
+            let entry = attempts.entry(key.clone()).or_default();
+            entry.failures.push(now);
+            if entry.failures.len() as u32 >= limit {
+                entry.locked_until = Some(now + self.lockout);
+                entry.failures.clear();
+                info!("🔒 Login locked for {} ({}s)", key, self.lockout.as_secs());
+            }
+        }
+        Ok(attempt)
+    }
+
+    pub fn record_success(&self, attempt: Attempt) {
+        if let Ok(mut attempts) = self.attempts.lock() {
+            attempts.remove(&ip_key(&attempt.ip));
+            attempts.remove(&user_key(&attempt.username));
+        }
+    }
+
+    /// Append an attempt to the audit log. `outcome` is "success", "failure" or "locked".
+    pub fn audit(&self, ip: &str, username: &str, outcome: &str) {
+        let entry = AuditEntry {
+            time: chrono::Local::now().to_rfc3339(),
+            username,
+            ip,
+            outcome,
+        };
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = self.audit_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.audit_path)?;
+            writeln!(file, "{}", serde_json::to_string(&entry)?)
+        };
+        if let Err(e) = write() {
+            warn!("⚠️ Login audit write failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(per_user: u32, per_ip: u32, lockout: Duration) -> LoginGuard {
+        LoginGuard {
+            max_failures_per_user: per_user,
+            max_failures_per_ip: per_ip,
+            lockout,
+            attempts: Mutex::new(HashMap::new()),
+            audit_path: std::env::temp_dir()
+                .join(format!("login_audit_{}.jsonl", std::process::id())),
+        }
+    }
+
+    #[test]
+    fn test_username_lockout_spans_ips() {
+        let guard = guard(3, 100, Duration::from_secs(60));
+        for ip in ["1.1.1.1", "2.2.2.2"] {
+            guard.begin_attempt(ip, "alice").unwrap();
+            assert_eq!(guard.locked_for(ip, "alice"), None);
+        }
+        guard.begin_attempt("3.3.3.3", "Alice").unwrap();
+        let remaining = guard.locked_for("4.4.4.4", "ALICE").unwrap();
+        assert!(remaining > Duration::from_secs(59) && remaining <= Duration::from_secs(60));
+        assert!(guard.begin_attempt("4.4.4.4", "alice").is_err());
+        // Other users from the same addresses may still try
+        assert_eq!(guard.locked_for("1.1.1.1", "bob"), None);
+    }
+
+    #[test]
+    fn test_ip_lockout_and_success_reset() {
+        let guard = guard(100, 2, Duration::from_secs(60));
+        let attempt = guard.begin_attempt("1.1.1.1", "alice").unwrap();
+        guard.record_success(attempt);
+        guard.begin_attempt("1.1.1.1", "bob").unwrap();
+        assert_eq!(guard.locked_for("1.1.1.1", "carol"), None);
+        guard.begin_attempt("1.1.1.1", "carol").unwrap();
+        assert!(guard.locked_for("1.1.1.1", "dave").is_some());
+        assert_eq!(guard.locked_for("2.2.2.2", "dave"), None);
+    }
+
+    #[test]
+    fn test_lockout_expires_and_counters_age_out() {
+        let guard = guard(2, 100, Duration::from_millis(100));
+        guard.begin_attempt("1.1.1.1", "alice").unwrap();
+        guard.begin_attempt("1.1.1.1", "alice").unwrap();
+        assert!(guard.locked_for("1.1.1.1", "alice").is_some());
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(guard.locked_for("1.1.1.1", "alice"), None);
+        assert!(guard.attempts.lock().unwrap().is_empty());
+
+        // A failure older than the window does not count towards the next lockout
+        guard.begin_attempt("1.1.1.1", "alice").unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+        guard.begin_attempt("1.1.1.1", "alice").unwrap();
+        assert_eq!(guard.locked_for("1.1.1.1", "alice"), None);
+    }
+
+    #[test]
+    fn test_concurrent_attempts_stop_at_the_limit() {
+        let guard = std::sync::Arc::new(guard(3, 100, Duration::from_secs(60)));
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(10));
+        let threads: Vec<_> = (0..10)
+            .map(|i| {
+                let guard = guard.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let attempt = guard.begin_attempt(&format!("10.0.0.{}", i), "alice");
+                    // Hold the reservation as a slow password check would
+                    std::thread::sleep(Duration::from_millis(50));
+                    attempt.is_ok()
+                })
+            })
+            .collect();
+        let admitted = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+        assert_eq!(admitted, 3);
+        assert!(guard.locked_for("10.0.0.99", "alice").is_some());
+    }
+}
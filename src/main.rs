@@ -19,8 +19,10 @@ use indicator_math::{
 };
 // New parallel analysis lib (RustLib/indicator_math)
 use indicator_math_v2::{
-    AnalysisGenerator as V2AnalysisGenerator, AnalysisOptions as V2AnalysisOptions,
-    Candle as V2Candle, CandleMasterCode,
+    AnalysisGenerator as V2AnalysisGenerator, AnalysisManager as V2AnalysisManager,
+    AnalysisOptions as V2AnalysisOptions,
+    patterns::passes_filter as pattern_filter_passes, Candle as V2Candle, CandleMasterCode,
+    CandlePattern, MasterCodeTable, RetentionPolicy as V2RetentionPolicy,
 };
 use serde::{Deserialize, Serialize};
 use std::env;
@@ -35,19 +37,83 @@ use tokio::task::JoinHandle;
 use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
-use tower_sessions::{Expiry, MemoryStore, Session, SessionManagerLayer};
+use tower_sessions::{cookie::SameSite, Expiry, Session, SessionManagerLayer};
+use tracing::{debug, error, info, info_span, trace, warn, Instrument};
 
 // Firestore Module
 mod firestore_manager;
-use firestore_manager::{GlobalFirestore, ScanRecord, TradeRecord};
+use firestore_manager::{FirestoreError, GlobalFirestore, ScanFilter, ScanRecord, TradeRecord};
+
+// Crash-safe writes and lot number allocation for log / config files
+mod file_store;
+
+// Background Firestore writer with an on-disk retry queue
+mod firestore_outbox;
+use firestore_outbox::{FirestoreOutbox, OutboxRecord, DEFAULT_MAX_DEPTH};
+
+// Performance report (equity curve, win rate, breakdowns) over the trade history
+mod analytics;
+
+// CSV / JSON / Parquet export of the trade history
+mod trade_export;
+use trade_export::{TradeHistoryQuery, TradeHistoryRow};
+
+// Local trade storage (SQLite) with optional JSON / Firestore sinks
+mod trade_store;
+use trade_store::{
+    OpenContract, SqliteTradeStore, TradeFilter, TradeLedger, TradeSinks, TRADE_DB_FILE,
+};
+
+// Session roles and API auth middleware
+mod auth;
+use auth::Role;
+
+// User accounts (data/users.json) and the `users` admin CLI
+mod users;
+use users::{UserStore, USERS_FILE};
+
+// Encrypted Deriv token store and log redaction
+mod secrets;
+use secrets::{DerivAccount, SecretStore};
+
+// Persistent sessions and login throttling / audit
+mod login_guard;
+mod session_store;
+use login_guard::LoginGuard;
+use session_store::{SqliteSessionStore, SESSION_DB_FILE};
 
 // Market Scanner Module
 mod market_scanner;
 use market_scanner::{AssetConfig, MarketScanner, ScanConfig};
 
+mod tick_recorder;
+use tick_recorder::{RecorderConfig, RecordingQuery, TickRecorder};
+
+// Tracing setup: levels, JSON output, rolling log files, runtime filter changes
+mod logging;
+use logging::Logging;
+
+// Prometheus metrics at /metrics
+mod metrics;
+use metrics::metrics;
+
+// Ctrl-C / SIGTERM handling and bot draining
+mod shutdown;
+
 // Version tracking
 const VERSION: &str = "1.2.0";
 
+// Shared StatusDesc -> StatusCode table (also embedded in both analysis libs)
+const CANDLE_MASTER_CODES_FILE: &str = "indicator_math/candle_master_codes.json";
+
+// Closed-candle history kept in memory per asset by long-running V2 generators
+const V2_HISTORY_RETENTION: usize = 1000;
+
+// Auto-trade generator snapshots (`<asset>.json`), so a restart only backfills the gap
+const GENERATOR_SNAPSHOT_DIR: &str = "data/generator_snapshots";
+// Snapshots further behind than this many candles are dropped and history is refetched
+const MAX_SNAPSHOT_GAP: u64 = 1000;
+
 #[derive(Debug, Deserialize)]
 pub struct LoginPayload {
     username: String,
@@ -276,23 +342,17 @@ fn default_target_win() -> u32 {
 }
 
 fn load_indicator_config() -> IndicatorConfig {
-    match fs::read_to_string("config.toml") {
-        Ok(content) => match toml::from_str::<IndicatorConfig>(&content) {
-            Ok(config) => {
-                println!("📊 Loaded config: short {} period {}, medium {} period {}, long {} period {}, action_mode: {}",
-                        config.indicators.short_ema_type, config.indicators.short_ema_period,
-                        config.indicators.medium_ema_type, config.indicators.medium_ema_period,
-                        config.indicators.long_ema_type, config.indicators.long_ema_period,
-                        config.indicators.action_mode);
-                config
-            }
-            Err(e) => {
-                println!("⚠️ Config parse error, using defaults: {}", e);
-                default_indicator_config()
-            }
-        },
-        Err(_) => {
-            println!("⚠️ config.toml not found, using defaults");
+    match file_store::read_recovering(Path::new("config.toml"), toml::from_str::<IndicatorConfig>) {
+        Some(config) => {
+            info!("📊 Loaded config: short {} period {}, medium {} period {}, long {} period {}, action_mode: {}",
+                    config.indicators.short_ema_type, config.indicators.short_ema_period,
+                    config.indicators.medium_ema_type, config.indicators.medium_ema_period,
+                    config.indicators.long_ema_type, config.indicators.long_ema_period,
+                    config.indicators.action_mode);
+            config
+        }
+        None => {
+            warn!("⚠️ config.toml not found or unreadable, using defaults");
             default_indicator_config()
         }
     }
@@ -301,13 +361,13 @@ fn load_indicator_config() -> IndicatorConfig {
 fn save_indicator_config(config: &IndicatorConfig) {
     match toml::to_string_pretty(config) {
         Ok(toml_str) => {
-            if let Err(e) = fs::write("config.toml", toml_str) {
-                println!("❌ Failed to save config: {}", e);
+            if let Err(e) = file_store::write_atomic(Path::new("config.toml"), toml_str) {
+                error!("❌ Failed to save config: {}", e);
             } else {
-                println!("💾 Config saved successfully.");
+                info!("💾 Config saved successfully.");
             }
         }
-        Err(e) => println!("❌ Failed to serialize config: {}", e),
+        Err(e) => error!("❌ Failed to serialize config: {}", e),
     }
 }
 
@@ -477,7 +537,9 @@ pub fn save_day_trade_log(wrapper: &DayTradeWrapper) {
     let file_path = format!("{}/trade.json", folder_path);
 
     if let Ok(json) = serde_json::to_string_pretty(wrapper) {
-        let _ = fs::write(file_path, json);
+        if let Err(e) = file_store::write_atomic(Path::new(&file_path), json) {
+            error!("❌ Failed to save {}: {}", file_path, e);
+        }
     }
 }
 // ===============================================================
@@ -495,36 +557,12 @@ pub fn ensure_daily_folder(folder_name: &str) -> String {
     path
 }
 
-pub fn get_next_lot_no(folder_path: &str) -> u32 {
-    let mut max_lot = 0;
-    if let Ok(entries) = fs::read_dir(folder_path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    if file_name.starts_with("lot_") && file_name.ends_with(".json") {
-                        if let Some(num_part) = file_name
-                            .trim_start_matches("lot_")
-                            .trim_end_matches(".json")
-                            .parse::<u32>()
-                            .ok()
-                        {
-                            if num_part > max_lot {
-                                max_lot = num_part;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    max_lot + 1
-}
-
 pub fn save_lot_log(folder_path: &str, lot_log: &LotLog) {
     let file_path = format!("{}/lot_{}.json", folder_path, lot_log.lot_no);
     if let Ok(json) = serde_json::to_string_pretty(lot_log) {
-        let _ = fs::write(file_path, json);
+        if let Err(e) = file_store::write_atomic(Path::new(&file_path), json) {
+            error!("❌ Failed to save {}: {}", file_path, e);
+        }
     }
 }
 
@@ -553,6 +591,10 @@ pub struct TradeSignalEntry {
     pub call_signal: String,
     #[serde(rename = "isActive")]
     pub is_active: String,
+    /// Comma-separated candle patterns (e.g. "Hammer,BullishEngulfing"); when set,
+    /// a matched signal only trades if one of them completed on the signal candle
+    #[serde(rename = "patternFilter", default)]
+    pub pattern_filter: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -581,6 +623,12 @@ pub struct CompactAnalysis {
     pub time: u64,
     pub action: String,
     pub status_code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+}
+
+fn pattern_names(patterns: &[CandlePattern]) -> Vec<String> {
+    patterns.iter().map(|p| p.name.clone()).collect()
 }
 
 #[derive(Serialize, Clone, Debug, Deserialize)]
@@ -625,6 +673,9 @@ struct ClientCommand {
     app_id: String,
     #[serde(default)]
     api_token: String,
+    /// Stored Deriv account to trade on: "demo" or "real" (default: demo if stored)
+    #[serde(default)]
+    account: String,
     #[serde(default)]
     duration: u64,
     #[serde(default)]
@@ -661,8 +712,16 @@ pub struct AutoTradeStatusMessage {
 struct AppState {
     tx: broadcast::Sender<BroadcastMessage>,
     current_conn: Arc<Mutex<Option<(JoinHandle<()>, tokio::sync::mpsc::Sender<String>)>>>,
-    firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: Arc<TradeLedger>,
+    users: Arc<UserStore>,
+    secrets: Option<Arc<SecretStore>>,
+    login_guard: Arc<LoginGuard>,
+    firestore: Arc<GlobalFirestore>,
     scanner: Arc<tokio::sync::RwLock<Option<MarketScanner>>>,
+    recorder: Arc<TickRecorder>,
+    logging: Arc<Logging>,
+    // Auto-trade V2 generators, kept here so shutdown can snapshot them
+    analysis: Arc<V2AnalysisManager>,
     // Auto-trade handle — persists beyond browser disconnect
     auto_trade: Arc<Mutex<Option<(JoinHandle<()>, tokio::sync::mpsc::Sender<String>)>>>,
 }
@@ -671,78 +730,385 @@ struct AppState {
 async fn main() {
     dotenv::dotenv().ok();
 
-    // Session setup
-    let session_store = MemoryStore::default();
+    // `rust-deriv-relay users ...` manages accounts and exits
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("users") {
+        if let Err(e) = users::run_cli(&args[1..]) {
+            println!("❌ {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let logging = Arc::new(Logging::init());
+
+    let users = Arc::new(UserStore::new(USERS_FILE));
+    match users.is_empty() {
+        Ok(true) => warn!(
+            "⚠️ No accounts in {}: only APP_USER/APP_PASSWORD can log in. Add users with `rust-deriv-relay users add <name> admin`",
+            USERS_FILE
+        ),
+        Ok(false) => {}
+        Err(e) => error!("❌ {:#}: every login is refused until it is fixed", e),
+    }
+
+    // Deriv tokens are stored encrypted; without RELAY_SECRET_KEY only browser-sent tokens work
+    let secrets = match SecretStore::from_env() {
+        Ok(Some(store)) => {
+            match store.rekey() {
+                Ok(0) => {}
+                Ok(n) => info!("🔑 Re-encrypted {} Deriv tokens with the new key", n),
+                Err(e) => error!("❌ Secret store re-key failed: {}", e),
+            }
+            // Older versions kept tokens in plaintext and did not record the account type
+            match users.migrate_legacy_tokens(|username, token| {
+                store.set_token(username, DerivAccount::Real, token)
+            }) {
+                Ok(moved) => {
+                    for username in moved {
+                        info!(
+                            "🔑 Moved {}'s plaintext Deriv token into the secret store as 'real'; check /api/account/tokens",
+                            username
+                        );
+                    }
+                }
+                Err(e) => error!("❌ Migrating plaintext Deriv tokens failed: {}", e),
+            }
+            Some(Arc::new(store))
+        }
+        Ok(None) => {
+            warn!("⚠️ RELAY_SECRET_KEY not set: server-side Deriv token storage disabled");
+            None
+        }
+        Err(e) => {
+            error!("❌ Secret store disabled: {}", e);
+            None
+        }
+    };
+
+    // Session setup: stored in SQLite so logins survive restarts.
+    // Behind TLS set SESSION_COOKIE_SECURE=true; SESSION_COOKIE_SAMESITE is strict|lax|none.
+    let session_store = match SqliteSessionStore::open(Path::new(SESSION_DB_FILE)) {
+        Ok(store) => store,
+        Err(e) => {
+            error!(
+                "❌ Session store open failed ({}), sessions will not persist",
+                e
+            );
+            SqliteSessionStore::open_in_memory().expect("in-memory SQLite unavailable")
+        }
+    };
+    {
+        let store = session_store.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = store.delete_expired().await {
+                    warn!("⚠️ Session cleanup failed: {}", e);
+                }
+            }
+        });
+    }
+    let cookie_secure = env::var("SESSION_COOKIE_SECURE").is_ok_and(|v| v == "1" || v == "true");
+    let same_site = match env::var("SESSION_COOKIE_SAMESITE")
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "strict" => SameSite::Strict,
+        "none" => SameSite::None,
+        _ => SameSite::Lax,
+    };
+    let idle_minutes = env::var("SESSION_IDLE_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(60);
+    info!(
+        "🍪 Sessions: secure={} same_site={:?} idle={}m",
+        cookie_secure, same_site, idle_minutes
+    );
     let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(Duration::minutes(60)));
+        .with_secure(cookie_secure)
+        .with_same_site(same_site)
+        .with_http_only(true)
+        .with_expiry(Expiry::OnInactivity(Duration::minutes(idle_minutes)));
 
     let (tx, _) = broadcast::channel::<BroadcastMessage>(1024);
 
     // Initialize Firestore
-    let mut firestore = GlobalFirestore::new();
-    let project_id =
-        env::var("FIRESTORE_PROJECT_ID").unwrap_or_else(|_| "your-project-id".to_string());
-    if project_id != "your-project-id" {
-        if let Err(e) = firestore.initialize(&project_id).await {
-            println!("⚠️ Firestore initialization warning: {}", e);
+    let emulator = firestore_manager::emulator_host();
+    let project_id = env::var("FIRESTORE_PROJECT_ID").unwrap_or_else(|_| {
+        // The emulator accepts any `demo-` project without credentials
+        if emulator.is_some() {
+            "demo-deriv-relay".to_string()
+        } else {
+            "your-project-id".to_string()
         }
+    });
+    let firestore = if project_id != "your-project-id" {
+        GlobalFirestore::new(Some(project_id))
     } else {
-        println!("⚠️ FIRESTORE_PROJECT_ID not set in .env - Firestore disabled");
+        warn!("⚠️ FIRESTORE_PROJECT_ID not set in .env - Firestore disabled");
+        GlobalFirestore::new(None)
+    };
+    if firestore.is_configured() {
+        if let Err(e) = firestore.initialize().await {
+            warn!(
+                "⚠️ Firestore initialization warning: {} (the outbox keeps retrying)",
+                e
+            );
+        }
     }
 
-    let firestore_arc = Arc::new(tokio::sync::Mutex::new(firestore));
+    let firestore_arc = Arc::new(firestore);
+
+    // Initialize the local trade store
+    let sqlite_store = match SqliteTradeStore::open(Path::new(TRADE_DB_FILE)) {
+        Ok(store) => {
+            info!("🗄️ Trade store ready: {}", TRADE_DB_FILE);
+            Arc::new(store)
+        }
+        Err(e) => {
+            error!("❌ Trade store open failed ({}), using in-memory store", e);
+            Arc::new(SqliteTradeStore::open_in_memory().expect("in-memory SQLite unavailable"))
+        }
+    };
+    let sinks = TradeSinks::from_env();
+    info!(
+        "🗄️ Trade sinks: json_logs={} firestore={}",
+        sinks.json_logs, sinks.firestore
+    );
+
+    // Firestore writes are queued in the trade store and sent by a background task
+    let outbox = Arc::new(FirestoreOutbox::new(
+        sqlite_store.clone(),
+        DEFAULT_MAX_DEPTH,
+        sinks.firestore,
+    ));
+    tokio::spawn(outbox.clone().run(firestore_arc.clone()));
+
+    let ledger = Arc::new(TradeLedger {
+        store: sqlite_store,
+        outbox,
+        sinks,
+    });
+
+    // Contracts an earlier run bought but never saw settle (crash, or the shutdown grace
+    // period ran out). Reported once, then cleared.
+    match ledger.with_store(|store| store.open_contracts()).await {
+        Ok(open) => {
+            for c in open {
+                warn!(
+                    contract_id = %c.contract_id,
+                    bot = %c.bot,
+                    asset = %c.symbol,
+                    "⚠️ {} contract opened {} (stake {}, lot {}) was not settled by the last run; check its result in the Deriv statement",
+                    c.contract_type,
+                    c.opened_at,
+                    c.stake,
+                    c.lot_no
+                );
+                ledger.contract_settled(&c.contract_id).await;
+            }
+        }
+        Err(e) => error!("❌ Reading open contracts failed: {}", e),
+    }
 
     // Initialize Market Scanner
-    let scanner = MarketScanner::new(firestore_arc.clone());
-    println!("📊 Market Scanner initialized");
+    let scanner = MarketScanner::new(ledger.clone());
+    info!("📊 Market Scanner initialized");
+
+    // Server-side tick/candle recorder; TICK_RECORDER_ASSETS starts it at boot
+    let recorder = Arc::new(TickRecorder::new());
+    if let Some(config) = RecorderConfig::from_env() {
+        if let Err(e) = recorder.start(config).await {
+            warn!("⚠️ Tick Recorder autostart failed: {}", e);
+        }
+    }
 
     let state = Arc::new(AppState {
         tx,
         current_conn: Arc::new(Mutex::new(None)),
+        ledger,
+        users,
+        secrets,
+        login_guard: Arc::new(LoginGuard::from_env()),
         firestore: firestore_arc,
         scanner: Arc::new(tokio::sync::RwLock::new(Some(scanner))),
+        recorder,
+        logging: logging.clone(),
+        analysis: Arc::new(
+            V2AnalysisManager::new(V2AnalysisOptions::default(), build_candle_master_codes())
+                .with_retention(V2RetentionPolicy::KeepLast(V2_HISTORY_RETENTION)),
+        ),
         auto_trade: Arc::new(Mutex::new(None)),
     });
+    let shutdown_state = state.clone();
 
-    let app = Router::new()
-        .route("/login", get(serve_login_html).post(login_handler))
-        .route("/logout", get(logout_handler))
-        .route("/ws", get(websocket_handler))
-        // Protected fallback using manual handler
-        .route("/save_tick_history", post(save_tick_history_handler))
+    // Every API route requires a session; writes also need the trader/admin role
+    let api = Router::new()
+        .route(
+            "/save_tick_history",
+            post(save_tick_history_handler)
+                // JSON-escaping can roughly double the size of `data`
+                .layer(axum::extract::DefaultBodyLimit::max(
+                    2 * MAX_TICK_UPLOAD_BYTES,
+                )),
+        )
         .route("/api/save_scan", post(save_scan_handler))
         // Scanner API endpoints
         .route("/api/scanner/start", post(scanner_start_handler))
         .route("/api/scanner/stop", post(scanner_stop_handler))
         .route("/api/scanner/status", get(scanner_status_handler))
+        // Tick recorder API endpoints
+        .route("/api/recorder/start", post(recorder_start_handler))
+        .route("/api/recorder/stop", post(recorder_stop_handler))
+        .route("/api/recorder/status", get(recorder_status_handler))
+        .route("/api/recorder/ticks", get(recorder_ticks_handler))
+        .route("/api/recorder/candles", get(recorder_candles_handler))
+        .route("/api/recorder/gaps", get(recorder_gaps_handler))
         .route("/api/system/resources", get(system_resources_handler)) // NEW
+        .route("/api/master-codes", get(master_codes_handler))
         // Trade Logging API endpoint
         .route("/api/save-trade", post(save_trade_handler))
+        .route("/api/trades", get(query_trades_handler))
+        .route(
+            "/api/firestore/outbox",
+            get(firestore_outbox_status_handler),
+        )
+        .route("/api/firestore/trades", get(firestore_trades_handler))
+        .route("/api/firestore/scans", get(firestore_scans_handler))
+        .route(
+            "/api/firestore/scans/top",
+            get(firestore_top_ranked_handler),
+        )
+        .route("/api/trade_history", get(trade_history_export_handler))
+        .route(
+            "/api/analytics/performance",
+            get(performance_report_handler),
+        )
         .route(
             "/api/trade_history/today",
             get(get_today_trade_history_handler),
         )
+        // Account of the logged-in user
+        .route("/api/account", get(account_handler))
+        .route("/api/account/tokens", get(list_tokens_handler))
+        .route(
+            "/api/account/tokens/:account",
+            post(set_token_handler).delete(delete_token_handler),
+        )
         // Trading Config API endpoints
         .route(
             "/api/trading-config",
             get(get_trading_config_handler).post(save_trading_config_handler),
         )
+        // Log filter (admin only)
+        .route(
+            "/api/admin/log-filter",
+            get(get_log_filter_handler).put(set_log_filter_handler),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.users.clone(),
+            auth::require_role,
+        ));
+
+    let app = Router::new()
+        .route("/login", get(serve_login_html).post(login_handler))
+        .route("/logout", get(logout_handler))
+        .route("/ws", get(websocket_handler))
+        // Needs a logged-in session, or the METRICS_TOKEN bearer token for scrapers
+        .route("/metrics", get(metrics_handler))
+        .merge(api)
+        // Protected fallback using manual handler
         .fallback(get(protected_file_handler))
         .layer(session_layer)
         .with_state(state);
 
-    println!("-----------------------------------------");
-    println!(
+    info!("-----------------------------------------");
+    info!(
         "🚀 RELAY SERVER v{} STARTING AT http://localhost:8080",
         VERSION
     );
-    println!("🔐 Authentication Enabled (User from .env)");
-    println!("📂 Make sure 'public/index.html' exists!");
-    println!("-----------------------------------------");
+    info!("🔐 Authentication Enabled (User from .env)");
+    info!("📂 Make sure 'public/index.html' exists!");
+    info!("-----------------------------------------");
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses are needed for per-IP login throttling
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown::signal())
+    .await
+    .unwrap();
+
+    drain_on_shutdown(&shutdown_state).await;
+    info!("👋 Relay stopped");
+    logging.flush();
+}
+
+/// Runs after the server stopped: drain the bots, stop the scanner and recorder, then give
+/// the Firestore outbox a last send
+async fn drain_on_shutdown(state: &AppState) {
+    let grace = shutdown::grace_period();
+    let bots: Vec<_> = [&state.current_conn, &state.auto_trade]
+        .into_iter()
+        .filter_map(|slot| slot.lock().unwrap().take())
+        .collect();
+    info!(
+        "🛑 Draining {} bots (up to {}s for open contracts)",
+        bots.len(),
+        grace.as_secs()
+    );
+    for (_, cmd_tx) in &bots {
+        let _ = cmd_tx.send(shutdown::DRAIN.to_string()).await;
+    }
+
+    if let Some(scanner) = state.scanner.read().await.as_ref() {
+        let _ = scanner.stop().await;
+    }
+    let _ = state.recorder.stop().await;
+
+    let deadline = tokio::time::Instant::now() + grace;
+    for (handle, _) in bots {
+        let abort = handle.abort_handle();
+        if tokio::time::timeout_at(deadline, handle).await.is_err() {
+            abort.abort();
+        }
+    }
+    match state
+        .analysis
+        .save_snapshots(Path::new(GENERATOR_SNAPSHOT_DIR))
+    {
+        Ok(0) => {}
+        Ok(saved) => info!("💾 Saved {} generator snapshots", saved),
+        Err(e) => error!("❌ Saving generator snapshots failed: {}", e),
+    }
+    match state
+        .ledger
+        .with_store(|store| store.open_contracts())
+        .await
+    {
+        Ok(open) if !open.is_empty() => {
+            let ids: Vec<&str> = open.iter().map(|c| c.contract_id.as_str()).collect();
+            warn!(
+                "⚠️ {} contracts still open, kept for the next start: {}",
+                open.len(),
+                ids.join(", ")
+            );
+        }
+        Ok(_) => info!("✅ No open contracts left"),
+        Err(e) => error!("❌ Reading open contracts failed: {}", e),
+    }
+
+    state
+        .ledger
+        .outbox
+        .flush(&state.firestore, shutdown::OUTBOX_FLUSH_TIMEOUT)
+        .await;
 }
 
 // Auth Handlers
@@ -754,18 +1120,59 @@ async fn serve_login_html() -> Html<String> {
 }
 
 async fn login_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
     session: Session,
     axum::Json(payload): axum::Json<LoginPayload>,
 ) -> Response {
-    let env_user = env::var("APP_USER").unwrap_or_else(|_| "admin".to_string());
-    let env_pass = env::var("APP_PASSWORD").unwrap_or_else(|_| "password".to_string());
+    let ip = login_guard::client_ip(peer, &headers);
+    // Bound what an attacker can put into the throttle map and audit log
+    let attempted: String = payload.username.chars().take(64).collect();
+    let guard = state.login_guard.clone();
+
+    // Counted as a failure before verifying, so concurrent requests cannot exceed the limit
+    let attempt = match guard.begin_attempt(&ip, &attempted) {
+        Ok(attempt) => attempt,
+        Err(remaining) => {
+            guard.audit(&ip, &attempted, "locked");
+            return Response::builder()
+                .status(429)
+                .header("Retry-After", remaining.as_secs().max(1).to_string())
+                .body(
+                    format!(
+                        "Too many failed logins, try again in {} minutes",
+                        remaining.as_secs().div_ceil(60)
+                    )
+                    .into(),
+                )
+                .unwrap();
+        }
+    };
 
-    if payload.username == env_user && payload.password == env_pass {
-        let _ = session.insert("user", "authenticated").await;
+    // argon2 verification is CPU-bound; keep it off the async workers
+    let users = state.users.clone();
+    let account = tokio::task::spawn_blocking(move || {
+        users.authenticate(&payload.username, &payload.password)
+    })
+    .await
+    .ok()
+    .flatten();
+
+    if let Some((username, role)) = account {
+        info!("🔐 Login: {} ({}) from {}", username, role.as_str(), ip);
+        guard.record_success(attempt);
+        guard.audit(&ip, &username, "success");
+        // New id on login so a session id planted before authentication is useless
+        let _ = session.cycle_id().await;
+        let _ = session.insert(auth::SESSION_USER_KEY, &username).await;
+        let _ = session.insert(auth::SESSION_ROLE_KEY, role.as_str()).await;
         // Return 200 OK
         return Response::builder().status(200).body("OK".into()).unwrap();
     }
 
+    warn!("🚫 Failed login for {} from {}", attempted, ip);
+    guard.audit(&ip, &attempted, "failure");
     Response::builder()
         .status(401)
         .body("Invalid credentials".into())
@@ -777,13 +1184,13 @@ async fn logout_handler(session: Session) -> Redirect {
     Redirect::to("/login")
 }
 
-async fn protected_file_handler(session: Session, req: Request) -> Response {
-    if session
-        .get::<String>("user")
-        .await
-        .unwrap_or(None)
-        .is_some()
-    {
+async fn protected_file_handler(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    req: Request,
+) -> Response {
+    // Checked against the users file, so removed accounts lose the dashboard too
+    if auth::current_user(&session, &state.users).await.is_some() {
         let service = ServeDir::new("public");
         match service.oneshot(req).await {
             Ok(res) => res.into_response(),
@@ -801,24 +1208,30 @@ async fn websocket_handler(
     session: Session,
     State(state): State<Arc<AppState>>,
 ) -> Response {
-    if session
-        .get::<String>("user")
-        .await
-        .unwrap_or(None)
-        .is_none()
-    {
+    let Some((username, role)) = auth::current_user(&session, &state.users).await else {
         return Redirect::to("/login").into_response();
-    }
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    };
+    let span = info_span!("browser", user = %username);
+    ws.on_upgrade(move |socket| handle_socket(socket, state, username, role).instrument(span))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
-    println!("🔌 New Browser connected");
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, username: String, role: Role) {
+    info!("🔌 New Browser connected ({}, {})", username, role.as_str());
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.tx.subscribe();
 
+    metrics().browsers.inc();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                // A slow browser skips what it missed instead of being dropped
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    metrics().broadcast_lagged.inc_by(skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
             if let Ok(json) = serde_json::to_string(&msg) {
                 if sender.send(Message::Text(json)).await.is_err() {
                     break;
@@ -828,15 +1241,46 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     });
 
     let state_clone = state.clone();
-    let mut recv_task = tokio::spawn(async move {
+    let recv = async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
-                println!("📥 Browser sent: {}", text);
-
+                debug!("📥 Browser sent: {}", secrets::redact(&text));
+
+                // The account may have been removed or changed role since the socket opened
+                let role = match auth::account_role(&state_clone.users, &username).await {
+                    Ok(Some(current)) => current,
+                    Ok(None) => {
+                        warn!("🚫 Closing socket: {}'s account no longer exists", username);
+                        break;
+                    }
+                    Err(e) => {
+                        error!(
+                            "❌ Cannot check {}'s account, command ignored: {:#}",
+                            username, e
+                        );
+                        continue;
+                    }
+                };
                 match serde_json::from_str::<ClientCommand>(&text) {
-                    Ok(req) => {
+                    Ok(req) if role < auth::ws_command_role(&req.command) => {
+                        warn!("🚫 {} denied for {} session", req.command, role.as_str());
+                    }
+                    Ok(req) if shutdown::requested() && req.command.starts_with("START_") => {
+                        warn!("🛑 {} refused: server is shutting down", req.command);
+                    }
+                    Ok(mut req) => {
+                        // Trade with the logged-in user's own Deriv account when one is stored
+                        if !req.api_token.is_empty() {
+                            secrets::register_secret(&req.api_token);
+                        }
+                        let stored = state_clone.secrets.as_ref().and_then(|s| {
+                            s.trading_token(&username, DerivAccount::parse(&req.account))
+                        });
+                        if let Some((_, token)) = stored {
+                            req.api_token = token;
+                        }
                         if req.command == "START_DERIV" {
-                            println!("🎯 Valid Command! Requesting Asset: {}", req.asset);
+                            info!("🎯 Valid Command! Requesting Asset: {}", req.asset);
 
                             let old_conn = {
                                 let mut conn_guard = state_clone.current_conn.lock().unwrap();
@@ -844,7 +1288,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             };
 
                             if let Some((old_handle, old_tx)) = old_conn {
-                                println!("🛑 Stopping old connection...");
+                                info!("🛑 Stopping old connection...");
                                 let _ = old_tx.send("FORGET".to_string()).await;
                                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                                 old_handle.abort();
@@ -852,18 +1296,28 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
                             let tx = state_clone.tx.clone();
                             let (cmd_tx, cmd_rx) = tokio::sync::mpsc::channel::<String>(10);
-                            let firestore = state_clone.firestore.clone();
-
-                            let handle = tokio::spawn(async move {
-                                connect_to_deriv(tx, req, cmd_rx, firestore).await;
-                            });
+                            let ledger = state_clone.ledger.clone();
+
+                            let span = info_span!(
+                                parent: None,
+                                "bot",
+                                kind = "single",
+                                asset = %req.asset,
+                                user = %username
+                            );
+                            let handle = tokio::spawn(
+                                async move {
+                                    connect_to_deriv(tx, req, cmd_rx, ledger).await;
+                                }
+                                .instrument(span),
+                            );
 
                             {
                                 let mut conn_guard = state_clone.current_conn.lock().unwrap();
                                 *conn_guard = Some((handle, cmd_tx));
                             }
                         } else if req.command == "UPDATE_MODE" {
-                            println!("🔄 Request to Update Trade Mode: {}", req.trade_mode);
+                            info!("🔄 Request to Update Trade Mode: {}", req.trade_mode);
                             // Send to current_conn (single asset viewer)
                             let cmd_tx = {
                                 state_clone
@@ -890,7 +1344,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 let _ = tx.send(text.clone()).await;
                             }
                         } else if req.command == "UPDATE_PARAMS" {
-                            println!("🔄 Request to Update Params: Money={}, Stake={}, Duration={} {}, Targets=P:{}/W:{}",
+                            info!("🔄 Request to Update Params: Money={}, Stake={}, Duration={} {}, Targets=P:{}/W:{}",
                                 req.money_mode, req.initial_stake, req.duration, req.duration_unit, req.target_profit, req.target_win);
 
                             // Load config, update, and save
@@ -935,7 +1389,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 let _ = tx.send(text.clone()).await;
                             }
                         } else if req.command == "SELL" {
-                            println!("🔻 Request to Sell Contract: {}", req.contract_id);
+                            info!(contract_id = %req.contract_id, "🔻 Request to Sell Contract");
                             // Send to current_conn
                             let cmd_tx = {
                                 state_clone
@@ -962,7 +1416,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 let _ = tx.send(format!("SELL:{}", req.contract_id)).await;
                             }
                         } else if req.command == "STOP_STREAMS" {
-                            println!("🛑 Request to Stop Streams (Keep Alive)");
+                            info!("🛑 Request to Stop Streams (Keep Alive)");
                             let cmd_tx = {
                                 state_clone
                                     .current_conn
@@ -974,12 +1428,10 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             if let Some(tx) = cmd_tx {
                                 let _ = tx.send("STOP_STREAMS".to_string()).await;
                             } else {
-                                println!("⚠️ No active connection to update.");
+                                warn!("⚠️ No active connection to update.");
                             }
                         } else if req.command == "START_MULTI_TRADE" {
-                            println!(
-                                "🎯 START_MULTI_TRADE: Starting multi-asset parallel analysis"
-                            );
+                            info!("🎯 START_MULTI_TRADE: Starting multi-asset parallel analysis");
 
                             // Stop existing connection if any
                             let old_conn = {
@@ -987,7 +1439,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 conn_guard.take()
                             };
                             if let Some((old_handle, old_tx)) = old_conn {
-                                println!("🛑 Stopping old connection...");
+                                info!("🛑 Stopping old connection...");
                                 let _ = old_tx.send("FORGET".to_string()).await;
                                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                                 old_handle.abort();
@@ -995,21 +1447,31 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
                             let tx = state_clone.tx.clone();
                             let (cmd_tx, cmd_rx) = tokio::sync::mpsc::channel::<String>(10);
-                            let firestore = state_clone.firestore.clone();
-
-                            let handle = tokio::spawn(async move {
-                                connect_multi_asset(tx, req, cmd_rx, firestore).await;
-                            });
+                            let ledger = state_clone.ledger.clone();
+
+                            let span = info_span!(
+                                parent: None,
+                                "bot",
+                                kind = "multi",
+                                assets = ?req.assets,
+                                user = %username
+                            );
+                            let handle = tokio::spawn(
+                                async move {
+                                    connect_multi_asset(tx, req, cmd_rx, ledger).await;
+                                }
+                                .instrument(span),
+                            );
 
                             {
                                 let mut conn_guard = state_clone.current_conn.lock().unwrap();
                                 *conn_guard = Some((handle, cmd_tx));
                             }
                         } else if req.command == "START_AUTO_MULTI" {
-                            println!(
+                            info!(
                                 "🎯 START_AUTO_MULTI: Starting browser-independent multi-asset auto-trade"
                             );
-                            println!("   Assets: {:?}", req.assets);
+                            info!("   Assets: {:?}", req.assets);
 
                             // Stop existing auto-trade if any
                             let old_auto = {
@@ -1017,7 +1479,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 auto_guard.take()
                             };
                             if let Some((old_handle, old_tx)) = old_auto {
-                                println!("🛑 Stopping previous auto-trade...");
+                                info!("🛑 Stopping previous auto-trade...");
                                 let _ = old_tx.send("STOP".to_string()).await;
                                 tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
                                 old_handle.abort();
@@ -1025,18 +1487,29 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
                             let tx = state_clone.tx.clone();
                             let (cmd_tx, cmd_rx) = tokio::sync::mpsc::channel::<String>(10);
-                            let firestore = state_clone.firestore.clone();
-
-                            let handle = tokio::spawn(async move {
-                                auto_multi_trade(tx, req, cmd_rx, firestore).await;
-                            });
+                            let ledger = state_clone.ledger.clone();
+                            let analysis = state_clone.analysis.clone();
+
+                            let span = info_span!(
+                                parent: None,
+                                "bot",
+                                kind = "auto",
+                                assets = ?req.assets,
+                                user = %username
+                            );
+                            let handle = tokio::spawn(
+                                async move {
+                                    auto_multi_trade(tx, req, cmd_rx, ledger, analysis).await;
+                                }
+                                .instrument(span),
+                            );
 
                             {
                                 let mut auto_guard = state_clone.auto_trade.lock().unwrap();
                                 *auto_guard = Some((handle, cmd_tx));
                             }
                         } else if req.command == "SYNC_STATUS" {
-                            println!("🔄 Request to Sync Status from Browser");
+                            info!("🔄 Request to Sync Status from Browser");
                             let single_tx = {
                                 state_clone
                                     .current_conn
@@ -1061,7 +1534,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 let _ = tx.send("SYNC".to_string()).await;
                             }
                         } else if req.command == "STOP_AUTO_TRADE" {
-                            println!("🛑 STOP_AUTO_TRADE: Stopping auto-trade task");
+                            info!("🛑 STOP_AUTO_TRADE: Stopping auto-trade task");
                             let old_auto = {
                                 let mut auto_guard = state_clone.auto_trade.lock().unwrap();
                                 auto_guard.take()
@@ -1070,29 +1543,50 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 let _ = old_tx.send("STOP".to_string()).await;
                                 tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
                                 old_handle.abort();
-                                println!("✅ Auto-trade stopped.");
+                                info!("✅ Auto-trade stopped.");
                             } else {
-                                println!("⚠️ No auto-trade running.");
+                                warn!("⚠️ No auto-trade running.");
                             }
                         }
                     }
-                    Err(e) => println!("⚠️ JSON Parse Error: {}", e),
+                    Err(e) => warn!("⚠️ JSON Parse Error: {}", e),
                 }
             }
         }
-    });
+    };
+    // Keep the browser span on commands logged from the receive task
+    let mut recv_task = tokio::spawn(recv.in_current_span());
 
     tokio::select! {
-        _ = (&mut send_task) => println!("📤 Send task ended"),
-        _ = (&mut recv_task) => println!("📥 Receive task ended"),
+        _ = (&mut send_task) => debug!("📤 Send task ended"),
+        _ = (&mut recv_task) => debug!("📥 Receive task ended"),
     };
+    metrics().browsers.dec();
+}
+
+/// Ends every tick and candle stream on a Deriv connection. Contract streams keep running,
+/// so results of open contracts still arrive.
+fn forget_market_streams() -> TungsteniteMessage {
+    TungsteniteMessage::Text(serde_json::json!({"forget_all": ["ticks", "candles"]}).to_string())
+}
+
+/// Step of the martingale ladder a trade was bought at, found from its stake. None in
+/// fixed-stake mode, or when the stake is not on the ladder.
+fn martingale_step(money_mode: &str, ladder: &[f64], stake: f64) -> Option<u32> {
+    if money_mode != "martingale" {
+        return None;
+    }
+    ladder
+        .iter()
+        .position(|s| (s - stake).abs() < 1e-9)
+        .map(|i| i as u32)
 }
 
 async fn connect_to_deriv(
     tx: broadcast::Sender<BroadcastMessage>,
     config: ClientCommand,
     mut cmd_rx: tokio::sync::mpsc::Receiver<String>,
-    firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: Arc<TradeLedger>,
 ) {
     let app_id = if config.app_id.is_empty() {
         "66726".to_string()
@@ -1100,11 +1594,12 @@ async fn connect_to_deriv(
         config.app_id
     };
     let url = format!("wss://ws.derivws.com/websockets/v3?app_id={}", app_id);
-    println!("🌐 Connecting to Deriv API for asset: {}...", config.asset);
+    info!("🌐 Connecting to Deriv API for asset: {}...", config.asset);
 
     match connect_async(&url).await {
         Ok((ws_stream, _)) => {
-            println!("✅ Connected to Deriv: {}", config.asset);
+            info!("✅ Connected to Deriv: {}", config.asset);
+            let _connected = metrics().connection("single");
             let (mut write, mut read) = ws_stream.split();
 
             let mut tick_sub_id: Option<String> = None;
@@ -1117,6 +1612,11 @@ async fn connect_to_deriv(
             let mut last_trade_minute: Option<u64> = None;
             let mut _pending_contract_id: Option<String> = None;
             let mut pending_contract_type: Option<String> = None;
+            // Contracts waiting for a result and buys not answered yet; a drain waits for both
+            let mut open_contracts: std::collections::HashSet<String> =
+                std::collections::HashSet::new();
+            let mut buys_in_flight = 0usize;
+            let mut draining = false;
             let mut current_trade_mode = config.trade_mode.clone();
             let mut current_money_mode = config.money_mode.clone();
 
@@ -1151,11 +1651,11 @@ async fn connect_to_deriv(
 
             // Daily Lot Logging
             let mut daily_folder = ensure_daily_folder(&get_daily_folder_name());
-            let mut current_lot_no = get_next_lot_no(&daily_folder);
+            let mut current_lot_no = file_store::allocate_lot_no(&daily_folder);
             let mut trades_for_lot: Vec<TradeObject> = Vec::new();
             let mut trade_count_in_lot = 0;
 
-            println!(
+            info!(
                 "📂 Current Daily Folder: {}, Starting Lot No: {}",
                 daily_folder, current_lot_no
             );
@@ -1187,8 +1687,8 @@ async fn connect_to_deriv(
                 "ticks": "R_100",
                 "subscribe": 1
             });
-            // println!("📊 Subscribe  Tick candles");
-            println!("🔥🔥🔥 SUBSCRIBE TICK EXECUTED 🔥🔥🔥");
+            // info!("📊 Subscribe  Tick candles");
+            debug!("🔥🔥🔥 SUBSCRIBE TICK EXECUTED 🔥🔥🔥");
 
             let _ = write
                 .send(TungsteniteMessage::Text(tick_msg.to_string()))
@@ -1214,7 +1714,7 @@ async fn connect_to_deriv(
                     cmd = cmd_rx.recv() => {
                         if let Some(cmd) = cmd {
                             if cmd == "FORGET" {
-                                println!("📤 Sending forget for all subscriptions...");
+                                info!("📤 Sending forget for all subscriptions...");
                                 if let Some(id) = tick_sub_id.take() {
                                     let forget_msg = serde_json::json!({"forget": id});
                                     let _ = write.send(TungsteniteMessage::Text(forget_msg.to_string())).await;
@@ -1225,8 +1725,18 @@ async fn connect_to_deriv(
                                 }
                                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                                 break;
+                            } else if cmd == shutdown::DRAIN {
+                                info!("📤 Shutdown: forgetting streams, {} contracts open", open_contracts.len() + buys_in_flight);
+                                let _ = write.send(forget_market_streams()).await;
+                                tick_sub_id = None;
+                                candle_sub_id = None;
+                                current_trade_mode = "idle".to_string();
+                                draining = true;
+                                if open_contracts.is_empty() && buys_in_flight == 0 {
+                                    break;
+                                }
                             } else if cmd == "STOP_STREAMS" {
-                                println!("📤 Sending forget for all subscriptions (Connection Kept Alive)...");
+                                info!("📤 Sending forget for all subscriptions (Connection Kept Alive)...");
                                 if let Some(id) = tick_sub_id.take() {
                                     let forget_msg = serde_json::json!({"forget": id});
                                     let _ = write.send(TungsteniteMessage::Text(forget_msg.to_string())).await;
@@ -1248,18 +1758,19 @@ async fn connect_to_deriv(
 
                                 // Reset lot if starting fresh
                                 if new_mode != "idle" && current_trade_mode == "idle" {
-                                    println!("🆕 Starting new Lot session");
+                                    info!("🆕 Starting new Lot session");
                                     // Update folder and Lot No
                                     daily_folder = ensure_daily_folder(&get_daily_folder_name());
-                                    current_lot_no = get_next_lot_no(&daily_folder);
+                                    current_lot_no = file_store::allocate_lot_no(&daily_folder);
                                     trades_for_lot.clear();
                                     trade_count_in_lot = 0;
 
                                     lot_grand_profit = 0.0;
                                     lot_win_count = 0;
                                     lot_active = true;
+                                    metrics().bot_profit("single", lot_grand_profit);
 
-                                    println!("🔢 New Lot No: {}", current_lot_no);
+                                    info!("🔢 New Lot No: {}", current_lot_no);
 
                                     let _ = tx.send(BroadcastMessage::LotStatus(LotStatus {
                                         msg_type: "lot_status".to_string(),
@@ -1273,7 +1784,7 @@ async fn connect_to_deriv(
                                 }
 
                                 current_trade_mode = new_mode;
-                                println!("🔄 Trade Mode Updated to: {}", current_trade_mode);
+                                info!("🔄 Trade Mode Updated to: {}", current_trade_mode);
 
                             } else if cmd.starts_with("PARAMS:") {
                                 let parts: Vec<&str> = cmd.split(':').collect();
@@ -1295,7 +1806,7 @@ async fn connect_to_deriv(
                                         indicator_config.trading.target_win_count = tw;
                                     }
 
-                                    println!("✅ Params Updated: Money={}, Stake={}, Duration={} {}, T.Profit={}, T.Win={}",
+                                    info!("✅ Params Updated: Money={}, Stake={}, Duration={} {}, T.Profit={}, T.Win={}",
                                         current_money_mode, current_initial_stake, current_duration, current_duration_unit,
                                         indicator_config.trading.target_grand_profit, indicator_config.trading.target_win_count);
 
@@ -1317,7 +1828,7 @@ async fn connect_to_deriv(
                                 }
                             } else if cmd.starts_with("SELL:") {
                                 let contract_id = cmd.replace("SELL:", "");
-                                println!("🔻 Sending Sell Request for: {}", contract_id);
+                                info!(contract_id = %contract_id, "🔻 Sending Sell Request");
                                 let sell_msg = serde_json::json!({
                                     "sell": contract_id,
                                     "price": 0
@@ -1339,12 +1850,16 @@ async fn connect_to_deriv(
                     }
 
                     msg = read.next() => {
-                        println!("📊 Received Msg");
+                        trace!("📊 Received Msg");
                         if let Some(Ok(TungsteniteMessage::Text(raw_text))) = msg {
                             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw_text) {
+                                metrics().message("single", &json);
 
                                 if let Some(error) = json.get("error") {
-                                    println!("❌ API Error: {}", error.get("message").unwrap_or(&serde_json::json!("Unknown error")));
+                                    error!("❌ API Error: {}", error.get("message").unwrap_or(&serde_json::json!("Unknown error")));
+                                    if json.get("msg_type").and_then(|m| m.as_str()) == Some("buy") {
+                                        metrics().buy_failed("single", &config.asset);
+                                    }
                                     break;
                                 }
 
@@ -1353,7 +1868,7 @@ async fn connect_to_deriv(
                                     // let mut initial_analysis_sent = false;
                                     if let Some(bal) = authorize.get("balance").and_then(|b| b.as_f64()) {
                                         balance = bal;
-                                        println!("💰 Current Balance: {}", balance);
+                                        info!("💰 Current Balance: {}", balance);
 
                                         // Send balance to frontend
                                         let balance_msg = BalanceMessage {
@@ -1379,7 +1894,7 @@ async fn connect_to_deriv(
                                 // Server time
                                 if let Some(tick) = json.get("tick") {
                                     if let Some(epoch) = tick.get("epoch").and_then(|e| e.as_u64()) {
-                                        println!("📊 Received Tick candles");
+                                        debug!("📊 Received Tick candles");
                                         let time_msg = ServerTime {
                                             msg_type: "server_time".to_string(),
                                             server_time: epoch,
@@ -1390,7 +1905,7 @@ async fn connect_to_deriv(
 
                                 // Historical candles
                                 if let Some(candles) = json.get("candles").and_then(|c| c.as_array()) {
-                                    println!("📊 Received {} historical candles", candles.len());
+                                    info!("📊 Received {} historical candles", candles.len());
 
                                     // Clear and rebuild candles_for_ema with historical data
                                     //candles_for_ema.clear();
@@ -1442,7 +1957,7 @@ async fn connect_to_deriv(
                                             long_type: indicator_config.indicators.long_ema_type.clone(),
                                         };
 
-                                        println!("📈 Sending initial EMA data: short {} points, medium {} points, long {} points",
+                                        info!("📈 Sending initial EMA data: short {} points, medium {} points, long {} points",
                                             ema_msg.short_ema.len(), ema_msg.medium_ema.len(), ema_msg.long_ema.len());
                                         let _ = tx.send(BroadcastMessage::EmaData(ema_msg));
 
@@ -1485,6 +2000,7 @@ async fn connect_to_deriv(
                                                 time: ic.time,
                                                 action: decision,
                                                 status_code: v2_result.status_code,
+                                                patterns: pattern_names(&v2_result.patterns),
                                             });
                                         }
 
@@ -1494,7 +2010,7 @@ async fn connect_to_deriv(
                                             v2_history = v2_history.into_iter().skip(skip_amt).collect();
                                         }
 
-                                        println!("🔍 V2 Historical Analysis: {} markers for {}", v2_history.len(), config.asset);
+                                        info!("🔍 V2 Historical Analysis: {} markers for {}", v2_history.len(), config.asset);
 
                                         if !v2_history.is_empty() {
                                             let hist_msg = HistoricalAnalysis {
@@ -1608,7 +2124,7 @@ async fn connect_to_deriv(
                                                 action_source: action_source.clone(),
                                             };
 
-                                            println!("📊 Initial Analysis Sent: Action={}, Source={}, MediumSlope={}",
+                                            info!("📊 Initial Analysis Sent: Action={}, Source={}, MediumSlope={}",
                                                 action_str, action_source, latest.ema_medium_slope_direction);
 
                                             let _ = tx.send(BroadcastMessage::Analysis(analysis_msg));
@@ -1684,7 +2200,7 @@ async fn connect_to_deriv(
                                                     long_type: indicator_config.indicators.long_ema_type.clone(),
                                                 };
 
-                                                println!("📈 EMA updated at candle close: minute {}", current_minute);
+                                                debug!("📈 EMA updated at candle close: minute {}", current_minute);
                                                 let _ = tx.send(BroadcastMessage::EmaData(ema_msg));
                                             }
                                         }
@@ -1817,7 +2333,7 @@ async fn connect_to_deriv(
 
                                         // Debug log to see trading conditions
                                         if seconds <= 5 {
-                                            println!("⏰ Time: {}:{:02} | Mode: {} | Token: {} | LastMin: {:?} | CurMin: {}",
+                                            debug!("⏰ Time: {}:{:02} | Mode: {} | Token: {} | LastMin: {:?} | CurMin: {}",
                                                 candle.time / 60 % 60, seconds,
                                                 current_trade_mode,
                                                 if config.api_token.is_empty() { "NO" } else { "YES" },
@@ -1856,7 +2372,9 @@ async fn connect_to_deriv(
                                                         current_initial_stake
                                                     };
 
-                                                    if balance >= stake {
+                                                    if shutdown::requested() {
+                                                        info!("⏸️ Shutting down: not placing {} trade", ct);
+                                                    } else if balance >= stake {
                                                         let buy_msg = serde_json::json!({
                                                             "buy": "1",
                                                             "price": stake,
@@ -1871,16 +2389,18 @@ async fn connect_to_deriv(
                                                             }
                                                         });
 
-                                                        println!("📈 [{}] Placing {} trade with stake: {} (balance: {})",
+                                                        info!("📈 [{}] Placing {} trade with stake: {} (balance: {})",
                                                             if current_trade_mode == "auto" { "AUTO" } else { "MANUAL" },
                                                             ct, stake, balance);
                                                         pending_contract_type = Some(ct.to_string());
                                                         let _ = write.send(TungsteniteMessage::Text(buy_msg.to_string())).await;
+                                                        metrics().buy_sent("single", &config.asset);
+                                                        buys_in_flight += 1;
                                                     } else {
-                                                        println!("⚠️ Insufficient balance: {} < stake: {}", balance, stake);
+                                                        warn!("⚠️ Insufficient balance: {} < stake: {}", balance, stake);
                                                     }
                                                 } else if current_trade_mode == "auto" {
-                                                    println!("⏸️ AUTO mode: Hold signal, skipping trade");
+                                                    info!("⏸️ AUTO mode: Hold signal, skipping trade");
                                                 }
                                             }
                                         }
@@ -1889,6 +2409,7 @@ async fn connect_to_deriv(
 
                                 // Handle buy response - support both string and number contract_id
                                 if let Some(buy) = json.get("buy") {
+                                    buys_in_flight = buys_in_flight.saturating_sub(1);
                                     // Try to get contract_id as string first, then as number
                                     let contract_id = buy.get("contract_id")
                                         .and_then(|c| c.as_str().map(|s| s.to_string()))
@@ -1896,11 +2417,26 @@ async fn connect_to_deriv(
 
                                     if let Some(contract_id) = contract_id {
                                         _pending_contract_id = Some(contract_id.clone());
-                                        println!("✅ Contract opened: {}", contract_id);
+                                        info!(
+                                            contract_id = %contract_id,
+                                            contract_type = pending_contract_type.as_deref().unwrap_or(""),
+                                            mode = %current_trade_mode,
+                                            "✅ Contract opened"
+                                        );
 
                                         // ส่งข้อมูล trade ที่เปิดไปให้ frontend
                                         let now = Local::now();
                                         let stake = buy.get("buy_price").and_then(|p| p.as_f64()).unwrap_or(0.0);
+                                        open_contracts.insert(contract_id.clone());
+                                        ledger.contract_opened(&OpenContract {
+                                            contract_id: contract_id.clone(),
+                                            bot: "single".to_string(),
+                                            symbol: config.asset.clone(),
+                                            contract_type: pending_contract_type.clone().unwrap_or_default(),
+                                            stake,
+                                            lot_no: current_lot_no,
+                                            opened_at: now.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                                        }).await;
 
                                         let trade_opened = TradeOpened {
                                             msg_type: "trade_opened".to_string(),
@@ -1920,11 +2456,14 @@ async fn connect_to_deriv(
                                         });
                                         let _ = write.send(TungsteniteMessage::Text(proposal_msg.to_string())).await;
                                     } else {
-                                        println!("⚠️ Buy response received but no contract_id found: {}", serde_json::to_string_pretty(&buy).unwrap_or_default());
+                                        warn!("⚠️ Buy response received but no contract_id found: {}", secrets::redact(&serde_json::to_string_pretty(&buy).unwrap_or_default()));
+                                        metrics().buy_failed("single", &config.asset);
                                     }
                                 } else if json.get("error").is_none() && json.get("msg_type").and_then(|m| m.as_str()) == Some("buy") {
                                     // Log if we got a buy response but couldn't parse it
-                                    println!("⚠️ Unexpected buy response format: {}", raw_text);
+                                    buys_in_flight = buys_in_flight.saturating_sub(1);
+                                    warn!("⚠️ Unexpected buy response format: {}", secrets::redact(&raw_text));
+                                    metrics().buy_failed("single", &config.asset);
                                 }
 
                                 // Handle contract updates and result
@@ -1973,6 +2512,7 @@ async fn connect_to_deriv(
                                             date_start,
                                         };
                                         let _ = tx.send(BroadcastMessage::TradeUpdate(trade_update));
+                                        debug!(contract_id = %contract_id, current_spot, profit, "📊 Contract update");
                                     }
 
                                     // Handle final result
@@ -1991,15 +2531,19 @@ async fn connect_to_deriv(
                                             }
                                             trade_count_in_lot += 1;
                                         }
+                                        metrics().settled("single", &config.asset, is_win);
+                                        open_contracts.remove(&contract_id);
+                                        ledger.contract_settled(&contract_id).await;
+                                        metrics().bot_profit("single", lot_grand_profit);
 
                                         if is_win {
                                             current_stake_index = 0;
-                                            println!("🎉 WIN! Profit: {}, Balance: {}", profit, balance);
+                                            info!(contract_id = %contract_id, "🎉 WIN! Profit: {}, Balance: {}", profit, balance);
                                         } else {
                                             if current_money_mode == "martingale" {
                                                 current_stake_index = (current_stake_index + 1).min(martingale_stakes.len() - 1);
                                             }
-                                            println!("❌ LOSS! Loss: {}, Balance: {}", profit, balance);
+                                            info!(contract_id = %contract_id, "❌ LOSS! Loss: {}, Balance: {}", profit, balance);
                                         }
 
                                         let result = TradeResult {
@@ -2055,8 +2599,8 @@ async fn connect_to_deriv(
                                                 lot_no: current_lot_no,
                                                 trade_object_list: trades_for_lot.clone(),
                                             };
-                                            save_lot_log(&ensure_daily_folder(&get_daily_folder_name()), &lot_log);
-                                            println!("💾 Saved Trade History for Lot {}", current_lot_no);
+                                            ledger.record_lot(&lot_log).await;
+                                            info!(contract_id = %contract_id, "💾 Saved Trade History for Lot {}", current_lot_no);
 
                                             // Save to Firestore
                                             let date_start_val = proposal.get("date_start").and_then(|d| d.as_u64()).unwrap_or(0);
@@ -2083,6 +2627,8 @@ async fn connect_to_deriv(
                                                 min_profit: profit, // Final value
                                                 max_profit: profit, // Final value
                                                 status: if is_win { "win".to_string() } else { "loss".to_string() },
+                                                status_code: String::new(), // single-asset trades are not status-code driven
+                                                martingale_step: martingale_step(&current_money_mode, &martingale_stakes, stake),
                                                 entry_spot: entry_spot_val,
                                                 exit_spot: exit_spot_val,
                                                 lot_no: current_lot_no,
@@ -2091,16 +2637,12 @@ async fn connect_to_deriv(
                                                 created_at: Local::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
                                             };
 
-                                            // Save to Firestore asynchronously
-                                            let fs = firestore.lock().await;
-                                            match fs.save_trade(&trade_record).await {
-                                                Ok(doc_id) => println!("🔥 Trade saved to Firestore: {}", doc_id),
-                                                Err(e) => println!("⚠️ Firestore save error: {}", e),
-                                            }
+                                            // Local store, then Firestore if enabled
+                                            ledger.record_trade(&trade_record).await;
                                         }
 
                                         if stop_trading {
-                                            println!("🛑 STOPPING TRADE: {}", stop_reason);
+                                            info!("🛑 STOPPING TRADE: {}", stop_reason);
                                             current_trade_mode = "idle".to_string();
                                             lot_active = false;
                                         }
@@ -2117,6 +2659,11 @@ async fn connect_to_deriv(
                                         }));
                                     }
                                 }
+
+                                if draining && open_contracts.is_empty() && buys_in_flight == 0 {
+                                    info!("✅ Shutdown: open contracts settled");
+                                    break;
+                                }
                             }
                         } else {
                             break;
@@ -2125,9 +2672,12 @@ async fn connect_to_deriv(
                 }
             }
 
-            println!("🔌 Deriv connection closed for: {}", config.asset);
+            info!("🔌 Deriv connection closed for: {}", config.asset);
+        }
+        Err(e) => {
+            metrics().connect_failed("single");
+            error!("❌ Deriv Connection Failed: {}", e);
         }
-        Err(e) => println!("❌ Deriv Connection Failed: {}", e),
     }
 }
 
@@ -2138,95 +2688,63 @@ async fn connect_to_deriv(
 //  CandleMasterCode is used for StatusCode resolution.
 // ============================================================================
 
-/// Build CandleMasterCode list from the same mapping as old lib's build_status_code_map
+/// Load the CandleMasterCode list from the shared table file (falls back to the embedded copy)
 fn build_candle_master_codes() -> Vec<CandleMasterCode> {
-    let pairs: Vec<(&str, u32)> = vec![
-        ("L-D-D-G-C", 2),
-        ("L-D-D-G-D", 3),
-        ("L-D-D-G-N", 4),
-        ("L-D-D-R-C", 5),
-        ("L-D-D-R-D", 6),
-        ("L-D-D-R-N", 7),
-        ("L-D-F-G-C", 8),
-        ("L-D-F-G-D", 9),
-        ("L-D-F-G-N", 10),
-        ("L-D-F-R-C", 11),
-        ("L-D-F-R-D", 12),
-        ("L-D-F-R-N", 13),
-        ("L-D-U-G-C", 14),
-        ("L-D-U-G-D", 15),
-        ("L-D-U-G-N", 16),
-        ("L-D-U-R-C", 17),
-        ("L-D-U-R-D", 18),
-        ("L-D-U-R-N", 19),
-        ("L-F-D-G-C", 20),
-        ("L-F-D-G-N", 21),
-        ("L-F-D-R-C", 22),
-        ("L-F-D-R-N", 23),
-        ("L-F-F-G-C", 24),
-        ("L-F-F-G-N", 25),
-        ("L-F-F-R-N", 26),
-        ("L-F-U-G-C", 27),
-        ("L-F-U-G-D", 28),
-        ("L-F-U-G-N", 29),
-        ("L-F-U-R-D", 30),
-        ("L-F-U-R-N", 31),
-        ("L-U-D-G-C", 32),
-        ("L-U-D-G-N", 33),
-        ("L-U-D-R-C", 34),
-        ("L-U-D-R-N", 35),
-        ("L-U-F-G-C", 36),
-        ("L-U-F-G-N", 37),
-        ("L-U-U-G-C", 38),
-        ("L-U-U-G-D", 39),
-        ("L-U-U-G-N", 40),
-        ("L-U-U-R-D", 41),
-        ("L-U-U-R-N", 42),
-        ("M-D-D-G-C", 43),
-        ("M-D-D-G-D", 44),
-        ("M-D-D-G-N", 45),
-        ("M-D-D-R-C", 46),
-        ("M-D-D-R-D", 47),
-        ("M-D-D-R-N", 48),
-        ("M-D-F-G-C", 49),
-        ("M-D-F-G-N", 50),
-        ("M-D-F-R-C", 51),
-        ("M-D-F-R-N", 52),
-        ("M-D-U-G-C", 53),
-        ("M-D-U-G-N", 54),
-        ("M-D-U-R-C", 55),
-        ("M-D-U-R-N", 56),
-        ("M-F-D-G-C", 57),
-        ("M-F-D-G-D", 58),
-        ("M-F-D-G-N", 59),
-        ("M-F-D-R-D", 60),
-        ("M-F-D-R-N", 61),
-        ("M-F-U-G-C", 62),
-        ("M-F-U-G-N", 63),
-        ("M-F-U-R-C", 64),
-        ("M-F-U-R-N", 65),
-        ("M-U-D-G-C", 67),
-        ("M-U-D-G-D", 68),
-        ("M-U-D-G-N", 69),
-        ("M-U-D-R-C", 70),
-        ("M-U-D-R-D", 71),
-        ("M-U-D-R-N", 72),
-        ("M-U-F-G-C", 73),
-        ("M-U-F-G-D", 74),
-        ("M-U-F-G-N", 75),
-        ("M-U-F-R-D", 76),
-        ("M-U-U-G-C", 79),
-        ("M-U-U-G-D", 80),
-        ("M-U-U-G-N", 81),
-        ("M-U-U-R-C", 82),
-        ("M-U-U-R-D", 83),
-        ("M-U-U-R-N", 84),
-    ];
-    pairs
-        .into_iter()
-        .map(|(desc, code)| CandleMasterCode {
-            status_desc: desc.to_string(),
-            status_code: code.to_string(),
+    MasterCodeTable::load_or_embedded(Path::new(CANDLE_MASTER_CODES_FILE)).codes
+}
+
+/// Generator for `asset`, resumed from its snapshot in `dir` when that is at most
+/// `MAX_SNAPSHOT_GAP` candles old. Also returns the open time of its last candle (`None` for
+/// a new generator) and how many candles to fetch.
+fn resume_generator(
+    analysis: &V2AnalysisManager,
+    dir: &Path,
+    asset: &str,
+    now: u64,
+) -> (V2AnalysisGenerator, Option<u64>, usize) {
+    let resumed = analysis.load_snapshot(dir, asset).ok().and_then(|gen| {
+        let last_time = gen.last_candle_time()?;
+        let gap = now.saturating_sub(last_time) / 60;
+        (gap <= MAX_SNAPSHOT_GAP).then_some((gen, last_time, gap as usize + 2))
+    });
+    match resumed {
+        Some((gen, last_time, count)) => (gen, Some(last_time), count),
+        None => (
+            V2AnalysisGenerator::new(analysis.options.clone(), analysis.master_codes.clone())
+                .with_retention(analysis.retention.clone()),
+            None,
+            MAX_SNAPSHOT_GAP as usize,
+        ),
+    }
+}
+
+/// Chart markers for every analysis the generator still holds
+fn historical_markers(
+    asset: &str,
+    gen: &V2AnalysisGenerator,
+    signal_entries: &[TradeSignalEntry],
+) -> Vec<CompactAnalysis> {
+    let entry = signal_entries.iter().find(|e| e.asset_code == asset);
+    gen.analysis_array
+        .iter()
+        .map(|res| {
+            let mut decision = "idle".to_string();
+            if let Some(entry) = entry {
+                let call_codes: Vec<&str> =
+                    entry.call_signal.split(',').map(|s| s.trim()).collect();
+                let put_codes: Vec<&str> = entry.put_signal.split(',').map(|s| s.trim()).collect();
+                if call_codes.contains(&res.status_code.as_str()) {
+                    decision = "call".to_string();
+                } else if put_codes.contains(&res.status_code.as_str()) {
+                    decision = "put".to_string();
+                }
+            }
+            CompactAnalysis {
+                time: res.candletime,
+                action: decision,
+                status_code: res.status_code.clone(),
+                patterns: pattern_names(&res.patterns),
+            }
         })
         .collect()
 }
@@ -2235,19 +2753,19 @@ async fn connect_multi_asset(
     tx: broadcast::Sender<BroadcastMessage>,
     config: ClientCommand,
     mut cmd_rx: tokio::sync::mpsc::Receiver<String>,
-    _firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    _ledger: Arc<TradeLedger>,
 ) {
     // 1. Load tradeSignal.json
     let signal_entries: Vec<TradeSignalEntry> = match fs::read_to_string("tradeSignal.json") {
         Ok(content) => match serde_json::from_str(&content) {
             Ok(entries) => entries,
             Err(e) => {
-                println!("❌ Failed to parse tradeSignal.json: {}", e);
+                error!("❌ Failed to parse tradeSignal.json: {}", e);
                 return;
             }
         },
         Err(e) => {
-            println!("❌ Failed to read tradeSignal.json: {}", e);
+            error!("❌ Failed to read tradeSignal.json: {}", e);
             return;
         }
     };
@@ -2258,7 +2776,7 @@ async fn connect_multi_asset(
         .collect();
 
     let asset_symbols: Vec<String> = active_assets.iter().map(|a| a.asset_code.clone()).collect();
-    println!(
+    info!(
         "📊 Multi-Asset V2: {} active assets: {:?}",
         asset_symbols.len(),
         asset_symbols
@@ -2276,11 +2794,12 @@ async fn connect_multi_asset(
         config.app_id
     };
     let url = format!("wss://ws.derivws.com/websockets/v3?app_id={}", app_id);
-    println!("🌐 Multi-Asset V2: Connecting to Deriv API...");
+    info!("🌐 Multi-Asset V2: Connecting to Deriv API...");
 
     match tokio_tungstenite::connect_async(&url).await {
         Ok((ws_stream, _)) => {
-            println!("✅ Multi-Asset V2: Connected to Deriv");
+            info!("✅ Multi-Asset V2: Connected to Deriv");
+            let _connected = metrics().connection("multi");
             let (mut write, mut read) = ws_stream.split();
 
             // Authorize if token provided
@@ -2294,7 +2813,7 @@ async fn connect_multi_asset(
                 if let Ok(Some(Ok(TungsteniteMessage::Text(text)))) =
                     tokio::time::timeout(tokio::time::Duration::from_secs(3), read.next()).await
                 {
-                    println!(
+                    info!(
                         "🔑 Auth response: {}",
                         text.chars().take(200).collect::<String>()
                     );
@@ -2314,7 +2833,7 @@ async fn connect_multi_asset(
 
             // Step A: Blast out all ticks_history requests at once
             for asset in &asset_symbols {
-                println!("📥 Requesting history for {}...", asset);
+                info!("📥 Requesting history for {}...", asset);
                 let req = serde_json::json!({
                     "ticks_history": asset,
                     "adjust_start_time": 1,
@@ -2339,8 +2858,9 @@ async fn connect_multi_asset(
                     tokio::time::timeout(tokio::time::Duration::from_secs(5), read.next()).await
                 {
                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+                        metrics().message("multi", &json);
                         if let Some(error) = json.get("error") {
-                            println!("❌ AutoTrade history error: {}", error);
+                            error!("❌ AutoTrade history error: {}", error);
                         }
 
                         // Check if this is a candles response
@@ -2360,7 +2880,8 @@ async fn connect_multi_asset(
                             let mut gen = V2AnalysisGenerator::new(
                                 v2_options.clone(),
                                 master_codes_arc.clone(),
-                            );
+                            )
+                            .with_retention(V2RetentionPolicy::KeepLast(V2_HISTORY_RETENTION));
                             let mut count = 0;
                             let mut historical_results = Vec::new();
 
@@ -2421,17 +2942,18 @@ async fn connect_multi_asset(
                                     time,
                                     action: decision,
                                     status_code: final_state.status_code,
+                                    patterns: pattern_names(&final_state.patterns),
                                 });
                                 count += 1;
                             }
 
                             if let Some(ref last) = gen.state.last_analysis {
-                                println!(
+                                info!(
                                     "  ✅ {} loaded {} candles | StatusCode={} StatusDesc={}",
                                     resp_asset, count, last.status_code, last.status_desc
                                 );
                             } else {
-                                println!(
+                                info!(
                                     "  ✅ {} loaded {} candles (no analysis yet)",
                                     resp_asset, count
                                 );
@@ -2452,7 +2974,7 @@ async fn connect_multi_asset(
 
                             generators.insert(resp_asset.clone(), gen);
                             pending_assets.remove(&resp_asset);
-                            println!(
+                            info!(
                                 "  📊 {} remaining: {:?}",
                                 pending_assets.len(),
                                 pending_assets
@@ -2460,7 +2982,7 @@ async fn connect_multi_asset(
                         }
                     }
                 } else {
-                    println!(
+                    info!(
                         "⏱️ Timeout waiting for history response, {} pending",
                         pending_assets.len()
                     );
@@ -2469,15 +2991,15 @@ async fn connect_multi_asset(
             }
 
             if !pending_assets.is_empty() {
-                println!("⚠️ Failed to load history for: {:?}", pending_assets);
+                warn!("⚠️ Failed to load history for: {:?}", pending_assets);
             }
-            println!(
+            info!(
                 "⚡ Parallel fetch completed in {:.1}s — {} generators ready",
                 fetch_start.elapsed().as_secs_f64(),
                 generators.len()
             );
 
-            println!(
+            info!(
                 "📊 All {} generators ready. Subscribing to live candles...",
                 generators.len()
             );
@@ -2509,8 +3031,8 @@ async fn connect_multi_asset(
                 tokio::select! {
                     cmd = cmd_rx.recv() => {
                         if let Some(cmd) = cmd {
-                            if cmd == "FORGET" || cmd == "STOP_STREAMS" {
-                                println!("🛑 Multi-Asset V2: Stopping all streams...");
+                            if cmd == "FORGET" || cmd == "STOP_STREAMS" || cmd == shutdown::DRAIN {
+                                info!("🛑 Multi-Asset V2: Stopping all streams...");
                                 for id in &sub_ids {
                                     let forget_msg = serde_json::json!({"forget": id});
                                     let _ = write.send(TungsteniteMessage::Text(forget_msg.to_string())).await;
@@ -2523,6 +3045,7 @@ async fn connect_multi_asset(
                     msg = read.next() => {
                         if let Some(Ok(TungsteniteMessage::Text(raw_text))) = msg {
                             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw_text) {
+                                metrics().message("multi", &json);
 
                                 // Track subscription IDs
                                 if let Some(sub) = json.get("subscription") {
@@ -2577,7 +3100,7 @@ async fn connect_multi_asset(
                                                         low: *pl, close: *pc,
                                                     };
                                                     let result = gen.append_candle(completed);
-                                                    println!(
+                                                    info!(
                                                         "  📊 {} candle closed | StatusCode={} Desc={}",
                                                         symbol, result.status_code, result.status_desc
                                                     );
@@ -2612,14 +3135,22 @@ async fn connect_multi_asset(
                                                         let put_codes: Vec<&str> = entry.put_signal.split(',').map(|s| s.trim()).collect();
 
                                                         let (decision, reason) = if call_codes.contains(&code_str.as_str()) {
-                                                            ("call".to_string(), format!("StatusCode {} matched CallSignal", code_str))
+                                                            if pattern_filter_passes(&analysis.patterns, &entry.pattern_filter, true) {
+                                                                ("call".to_string(), format!("StatusCode {} matched CallSignal", code_str))
+                                                            } else {
+                                                                ("idle".to_string(), format!("StatusCode {} matched CallSignal, no {} pattern", code_str, entry.pattern_filter))
+                                                            }
                                                         } else if put_codes.contains(&code_str.as_str()) {
-                                                            ("put".to_string(), format!("StatusCode {} matched PutSignal", code_str))
+                                                            if pattern_filter_passes(&analysis.patterns, &entry.pattern_filter, false) {
+                                                                ("put".to_string(), format!("StatusCode {} matched PutSignal", code_str))
+                                                            } else {
+                                                                ("idle".to_string(), format!("StatusCode {} matched PutSignal, no {} pattern", code_str, entry.pattern_filter))
+                                                            }
                                                         } else {
                                                             ("idle".to_string(), format!("StatusCode {} — no match", code_str))
                                                         };
 
-                                                        println!("  📊 {} | Code={} | Desc={} | Decision={}",
+                                                        info!("  📊 {} | Code={} | Desc={} | Decision={}",
                                                             asset_code, code_str, analysis.status_desc, decision);
 
                                                         signal_results.push(AssetSignalResult {
@@ -2638,7 +3169,7 @@ async fn connect_multi_asset(
                                             }
 
                                             if !signal_results.is_empty() {
-                                                println!("📡 Broadcasting multi_analysis: {} assets at minute {}",
+                                                debug!("📡 Broadcasting multi_analysis: {} assets at minute {}",
                                                     signal_results.len(), current_minute);
 
                                                 let multi_msg = MultiAnalysisMessage {
@@ -2654,7 +3185,7 @@ async fn connect_multi_asset(
 
                                 // Handle errors
                                 if let Some(error) = json.get("error") {
-                                    println!("❌ Multi-Asset API Error: {}",
+                                    error!("❌ Multi-Asset API Error: {}",
                                         error.get("message").unwrap_or(&serde_json::json!("Unknown")));
                                 }
                             }
@@ -2665,9 +3196,12 @@ async fn connect_multi_asset(
                 }
             }
 
-            println!("🔌 Multi-Asset V2: Connection closed.");
+            info!("🔌 Multi-Asset V2: Connection closed.");
+        }
+        Err(e) => {
+            metrics().connect_failed("multi");
+            error!("❌ Multi-Asset V2: Connection Failed: {}", e);
         }
-        Err(e) => println!("❌ Multi-Asset V2: Connection Failed: {}", e),
     }
 }
 
@@ -2683,15 +3217,16 @@ async fn auto_multi_trade(
     tx: broadcast::Sender<BroadcastMessage>,
     config: ClientCommand,
     mut cmd_rx: tokio::sync::mpsc::Receiver<String>,
-    firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: Arc<TradeLedger>,
+    analysis: Arc<V2AnalysisManager>,
 ) {
-    println!("🤖 ====== AUTO MULTI-TRADE STARTED ======");
-    println!("   Assets: {:?}", config.assets);
-    println!(
+    info!("🤖 ====== AUTO MULTI-TRADE STARTED ======");
+    info!("   Assets: {:?}", config.assets);
+    info!(
         "   Stake: {}, Mode: {}, Duration: {}{}",
         config.initial_stake, config.money_mode, config.duration, config.duration_unit
     );
-    println!(
+    info!(
         "   Target Profit: {}, Target Win: {}",
         config.target_profit, config.target_win
     );
@@ -2701,12 +3236,12 @@ async fn auto_multi_trade(
         Ok(content) => match serde_json::from_str(&content) {
             Ok(entries) => entries,
             Err(e) => {
-                println!("❌ AutoTrade: Failed to parse tradeSignal.json: {}", e);
+                error!("❌ AutoTrade: Failed to parse tradeSignal.json: {}", e);
                 return;
             }
         },
         Err(e) => {
-            println!("❌ AutoTrade: Failed to read tradeSignal.json: {}", e);
+            error!("❌ AutoTrade: Failed to read tradeSignal.json: {}", e);
             return;
         }
     };
@@ -2724,21 +3259,16 @@ async fn auto_multi_trade(
     };
 
     if asset_symbols.is_empty() {
-        println!("❌ AutoTrade: No assets selected!");
+        error!("❌ AutoTrade: No assets selected!");
         return;
     }
 
-    println!(
+    info!(
         "📊 AutoTrade: {} assets to analyze: {:?}",
         asset_symbols.len(),
         asset_symbols
     );
 
-    // Build CandleMasterCode list for StatusCode resolution
-    let master_codes = build_candle_master_codes();
-    let master_codes_arc = std::sync::Arc::new(master_codes);
-    let v2_options = V2AnalysisOptions::default();
-
     // 2. Connect to Deriv API
     let app_id = if config.app_id.is_empty() {
         "66726".to_string()
@@ -2746,11 +3276,12 @@ async fn auto_multi_trade(
         config.app_id.clone()
     };
     let url = format!("wss://ws.derivws.com/websockets/v3?app_id={}", app_id);
-    println!("🌐 AutoTrade: Connecting to Deriv API...");
+    info!("🌐 AutoTrade: Connecting to Deriv API...");
 
     match tokio_tungstenite::connect_async(&url).await {
         Ok((ws_stream, _)) => {
-            println!("✅ AutoTrade: Connected to Deriv");
+            info!("✅ AutoTrade: Connected to Deriv");
+            let _connected = metrics().connection("auto");
             let (mut write, mut read) = ws_stream.split();
 
             // Authorize if token provided
@@ -2764,25 +3295,39 @@ async fn auto_multi_trade(
                 if let Ok(Some(Ok(TungsteniteMessage::Text(text)))) =
                     tokio::time::timeout(tokio::time::Duration::from_secs(3), read.next()).await
                 {
-                    println!(
+                    info!(
                         "🔑 Auth response: {}",
                         text.chars().take(200).collect::<String>()
                     );
                 }
             }
 
-            // 3. Fetch historical candles and initialize V2 generators per asset (PARALLEL)
-            let mut generators: std::collections::HashMap<String, V2AnalysisGenerator> =
-                std::collections::HashMap::new();
+            // 3. Resume V2 generators from their snapshots and fetch the candles they miss
+            // (full history for assets without a usable snapshot)
+            analysis.generators.clear();
+            let generators = analysis.generators.clone();
+            let snapshot_dir = Path::new(GENERATOR_SNAPSHOT_DIR);
             let mut current_candle: std::collections::HashMap<String, (u64, f64, f64, f64, f64)> =
                 std::collections::HashMap::new();
 
             for asset in &asset_symbols {
-                println!("📥 AutoTrade: Fetching history for {}...", asset);
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let (mut gen, resumed_from, fetch_count) =
+                    resume_generator(&analysis, snapshot_dir, asset, now);
+                match resumed_from {
+                    Some(last_time) => info!(
+                        "📥 AutoTrade: {} resumed from snapshot at {}, fetching {} candles...",
+                        asset, last_time, fetch_count
+                    ),
+                    None => info!("📥 AutoTrade: Fetching history for {}...", asset),
+                }
                 let req = serde_json::json!({
                     "ticks_history": asset,
                     "adjust_start_time": 1,
-                    "count": 1000,
+                    "count": fetch_count,
                     "end": "latest",
                     "style": "candles",
                     "granularity": 60
@@ -2798,8 +3343,9 @@ async fn auto_multi_trade(
                         tokio::time::timeout(tokio::time::Duration::from_secs(2), read.next()).await
                     {
                         if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+                            metrics().message("auto", &json);
                             if let Some(error) = json.get("error") {
-                                println!("❌ AutoTrade history error: {}", error);
+                                error!("❌ AutoTrade history error: {}", error);
                             }
                             if let Some(candles_arr) =
                                 json.get("candles").and_then(|c| c.as_array())
@@ -2814,15 +3360,20 @@ async fn auto_multi_trade(
                                     continue;
                                 }
 
-                                let mut gen = V2AnalysisGenerator::new(
-                                    v2_options.clone(),
-                                    master_codes_arc.clone(),
-                                );
+                                // Skip what the snapshot already has and the candle still forming
+                                let now = SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_secs();
                                 let mut count = 0;
-                                let mut historical_results = Vec::new();
 
                                 for c in candles_arr {
                                     let time = c.get("epoch").and_then(|v| v.as_u64()).unwrap_or(0);
+                                    if resumed_from.is_some_and(|last| time <= last)
+                                        || time + 60 > now
+                                    {
+                                        continue;
+                                    }
                                     let open = c
                                         .get("open")
                                         .and_then(|v| v.as_f64())
@@ -2852,69 +3403,45 @@ async fn auto_multi_trade(
                                         })
                                         .unwrap_or(0.0);
 
-                                    let final_state = gen.append_candle(V2Candle {
+                                    gen.append_candle(V2Candle {
                                         time,
                                         open,
                                         high,
                                         low,
                                         close,
                                     });
-
-                                    // Collect history for markers (keep last 100)
-                                    let mut decision = "idle".to_string();
-                                    if let Some(entry) =
-                                        signal_entries.iter().find(|e| e.asset_code == *asset)
-                                    {
-                                        let call_codes: Vec<&str> = entry
-                                            .call_signal
-                                            .split(',')
-                                            .map(|s| s.trim())
-                                            .collect();
-                                        let put_codes: Vec<&str> =
-                                            entry.put_signal.split(',').map(|s| s.trim()).collect();
-                                        if call_codes.contains(&final_state.status_code.as_str()) {
-                                            decision = "call".to_string();
-                                        } else if put_codes
-                                            .contains(&final_state.status_code.as_str())
-                                        {
-                                            decision = "put".to_string();
-                                        }
-                                    }
-
-                                    historical_results.push(CompactAnalysis {
-                                        time,
-                                        action: decision,
-                                        status_code: final_state.status_code,
-                                    });
                                     count += 1;
                                 }
 
+                                let source = if resumed_from.is_some() {
+                                    "snapshot +"
+                                } else {
+                                    "loaded"
+                                };
                                 if let Some(ref last) = gen.state.last_analysis {
-                                    println!(
-                                        "  ✅ {} loaded {} candles | StatusCode={} StatusDesc={}",
-                                        asset, count, last.status_code, last.status_desc
+                                    info!(
+                                        "  ✅ {} {} {} candles | StatusCode={} StatusDesc={}",
+                                        asset, source, count, last.status_code, last.status_desc
                                     );
                                 } else {
-                                    println!(
-                                        "  ✅ {} loaded {} candles (no analysis yet)",
-                                        asset, count
+                                    info!(
+                                        "  ✅ {} {} {} candles (no analysis yet)",
+                                        asset, source, count
                                     );
                                 }
 
                                 // Send history to frontend
-                                if historical_results.len() > 1000 {
-                                    let skip_amt = historical_results.len() - 1000;
-                                    historical_results =
-                                        historical_results.into_iter().skip(skip_amt).collect();
-                                }
                                 let hist_msg = HistoricalAnalysis {
                                     msg_type: "historical_analysis".to_string(),
                                     symbol: asset.clone(),
-                                    results: historical_results,
+                                    results: historical_markers(asset, &gen, &signal_entries),
                                 };
                                 let _ = tx.send(BroadcastMessage::HistoricalAnalysis(hist_msg));
 
                                 generators.insert(asset.clone(), gen);
+                                if let Err(e) = analysis.save_snapshot(snapshot_dir, asset) {
+                                    warn!("⚠️ Saving {} snapshot failed: {}", asset, e);
+                                }
                                 break; // Success, break while loop for this asset
                             }
                         }
@@ -2925,7 +3452,7 @@ async fn auto_multi_trade(
                 tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
             }
 
-            println!(
+            info!(
                 "📊 AutoTrade: All {} generators ready. Subscribing to live candles...",
                 generators.len()
             );
@@ -2962,6 +3489,7 @@ async fn auto_multi_trade(
             let mut last_check_minute: Option<u64> = None;
             let mut balance: f64 = 1000.0;
             let mut grand_profit: f64 = 0.0;
+            metrics().bot_profit("auto", grand_profit);
             let mut win_count: u32 = 0;
             let mut trade_count: u32 = 0;
             let mut lot_active = true;
@@ -2970,6 +3498,10 @@ async fn auto_multi_trade(
                 std::collections::HashMap::new();
             let mut pending_contracts: std::collections::HashMap<String, String> =
                 std::collections::HashMap::new(); // contract_id -> asset
+            let mut buys_in_flight = 0usize;
+            let mut draining = false;
+            let mut entry_status_codes: std::collections::HashMap<String, String> =
+                std::collections::HashMap::new(); // contract_id -> status code at entry
 
             // === NEW DAY TRADE LOGGING STATE ===
             let mut day_trade_entries: Vec<DayTradeEntry> = Vec::new();
@@ -3006,10 +3538,10 @@ async fn auto_multi_trade(
             // Lot logging
             let folder_name = get_daily_folder_name();
             let folder_path = ensure_daily_folder(&folder_name);
-            let lot_no = get_next_lot_no(&folder_path);
+            let lot_no = file_store::allocate_lot_no(&folder_path);
             let mut trades_for_lot: Vec<TradeObject> = Vec::new();
 
-            println!("🤖 AutoTrade: Entering main trading loop (Lot #{})", lot_no);
+            info!("🤖 AutoTrade: Entering main trading loop (Lot #{})", lot_no);
             let mut initial_check_done = false; // Force first signal check immediately
 
             // 5. Main event loop — browser independent!
@@ -3018,7 +3550,7 @@ async fn auto_multi_trade(
                     cmd = cmd_rx.recv() => {
                         if let Some(cmd) = cmd {
                             if cmd == "STOP" {
-                                println!("🛑 AutoTrade: Stop command received");
+                                info!("🛑 AutoTrade: Stop command received");
                                 // Unsubscribe all
                                 for id in &sub_ids {
                                     let forget_msg = serde_json::json!({"forget": id});
@@ -3033,6 +3565,22 @@ async fn auto_multi_trade(
                                     message: "Auto-trade stopped by user".to_string(),
                                 }));
                                 break;
+                            } else if cmd == shutdown::DRAIN {
+                                // Contract streams stay subscribed until the results arrive
+                                info!("📤 AutoTrade: Shutdown, forgetting streams ({} contracts open)", pending_contracts.len() + buys_in_flight);
+                                let _ = write.send(forget_market_streams()).await;
+                                draining = true;
+                                let _ = tx.send(BroadcastMessage::AutoTradeStatus(AutoTradeStatusMessage {
+                                    msg_type: "auto_trade_status".to_string(),
+                                    active: false,
+                                    entries: vec![],
+                                    grand_profit,
+                                    trade_count,
+                                    message: "Auto-trade stopped: server shutting down".to_string(),
+                                }));
+                                if pending_contracts.is_empty() && buys_in_flight == 0 {
+                                    break;
+                                }
                             } else if cmd == "SYNC" {
                                 let _ = tx.send(BroadcastMessage::AutoTradeStatus(AutoTradeStatusMessage {
                                     msg_type: "auto_trade_status".to_string(),
@@ -3058,40 +3606,22 @@ async fn auto_multi_trade(
                                 }));
 
                                 // Re-broadcast historical_analysis for ALL assets so browser gets markers
-                                for (asset_sym, gen) in &generators {
-                                    // Rebuild CompactAnalysis from generator's analysis_array
-                                    let history_results: Vec<CompactAnalysis> = gen.analysis_array.iter().map(|res| {
-                                        let mut decision = "idle".to_string();
-                                        if let Some(entry) = signal_entries.iter().find(|e| e.asset_code == *asset_sym) {
-                                            let call_codes: Vec<&str> = entry.call_signal.split(',').map(|s| s.trim()).collect();
-                                            let put_codes: Vec<&str> = entry.put_signal.split(',').map(|s| s.trim()).collect();
-                                            if call_codes.contains(&res.status_code.as_str()) {
-                                                decision = "call".to_string();
-                                            } else if put_codes.contains(&res.status_code.as_str()) {
-                                                decision = "put".to_string();
-                                            }
-                                        }
-                                        CompactAnalysis {
-                                            time: res.candletime,
-                                            action: decision,
-                                            status_code: res.status_code.clone(),
-                                        }
-                                    }).collect();
-
+                                for entry in generators.iter() {
+                                    let history_results = historical_markers(entry.key(), entry.value(), &signal_entries);
                                     if !history_results.is_empty() {
                                         let hist_msg = HistoricalAnalysis {
                                             msg_type: "historical_analysis".to_string(),
-                                            symbol: asset_sym.clone(),
+                                            symbol: entry.key().clone(),
                                             results: history_results,
                                         };
                                         let _ = tx.send(BroadcastMessage::HistoricalAnalysis(hist_msg));
                                     }
                                 }
-                                println!("📡 SYNC: Re-broadcast historical_analysis for {} assets", generators.len());
+                                info!("📡 SYNC: Re-broadcast historical_analysis for {} assets", generators.len());
                             } else if cmd.starts_with("SELL:") {
                                 // Handle SELL command forwarded from handle_socket
                                 let contract_id = cmd.trim_start_matches("SELL:").to_string();
-                                println!("🔻 AutoTrade: Selling contract {}", contract_id);
+                                info!(contract_id = %contract_id, "🔻 AutoTrade: Selling contract");
                                 let sell_msg = serde_json::json!({
                                     "sell": contract_id,
                                     "price": 0
@@ -3107,7 +3637,7 @@ async fn auto_multi_trade(
                                         if let Some(mm) = json_cmd.get("money_mode").and_then(|v| v.as_str()) { current_money_mode = mm.to_string(); }
                                         if let Some(du) = json_cmd.get("duration_unit").and_then(|v| v.as_str()) { current_duration_unit = du.to_string(); }
 
-                                        println!("🔄 AutoTrade: Settings updated -> Target: ${}, Win: {}, Stake: ${}, Mode: {}, Dur: {}{}",
+                                        info!("🔄 AutoTrade: Settings updated -> Target: ${}, Win: {}, Stake: ${}, Mode: {}, Dur: {}{}",
                                             target_profit, target_win, current_initial_stake, current_money_mode, current_duration, current_duration_unit);
 
                                         // Broadcast updated lot status to browser
@@ -3124,10 +3654,10 @@ async fn auto_multi_trade(
                                         if let Some(tm) = json_cmd.get("trade_mode").and_then(|v| v.as_str()) {
                                             if tm == "idle" {
                                                 lot_active = false;
-                                                println!("⏸️ AutoTrade: Set to IDLE (Paused).");
+                                                info!("⏸️ AutoTrade: Set to IDLE (Paused).");
                                             } else if tm == "auto" {
                                                 lot_active = true;
-                                                println!("▶️ AutoTrade: Set to AUTO (Resumed).");
+                                                info!("▶️ AutoTrade: Set to AUTO (Resumed).");
                                             }
                                             // Broadcast mode change to browser
                                             let _ = tx.send(BroadcastMessage::LotStatus(LotStatus {
@@ -3149,6 +3679,7 @@ async fn auto_multi_trade(
                     msg = read.next() => {
                         if let Some(Ok(TungsteniteMessage::Text(raw_text))) = msg {
                             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw_text) {
+                                metrics().message("auto", &json);
 
                                 // Track subscription IDs
                                 if let Some(sub) = json.get("subscription") {
@@ -3168,7 +3699,7 @@ async fn auto_multi_trade(
                                             balance,
                                         };
                                         let _ = tx.send(BroadcastMessage::Balance(balance_msg));
-                                        println!("💰 AutoTrade: Balance = {}", balance);
+                                        info!("💰 AutoTrade: Balance = {}", balance);
                                     }
                                 }
 
@@ -3196,17 +3727,20 @@ async fn auto_multi_trade(
 
                                         if open_time != prev_open_time && prev_open_time > 0 {
                                             if let Some((pt, po, ph, pl, pc)) = current_candle.get(&symbol) {
-                                                if let Some(gen) = generators.get_mut(&symbol) {
+                                                if let Some(mut gen) = generators.get_mut(&symbol) {
                                                     let completed = V2Candle {
                                                         time: *pt, open: *po, high: *ph,
                                                         low: *pl, close: *pc,
                                                     };
                                                     let result = gen.append_candle(completed);
-                                                    println!(
+                                                    info!(
                                                         "  📊 AutoTrade {} candle closed | StatusCode={} Desc={}",
                                                         symbol, result.status_code, result.status_desc
                                                     );
                                                 }
+                                                if let Err(e) = analysis.save_snapshot(snapshot_dir, &symbol) {
+                                                    warn!("⚠️ Saving {} snapshot failed: {}", symbol, e);
+                                                }
                                             }
                                         }
 
@@ -3245,18 +3779,26 @@ async fn auto_multi_trade(
                                                         let put_codes: Vec<&str> = entry.put_signal.split(',').map(|s| s.trim()).collect();
 
                                                         let (decision, _reason) = if call_codes.contains(&code_str.as_str()) {
-                                                            ("CALL".to_string(), format!("StatusCode {} matched CallSignal", code_str))
+                                                            if pattern_filter_passes(&analysis.patterns, &entry.pattern_filter, true) {
+                                                                ("CALL".to_string(), format!("StatusCode {} matched CallSignal", code_str))
+                                                            } else {
+                                                                ("IDLE".to_string(), format!("StatusCode {} matched CallSignal, no {} pattern", code_str, entry.pattern_filter))
+                                                            }
                                                         } else if put_codes.contains(&code_str.as_str()) {
-                                                            ("PUT".to_string(), format!("StatusCode {} matched PutSignal", code_str))
+                                                            if pattern_filter_passes(&analysis.patterns, &entry.pattern_filter, false) {
+                                                                ("PUT".to_string(), format!("StatusCode {} matched PutSignal", code_str))
+                                                            } else {
+                                                                ("IDLE".to_string(), format!("StatusCode {} matched PutSignal, no {} pattern", code_str, entry.pattern_filter))
+                                                            }
                                                         } else {
                                                             ("IDLE".to_string(), format!("StatusCode {} — no match", code_str))
                                                         };
 
-                                                        println!("  📊 AutoTrade {} | Code={} | Desc={} | Decision={}",
+                                                        info!(asset = %asset_code, "  📊 AutoTrade {} | Code={} | Desc={} | Decision={}",
                                                             asset_code, code_str, analysis.status_desc, decision);
 
                                                         // Execute trade if CALL or PUT
-                                                        if decision == "CALL" || decision == "PUT" {
+                                                        if (decision == "CALL" || decision == "PUT") && !shutdown::requested() {
                                                             let stake_idx = stake_index_per_asset.get(asset_code).copied().unwrap_or(0);
                                                             let stake = if current_money_mode == "martingale" {
                                                                 martingale_stakes[stake_idx.min(martingale_stakes.len() - 1)]
@@ -3279,8 +3821,10 @@ async fn auto_multi_trade(
                                                                     }
                                                                 });
 
-                                                                println!("📈 AutoTrade: Placing {} on {} with stake ${}", decision, asset_code, stake);
+                                                                info!(asset = %asset_code, status_code = %code_str, "📈 AutoTrade: Placing {} on {} with stake ${}", decision, asset_code, stake);
                                                                 let _ = write.send(TungsteniteMessage::Text(buy_msg.to_string())).await;
+                                                                metrics().buy_sent("auto", asset_code);
+                                                                buys_in_flight += 1;
 
                                                                 trade_entries.push(AutoTradeEntry {
                                                                     asset: asset_code.clone(),
@@ -3293,7 +3837,7 @@ async fn auto_multi_trade(
                                                                 // Small delay between multiple buy orders
                                                                 tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
                                                             } else {
-                                                                println!("⚠️ AutoTrade: Insufficient balance for {} (need {}, have {})", asset_code, stake, balance);
+                                                                warn!("⚠️ AutoTrade: Insufficient balance for {} (need {}, have {})", asset_code, stake, balance);
                                                             }
                                                         }
                                                     }
@@ -3302,7 +3846,7 @@ async fn auto_multi_trade(
 
                                             // Broadcast trade entries to browser (if connected)
                                             if !trade_entries.is_empty() {
-                                                println!("🔥 AutoTrade: {} trades placed this minute", trade_entries.len());
+                                                info!("🔥 AutoTrade: {} trades placed this minute", trade_entries.len());
                                                 let _ = tx.send(BroadcastMessage::AutoTradeStatus(AutoTradeStatusMessage {
                                                     msg_type: "auto_trade_status".to_string(),
                                                     active: true,
@@ -3359,6 +3903,7 @@ async fn auto_multi_trade(
 
                                 // Handle buy response
                                 if let Some(buy) = json.get("buy") {
+                                    buys_in_flight = buys_in_flight.saturating_sub(1);
                                     let contract_id = buy.get("contract_id")
                                         .and_then(|c| c.as_str().map(|s| s.to_string()))
                                         .or_else(|| buy.get("contract_id").and_then(|c| c.as_u64().map(|n| n.to_string())));
@@ -3373,10 +3918,37 @@ async fn auto_multi_trade(
                                             .to_string();
 
                                         pending_contracts.insert(cid.clone(), asset_for_contract.clone());
+                                        // Status code that triggered the entry, stored with the trade on close
+                                        let entry_code = generators
+                                            .get(&asset_for_contract)
+                                            .and_then(|g| g.state.last_analysis.as_ref().map(|a| a.status_code.clone()));
+                                        if let Some(code) = &entry_code {
+                                            entry_status_codes.insert(cid.clone(), code.clone());
+                                        }
                                         trade_count += 1;
 
                                         let stake = buy.get("buy_price").and_then(|p| p.as_f64()).unwrap_or(0.0);
-                                        println!("✅ AutoTrade: Contract {} opened for {} (stake: ${})", cid, asset_for_contract, stake);
+                                        ledger.contract_opened(&OpenContract {
+                                            contract_id: cid.clone(),
+                                            bot: "auto".to_string(),
+                                            symbol: asset_for_contract.clone(),
+                                            contract_type: json.get("echo_req")
+                                                .and_then(|e| e.get("parameters"))
+                                                .and_then(|p| p.get("contract_type"))
+                                                .and_then(|s| s.as_str())
+                                                .unwrap_or("")
+                                                .to_string(),
+                                            stake,
+                                            lot_no,
+                                            opened_at: Local::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+                                        }).await;
+                                        info!(
+                                            contract_id = %cid,
+                                            asset = %asset_for_contract,
+                                            status_code = entry_code.as_deref().unwrap_or(""),
+                                            "✅ AutoTrade: Contract opened (stake: ${})",
+                                            stake
+                                        );
 
                                         // Broadcast trade_opened
                                         let trade_opened_time = Local::now().format("%H:%M:%S").to_string();
@@ -3448,6 +4020,7 @@ async fn auto_multi_trade(
                                             date_start,
                                         };
                                         let _ = tx.send(BroadcastMessage::TradeUpdate(trade_update));
+                                        debug!(contract_id = %contract_id, current_spot, profit, "📊 Contract update");
                                     }
 
                                     if status == "sold" || status == "won" || status == "lost" {
@@ -3462,6 +4035,9 @@ async fn auto_multi_trade(
 
                                         // Get asset for this contract
                                         let asset_for_contract = pending_contracts.remove(&contract_id).unwrap_or_default();
+                                        ledger.contract_settled(&contract_id).await;
+                                        metrics().settled("auto", &asset_for_contract, is_win);
+                                        metrics().bot_profit("auto", grand_profit);
 
                                         // Update martingale state per asset
                                         if is_win {
@@ -3472,7 +4048,7 @@ async fn auto_multi_trade(
                                         }
 
                                         let icon = if is_win { "🎉" } else { "❌" };
-                                        println!("{} AutoTrade: {} {} | Profit: ${:.2} | Balance: ${:.2} | Grand: ${:.2} | Wins: {}",
+                                        info!(contract_id = %contract_id, asset = %asset_for_contract, "{} AutoTrade: {} {} | Profit: ${:.2} | Balance: ${:.2} | Grand: ${:.2} | Wins: {}",
                                             icon, asset_for_contract, if is_win { "WIN" } else { "LOSS" }, profit, balance, grand_profit, win_count);
 
                                         // Broadcast result
@@ -3507,9 +4083,9 @@ async fn auto_multi_trade(
                                             lot_no,
                                             trade_object_list: trades_for_lot.clone(),
                                         };
-                                        save_lot_log(&ensure_daily_folder(&get_daily_folder_name()), &lot_log);
+                                        ledger.record_lot(&lot_log).await;
 
-                                        // Save to Firestore
+                                        // Save to the trade store / Firestore
                                         let date_start_val = proposal.get("date_start").and_then(|d| d.as_u64()).unwrap_or(0);
                                         let date_expiry_val = proposal.get("date_expiry").and_then(|d| d.as_u64()).unwrap_or(0);
                                         let payout_val = proposal.get("payout").and_then(|p| p.as_f64()).unwrap_or(0.0);
@@ -3534,6 +4110,8 @@ async fn auto_multi_trade(
                                                 min_profit: profit,
                                                 max_profit: profit,
                                                 status: if is_win { "win".to_string() } else { "loss".to_string() },
+                                                status_code: entry_status_codes.remove(&contract_id).unwrap_or_default(),
+                                                martingale_step: martingale_step(&current_money_mode, &martingale_stakes, stake),
                                                 entry_spot: entry_spot_val,
                                                 exit_spot: _exit_spot_val,
                                                 lot_no,
@@ -3586,14 +4164,12 @@ async fn auto_multi_trade(
                                                 day_trade_list: day_trade_entries.clone(),
                                             }
                                         };
-                                        save_day_trade_log(&day_trade_wrapper);
+                                        if ledger.sinks.json_logs {
+                                            save_day_trade_log(&day_trade_wrapper);
+                                        }
                                         // ========================================
 
-                                        let fs = firestore.lock().await;
-                                        match fs.save_trade(&trade_record).await {
-                                            Ok(doc_id) => println!("🔥 AutoTrade: Trade saved to Firestore: {}", doc_id),
-                                            Err(e) => println!("⚠️ AutoTrade: Firestore save error: {}", e),
-                                        }
+                                        ledger.record_trade(&trade_record).await;
 
                                         // Broadcast lot status
                                         let _ = tx.send(BroadcastMessage::LotStatus(LotStatus {
@@ -3611,18 +4187,18 @@ async fn auto_multi_trade(
                                         if current_money_mode == "fix" {
                                             if grand_profit >= target_profit {
                                                 stop_trading = true;
-                                                println!("🏆 AutoTrade: TARGET PROFIT REACHED! ${:.2} >= ${:.2}", grand_profit, target_profit);
+                                                info!("🏆 AutoTrade: TARGET PROFIT REACHED! ${:.2} >= ${:.2}", grand_profit, target_profit);
                                             }
                                         } else if current_money_mode == "martingale" {
                                             if win_count >= target_win {
                                                 stop_trading = true;
-                                                println!("🏆 AutoTrade: TARGET WIN COUNT REACHED! {} >= {}", win_count, target_win);
+                                                info!("🏆 AutoTrade: TARGET WIN COUNT REACHED! {} >= {}", win_count, target_win);
                                             }
                                         }
 
                                         if stop_trading {
                                             let _lot_active = false;
-                                            println!("🛑 AutoTrade: STOPPING — conditions met!");
+                                            info!("🛑 AutoTrade: STOPPING — conditions met!");
 
                                             // Unsubscribe
                                             for id in &sub_ids {
@@ -3645,25 +4221,42 @@ async fn auto_multi_trade(
 
                                 // Handle errors
                                 if let Some(error) = json.get("error") {
-                                    println!("❌ AutoTrade API Error: {}",
+                                    error!("❌ AutoTrade API Error: {}",
                                         error.get("message").unwrap_or(&serde_json::json!("Unknown")));
+                                    if json.get("msg_type").and_then(|m| m.as_str()) == Some("buy") {
+                                        let asset = json.get("echo_req")
+                                            .and_then(|e| e.get("parameters"))
+                                            .and_then(|p| p.get("symbol"))
+                                            .and_then(|s| s.as_str())
+                                            .unwrap_or("");
+                                        metrics().buy_failed("auto", asset);
+                                        buys_in_flight = buys_in_flight.saturating_sub(1);
+                                    }
+                                }
+
+                                if draining && pending_contracts.is_empty() && buys_in_flight == 0 {
+                                    info!("✅ AutoTrade: Shutdown, open contracts settled");
+                                    break;
                                 }
                             }
                         } else {
                             // WebSocket disconnected from Deriv — try reconnect
-                            println!("⚠️ AutoTrade: Deriv WebSocket disconnected");
+                            warn!("⚠️ AutoTrade: Deriv WebSocket disconnected");
                             break;
                         }
                     }
                 }
             }
 
-            println!(
+            info!(
                 "🤖 AutoTrade: Session ended. Grand P/L: ${:.2}, Trades: {}, Wins: {}",
                 grand_profit, trade_count, win_count
             );
         }
-        Err(e) => println!("❌ AutoTrade: Connection Failed: {}", e),
+        Err(e) => {
+            metrics().connect_failed("auto");
+            error!("❌ AutoTrade: Connection Failed: {}", e);
+        }
     }
 }
 
@@ -3699,31 +4292,126 @@ pub struct SaveTickHistoryPayload {
     pub data: String,
 }
 
+/// Largest accepted `data` string and record count for one upload
+const MAX_TICK_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+const MAX_TICK_UPLOAD_RECORDS: usize = 500_000;
+
+/// Folder and file names are a single path component of `[A-Za-z0-9_-]`
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 128
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Check that `data` is a JSON array of candles (`open/high/low/close` + `epoch`) or ticks
+/// (`quote` + `epoch`). Returns the record count.
+fn validate_tick_history(data: &str) -> Result<usize, String> {
+    let records: Vec<serde_json::Value> =
+        serde_json::from_str(data).map_err(|e| format!("data is not a JSON array: {}", e))?;
+    if records.is_empty() {
+        return Err("data is empty".to_string());
+    }
+    if records.len() > MAX_TICK_UPLOAD_RECORDS {
+        return Err(format!(
+            "data has {} records, limit is {}",
+            records.len(),
+            MAX_TICK_UPLOAD_RECORDS
+        ));
+    }
+
+    let mut last_epoch = 0;
+    for (i, record) in records.iter().enumerate() {
+        let epoch = if record.get("quote").is_some() {
+            let quote = record
+                .get("quote")
+                .and_then(|q| q.as_f64().or_else(|| q.as_str()?.parse().ok()));
+            let epoch = record.get("epoch").and_then(|e| e.as_u64());
+            match (quote, epoch) {
+                (Some(q), Some(epoch)) if q.is_finite() => epoch,
+                _ => return Err(format!("record {}: invalid tick", i)),
+            }
+        } else {
+            match parse_flexible(record) {
+                Ok(c)
+                    if [c.open, c.high, c.low, c.close]
+                        .iter()
+                        .all(|v| v.is_finite())
+                        && c.high >= c.low =>
+                {
+                    c.time
+                }
+                _ => return Err(format!("record {}: invalid candle", i)),
+            }
+        };
+        if epoch < last_epoch {
+            return Err(format!("record {}: epoch {} is out of order", i, epoch));
+        }
+        last_epoch = epoch;
+    }
+    Ok(records.len())
+}
+
+fn tick_upload_error(status: StatusCode, error: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(
+            serde_json::json!({ "success": false, "error": error })
+                .to_string()
+                .into(),
+        )
+        .unwrap()
+}
+
+// POST /save_tick_history: store a browser-recorded candle/tick array as
+// tickhistory/<folder_name>/<filename>.json
 async fn save_tick_history_handler(
-    axum::Json(payload): axum::Json<SaveTickHistoryPayload>,
+    payload: Result<axum::Json<SaveTickHistoryPayload>, axum::extract::rejection::JsonRejection>,
 ) -> Response {
-    let path_str = format!("tickhistory/{}", payload.folder_name);
-    let path = Path::new(&path_str);
+    let payload = match payload {
+        Ok(axum::Json(payload)) => payload,
+        Err(e) => return tick_upload_error(e.status(), &e.body_text()),
+    };
 
-    if let Err(e) = std::fs::create_dir_all(path) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to create dir: {}", e),
-        )
-            .into_response();
+    if !is_safe_name(&payload.folder_name) || !is_safe_name(&payload.filename) {
+        return tick_upload_error(
+            StatusCode::BAD_REQUEST,
+            "folder_name and filename may only contain letters, digits, '_' and '-'",
+        );
+    }
+    if payload.data.len() > MAX_TICK_UPLOAD_BYTES {
+        return tick_upload_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("data exceeds {} bytes", MAX_TICK_UPLOAD_BYTES),
+        );
     }
+    let count = match validate_tick_history(&payload.data) {
+        Ok(count) => count,
+        Err(e) => return tick_upload_error(StatusCode::UNPROCESSABLE_ENTITY, &e),
+    };
 
-    let file_path = path.join(format!("{}.json", payload.filename));
-    match std::fs::write(&file_path, payload.data) {
-        Ok(_) => Response::builder()
-            .status(200)
-            .body("Saved".into())
-            .unwrap(),
-        Err(e) => (
+    let file_path = Path::new("tickhistory")
+        .join(&payload.folder_name)
+        .join(format!("{}.json", payload.filename));
+    match file_store::write_atomic(&file_path, payload.data.as_bytes()) {
+        Ok(_) => {
+            info!("💾 Tick history saved: {:?} ({} records)", file_path, count);
+            Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .body(
+                    serde_json::json!({ "success": true, "records": count })
+                        .to_string()
+                        .into(),
+                )
+                .unwrap()
+        }
+        Err(e) => tick_upload_error(
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to write: {}", e),
-        )
-            .into_response(),
+            &format!("Failed to write: {}", e),
+        ),
     }
 }
 
@@ -3752,13 +4440,12 @@ async fn save_scan_handler(
     State(state): State<Arc<AppState>>,
     axum::Json(payload): axum::Json<SaveScanPayload>,
 ) -> Response {
-    println!(
+    info!(
         "📊 Received scan data: {} assets at {}",
         payload.assets.len(),
         payload.scan_time
     );
 
-    let firestore = state.firestore.lock().await;
     let mut saved_count = 0;
     let mut errors = Vec::new();
 
@@ -3777,7 +4464,7 @@ async fn save_scan_handler(
             rank: asset.rank.unwrap_or(0),
         };
 
-        match firestore.save_scan(&record).await {
+        match state.ledger.record_scan(&record, true).await {
             Ok(_) => saved_count += 1,
             Err(e) => errors.push(format!("{}: {}", asset.symbol, e)),
         }
@@ -3831,7 +4518,7 @@ async fn scanner_start_handler(
     State(state): State<Arc<AppState>>,
     axum::Json(payload): axum::Json<ScannerStartPayload>,
 ) -> Response {
-    println!(
+    info!(
         "📊 Scanner start request: {} assets, interval {}s, save_db: {}",
         payload.assets.len(),
         payload.interval_seconds,
@@ -3878,7 +4565,7 @@ async fn scanner_start_handler(
 }
 
 async fn scanner_stop_handler(State(state): State<Arc<AppState>>) -> Response {
-    println!("📊 Scanner stop request");
+    info!("📊 Scanner stop request");
 
     let scanner_lock = state.scanner.read().await;
     if let Some(ref scanner) = *scanner_lock {
@@ -3928,6 +4615,141 @@ async fn scanner_status_handler(State(state): State<Arc<AppState>>) -> Response
     }
 }
 
+async fn recorder_start_handler(
+    State(state): State<Arc<AppState>>,
+    axum::Json(config): axum::Json<RecorderConfig>,
+) -> Response {
+    info!(
+        "🎙️ Recorder start request: {} assets, {}s candles",
+        config.assets.len(),
+        config.granularity
+    );
+    match state.recorder.start(config).await {
+        Ok(_) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body("{\"success\": true, \"message\": \"Recorder started\"}".into())
+            .unwrap(),
+        // The error can quote a rejected asset name, so it is escaped as JSON
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            axum::Json(serde_json::json!({ "success": false, "error": e })),
+        )
+            .into_response(),
+    }
+}
+
+async fn recorder_stop_handler(State(state): State<Arc<AppState>>) -> Response {
+    info!("🎙️ Recorder stop request");
+    match state.recorder.stop().await {
+        Ok(_) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body("{\"success\": true, \"message\": \"Recorder stopped\"}".into())
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(400)
+            .header("Content-Type", "application/json")
+            .body(format!("{{\"success\": false, \"error\": \"{}\"}}", e).into())
+            .unwrap(),
+    }
+}
+
+async fn recorder_status_handler(State(state): State<Arc<AppState>>) -> Response {
+    let status = state.recorder.get_status().await;
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&status).unwrap_or_default().into())
+        .unwrap()
+}
+
+fn recording_response<T: Serialize>(key: &str, result: Result<Vec<T>, String>) -> Response {
+    match result {
+        Ok(items) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": true, "count": items.len(), key: items })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(400)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": false, "error": e })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
+    }
+}
+
+/// Recording files are read on the blocking pool
+async fn read_recording<T: Send + 'static>(
+    read: impl FnOnce() -> Result<Vec<T>, String> + Send + 'static,
+) -> Result<Vec<T>, String> {
+    tokio::task::spawn_blocking(read)
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()))
+}
+
+// GET /api/recorder/ticks?symbol=R_10&from=<unix>&to=<unix>&limit=N
+async fn recorder_ticks_handler(
+    axum::extract::Query(query): axum::extract::Query<RecordingQuery>,
+) -> Response {
+    recording_response("ticks", read_recording(move || query.ticks()).await)
+}
+
+// GET /api/recorder/candles?symbol=R_10&granularity=60&from=<unix>&to=<unix>&limit=N
+async fn recorder_candles_handler(
+    axum::extract::Query(query): axum::extract::Query<RecordingQuery>,
+) -> Response {
+    recording_response("candles", read_recording(move || query.candles()).await)
+}
+
+// GET /api/recorder/gaps?symbol=R_10&from=<unix>&to=<unix>
+async fn recorder_gaps_handler(
+    axum::extract::Query(query): axum::extract::Query<RecordingQuery>,
+) -> Response {
+    recording_response("gaps", read_recording(move || query.gaps()).await)
+}
+
+#[derive(Debug, Serialize)]
+pub struct MasterCodesReport {
+    version: u32,
+    codes: Vec<CandleMasterCode>,
+    /// Valid descriptors without a code in the loaded table
+    missing: Vec<String>,
+    /// Descriptors produced live since startup that had no code
+    unmapped: Vec<indicator_math_v2::master_codes::UnmappedDescriptor>,
+}
+
+async fn master_codes_handler() -> Response {
+    let table = MasterCodeTable::load_or_embedded(Path::new(CANDLE_MASTER_CODES_FILE));
+    let report = MasterCodesReport {
+        version: table.version,
+        missing: table.missing_descriptors(),
+        codes: table.codes,
+        unmapped: indicator_math_v2::master_codes::unmapped_report(),
+    };
+
+    match serde_json::to_string(&report) {
+        Ok(json) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body(json.into())
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header("Content-Type", "application/json")
+            .body(format!("{{\"error\": \"{}\"}}", e).into())
+            .unwrap(),
+    }
+}
+
 async fn system_resources_handler() -> Response {
     let mut sys = System::new_all();
     sys.refresh_all();
@@ -3960,6 +4782,42 @@ async fn system_resources_handler() -> Response {
     }
 }
 
+// GET /metrics: Prometheus text format. Closed unless the request has a viewer session or
+// `Authorization: Bearer <METRICS_TOKEN>`; without METRICS_TOKEN only sessions get in.
+async fn metrics_handler(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let token_ok = match (bearer, env::var("METRICS_TOKEN")) {
+        (Some(presented), Ok(token)) if !token.is_empty() => {
+            auth::secret_matches(presented, &token)
+        }
+        _ => false,
+    };
+    if !token_ok && auth::current_user(&session, &state.users).await.is_none() {
+        return Response::builder()
+            .status(401)
+            .body("unauthorized\n".into())
+            .unwrap();
+    }
+
+    let m = metrics();
+    m.firestore_queue_depth
+        .set(state.ledger.outbox.status().depth as i64);
+    m.broadcast_queued.set(state.tx.len() as i64);
+
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(m.render().into())
+        .unwrap()
+}
+
 // ==================== Trading Config API Handlers ====================
 
 const TRADING_CONFIG_FILE: &str = "public/config.json";
@@ -4014,28 +4872,22 @@ pub struct AssetItem {
 }
 
 fn load_trading_config() -> Option<TradingConfigPayload> {
-    match fs::read_to_string(TRADING_CONFIG_FILE) {
-        Ok(content) => match serde_json::from_str::<TradingConfigPayload>(&content) {
-            Ok(config) => Some(config),
-            Err(e) => {
-                println!("⚠️ Trading config parse error: {}", e);
-                None
-            }
-        },
-        Err(_) => {
-            println!("📁 No trading config file found, will create on first save");
-            None
-        }
+    let config = file_store::read_recovering(Path::new(TRADING_CONFIG_FILE), |s| {
+        serde_json::from_str::<TradingConfigPayload>(s)
+    });
+    if config.is_none() {
+        info!("📁 No usable trading config file, will create on first save");
     }
+    config
 }
 
 fn save_trading_config(config: &TradingConfigPayload) -> Result<(), String> {
     match serde_json::to_string_pretty(config) {
         Ok(json_str) => {
-            if let Err(e) = fs::write(TRADING_CONFIG_FILE, json_str) {
+            if let Err(e) = file_store::write_atomic(Path::new(TRADING_CONFIG_FILE), json_str) {
                 Err(format!("Failed to write config file: {}", e))
             } else {
-                println!(
+                info!(
                     "💾 Trading config saved successfully for user: {}",
                     config.username
                 );
@@ -4121,9 +4973,13 @@ async fn get_trading_config_handler() -> Response {
 }
 
 async fn save_trading_config_handler(
-    axum::Json(payload): axum::Json<TradingConfigPayload>,
+    session: Session,
+    axum::Json(mut payload): axum::Json<TradingConfigPayload>,
 ) -> Response {
-    println!("📊 Saving trading config for user: {}", payload.username);
+    if let Some((username, _)) = auth::session_user(&session).await {
+        payload.username = username;
+    }
+    info!("📊 Saving trading config for user: {}", payload.username);
 
     match save_trading_config(&payload) {
         Ok(_) => Response::builder()
@@ -4139,6 +4995,198 @@ async fn save_trading_config_handler(
     }
 }
 
+// ==================== ACCOUNT HANDLERS ====================
+
+#[derive(Debug, Deserialize)]
+pub struct DerivTokenPayload {
+    pub token: String,
+}
+
+fn account_json(status: u16, body: serde_json::Value) -> Response {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(body.to_string().into())
+        .unwrap()
+}
+
+// GET /api/account: username, role and which Deriv accounts have a stored token
+async fn account_handler(State(state): State<Arc<AppState>>, session: Session) -> Response {
+    let Some((username, role)) = auth::session_user(&session).await else {
+        return account_json(401, serde_json::json!({ "error": "not logged in" }));
+    };
+    let deriv_accounts: Vec<DerivAccount> = state
+        .secrets
+        .as_ref()
+        .map(|s| s.status(&username).into_iter().map(|t| t.account).collect())
+        .unwrap_or_default();
+    account_json(
+        200,
+        serde_json::json!({
+            "username": username,
+            "role": role,
+            "deriv_accounts": deriv_accounts,
+        }),
+    )
+}
+
+/// Session user, secret store and account from a token request, or the error response
+async fn token_request(
+    state: &AppState,
+    session: &Session,
+    account: &str,
+) -> Result<(String, Arc<SecretStore>, DerivAccount), Response> {
+    let Some((username, _)) = auth::session_user(session).await else {
+        return Err(account_json(
+            401,
+            serde_json::json!({ "success": false, "error": "not logged in" }),
+        ));
+    };
+    let Some(secrets) = state.secrets.clone() else {
+        return Err(account_json(
+            503,
+            serde_json::json!({ "success": false, "error": "token storage disabled: RELAY_SECRET_KEY is not set" }),
+        ));
+    };
+    let Some(account) = DerivAccount::parse(account) else {
+        return Err(account_json(
+            400,
+            serde_json::json!({ "success": false, "error": "account must be demo or real" }),
+        ));
+    };
+    Ok((username, secrets, account))
+}
+
+// GET /api/account/tokens: stored Deriv tokens of the logged-in user (hints only)
+async fn list_tokens_handler(State(state): State<Arc<AppState>>, session: Session) -> Response {
+    let Some((username, _)) = auth::session_user(&session).await else {
+        return account_json(401, serde_json::json!({ "error": "not logged in" }));
+    };
+    let tokens = state
+        .secrets
+        .as_ref()
+        .map(|s| s.status(&username))
+        .unwrap_or_default();
+    account_json(
+        200,
+        serde_json::json!({ "enabled": state.secrets.is_some(), "tokens": tokens }),
+    )
+}
+
+// POST /api/account/tokens/:account (demo|real): set or rotate a Deriv token
+async fn set_token_handler(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    axum::extract::Path(account): axum::extract::Path<String>,
+    axum::Json(payload): axum::Json<DerivTokenPayload>,
+) -> Response {
+    let (username, secrets, account) = match token_request(&state, &session, &account).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    if !secrets::is_valid_token(&payload.token) {
+        return account_json(
+            400,
+            serde_json::json!({ "success": false, "error": "invalid token" }),
+        );
+    }
+    let user = username.clone();
+    let result =
+        tokio::task::spawn_blocking(move || secrets.set_token(&user, account, &payload.token))
+            .await
+            .unwrap_or_else(|e| Err(anyhow::anyhow!(e)));
+    match result {
+        Ok(_) => {
+            info!(
+                "🔑 Deriv {} token updated for {}",
+                account.as_str(),
+                username
+            );
+            account_json(200, serde_json::json!({ "success": true }))
+        }
+        Err(e) => {
+            error!("❌ Storing Deriv token for {}: {:#}", username, e);
+            account_json(
+                500,
+                serde_json::json!({ "success": false, "error": "token storage unavailable" }),
+            )
+        }
+    }
+}
+
+// DELETE /api/account/tokens/:account
+async fn delete_token_handler(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    axum::extract::Path(account): axum::extract::Path<String>,
+) -> Response {
+    let (username, secrets, account) = match token_request(&state, &session, &account).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    let user = username.clone();
+    let result = tokio::task::spawn_blocking(move || secrets.remove_token(&user, account))
+        .await
+        .unwrap_or_else(|e| Err(anyhow::anyhow!(e)));
+    match result {
+        Ok(true) => {
+            info!(
+                "🔑 Deriv {} token removed for {}",
+                account.as_str(),
+                username
+            );
+            account_json(200, serde_json::json!({ "success": true }))
+        }
+        Ok(false) => account_json(
+            404,
+            serde_json::json!({ "success": false, "error": "no token stored" }),
+        ),
+        Err(e) => {
+            error!("❌ Removing Deriv token for {}: {:#}", username, e);
+            account_json(
+                500,
+                serde_json::json!({ "success": false, "error": "token storage unavailable" }),
+            )
+        }
+    }
+}
+
+// ==================== LOG FILTER HANDLERS ====================
+
+#[derive(Deserialize)]
+struct LogFilterPayload {
+    filter: String,
+}
+
+async fn get_log_filter_handler(State(state): State<Arc<AppState>>) -> Response {
+    account_json(
+        200,
+        serde_json::json!({ "success": true, "filter": state.logging.filter() }),
+    )
+}
+
+/// `PUT /api/admin/log-filter` with `{"filter": "info,rust_deriv_relay=debug"}`
+async fn set_log_filter_handler(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    axum::Json(payload): axum::Json<LogFilterPayload>,
+) -> Response {
+    let username = auth::session_user(&session)
+        .await
+        .map(|(u, _)| u)
+        .unwrap_or_default();
+    match state.logging.set_filter(&payload.filter) {
+        Ok(()) => {
+            warn!(user = %username, "🎚️ Log filter changed to {}", payload.filter.trim());
+            account_json(
+                200,
+                serde_json::json!({ "success": true, "filter": state.logging.filter() }),
+            )
+        }
+        Err(e) => account_json(400, serde_json::json!({ "success": false, "error": e })),
+    }
+}
+
 // ==================== SAVE TRADE HANDLER ====================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -4160,13 +5208,17 @@ pub struct SaveTradePayload {
     pub entry_spot: f64,
     #[serde(default)]
     pub exit_spot: f64,
+    #[serde(default)]
+    pub status_code: String,
+    #[serde(default)]
+    pub martingale_step: Option<u32>,
 }
 
 async fn save_trade_handler(
     State(state): State<Arc<AppState>>,
     axum::Json(payload): axum::Json<SaveTradePayload>,
 ) -> Response {
-    println!(
+    info!(
         "💾 Save trade request: {} {} {} profit: {}",
         payload.contract_id, payload.symbol, payload.status, payload.profit_loss
     );
@@ -4186,6 +5238,8 @@ async fn save_trade_handler(
         min_profit: 0.0,
         max_profit: 0.0,
         status: payload.status.clone(),
+        status_code: payload.status_code.clone(),
+        martingale_step: payload.martingale_step,
         entry_spot: payload.entry_spot,
         exit_spot: payload.exit_spot,
         lot_no: 0,
@@ -4194,43 +5248,358 @@ async fn save_trade_handler(
         created_at: payload.created_at.clone(),
     };
 
-    // Save to Firestore
-    let firestore = state.firestore.lock().await;
-    match firestore.save_trade(&trade_record).await {
-        Ok(doc_id) => {
-            println!("✅ Trade saved to Firestore: {}", doc_id);
-            Response::builder()
-                .status(200)
-                .header("Content-Type", "application/json")
-                .body(format!("{{\"success\": true, \"doc_id\": \"{}\"}}", doc_id).into())
-                .unwrap()
-        }
-        Err(e) => {
-            println!("❌ Failed to save trade: {}", e);
-            Response::builder()
-                .status(500)
+    // The local store is the source of truth; Firestore is queued behind it
+    let doc_id = firestore_manager::trade_doc_id(&trade_record);
+    let outbox = state
+        .ledger
+        .sinks
+        .firestore
+        .then(|| state.ledger.outbox.clone());
+    let saved = state
+        .ledger
+        .with_store(move |store| {
+            store.save_trade(&trade_record)?;
+            if let Some(outbox) = outbox {
+                outbox.enqueue(OutboxRecord::Trade(trade_record));
+            }
+            Ok(())
+        })
+        .await;
+    if let Err(e) = saved {
+        error!("❌ Failed to save trade: {}", e);
+        return Response::builder()
+            .status(500)
+            .header("Content-Type", "application/json")
+            .body(format!("{{\"success\": false, \"error\": \"{}\"}}", e).into())
+            .unwrap();
+    }
+
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(format!("{{\"success\": true, \"doc_id\": \"{}\"}}", doc_id).into())
+        .unwrap()
+}
+
+// GET /api/trades?from=2026-10-11&to=2026-10-18&symbol=R_75&status=loss&status_code=12
+async fn query_trades_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(filter): axum::extract::Query<TradeFilter>,
+) -> Response {
+    match state
+        .ledger
+        .with_store(move |store| store.query_trades(&filter))
+        .await
+    {
+        Ok(trades) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": true, "count": trades.len(), "trades": trades })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header("Content-Type", "application/json")
+            .body(format!("{{\"success\": false, \"error\": \"{}\"}}", e).into())
+            .unwrap(),
+    }
+}
+
+// GET /api/firestore/outbox: queue depth and failure counters of the Firestore writer
+async fn firestore_outbox_status_handler(State(state): State<Arc<AppState>>) -> Response {
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(
+            serde_json::to_string(&state.ledger.outbox.status())
+                .unwrap_or_default()
+                .into(),
+        )
+        .unwrap()
+}
+
+fn firestore_query_response<T: Serialize>(result: Result<Vec<T>, FirestoreError>) -> Response {
+    match result {
+        Ok(items) => Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": true, "count": items.len(), "items": items })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(match e {
+                FirestoreError::NotInitialized => 503,
+                _ => 500,
+            })
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": false, "error": e.to_string() })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
+    }
+}
+
+// GET /api/firestore/trades?from=&to=&symbol=&lot_no=&status=&status_code=&limit=
+async fn firestore_trades_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(filter): axum::extract::Query<TradeFilter>,
+) -> Response {
+    firestore_query_response(state.firestore.query_trades(&filter).await)
+}
+
+// GET /api/firestore/scans?from=&to=&symbol=&limit=
+async fn firestore_scans_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(filter): axum::extract::Query<ScanFilter>,
+) -> Response {
+    firestore_query_response(state.firestore.query_scans(&filter).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct TopRankedQuery {
+    scan_time: Option<String>,
+    limit: Option<u32>,
+}
+
+// GET /api/firestore/scans/top?scan_time=&limit=  (latest scan when scan_time is omitted)
+async fn firestore_top_ranked_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<TopRankedQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(10);
+    firestore_query_response(
+        state
+            .firestore
+            .top_ranked(query.scan_time.as_deref(), limit)
+            .await,
+    )
+}
+
+/// `trade_export::collect_rows` on the blocking pool (SQLite and day-file reads)
+async fn export_rows(state: &AppState, query: &TradeHistoryQuery) -> Vec<TradeHistoryRow> {
+    let query = query.clone();
+    state
+        .ledger
+        .with_store(move |store| Ok(trade_export::collect_rows(&query, store)))
+        .await
+        .unwrap_or_else(|e| {
+            error!("❌ Trade history export failed: {}", e);
+            Vec::new()
+        })
+}
+
+// GET /api/trade_history?from=2026-10-12&to=2026-10-18&asset=R_75&format=csv|json|parquet
+async fn trade_history_export_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(mut query): axum::extract::Query<TradeHistoryQuery>,
+) -> Response {
+    let (from, to) = match query.parse_dates() {
+        Ok(dates) => dates,
+        Err(e) => return bad_date_response(&e),
+    };
+    let format = query.format.as_deref().unwrap_or("json").to_lowercase();
+    let rows = export_rows(&state, &query).await;
+    let file_name = format!(
+        "trade_history_{}_{}",
+        from.map_or("start".to_string(), |d| d.format("%Y-%m-%d").to_string()),
+        to.map_or("end".to_string(), |d| d.format("%Y-%m-%d").to_string())
+    );
+
+    let (body, content_type) = match format.as_str() {
+        "json" => return axum::Json(rows).into_response(),
+        "csv" => (trade_export::to_csv(&rows), "text/csv; charset=utf-8"),
+        "parquet" => (
+            trade_export::to_parquet(&rows),
+            "application/vnd.apache.parquet",
+        ),
+        other => {
+            return Response::builder()
+                .status(400)
                 .header("Content-Type", "application/json")
-                .body(format!("{{\"success\": false, \"error\": \"{}\"}}", e).into())
-                .unwrap()
+                .body(
+                    serde_json::json!({
+                        "success": false,
+                        "error": format!("unknown format '{}', use csv, json or parquet", other)
+                    })
+                    .to_string()
+                    .into(),
+                )
+                .unwrap();
         }
+    };
+
+    match body {
+        Ok(bytes) => Response::builder()
+            .status(200)
+            .header("Content-Type", content_type)
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}.{}\"", file_name, format),
+            )
+            .body(bytes.into())
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::json!({ "success": false, "error": e.to_string() })
+                    .to_string()
+                    .into(),
+            )
+            .unwrap(),
     }
 }
 
+fn bad_date_response(error: &str) -> Response {
+    Response::builder()
+        .status(400)
+        .header("Content-Type", "application/json")
+        .body(
+            serde_json::json!({ "success": false, "error": error })
+                .to_string()
+                .into(),
+        )
+        .unwrap()
+}
+
+// GET /api/analytics/performance?from=2026-10-12&to=2026-10-18&asset=R_75
+async fn performance_report_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(mut query): axum::extract::Query<TradeHistoryQuery>,
+) -> Response {
+    if let Err(e) = query.parse_dates() {
+        return bad_date_response(&e);
+    }
+    let rows = export_rows(&state, &query).await;
+    let report = analytics::build_report(&rows);
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(
+            serde_json::json!({
+                "success": true,
+                "from": query.from,
+                "to": query.to,
+                "asset": query.asset,
+                "report": report,
+            })
+            .to_string()
+            .into(),
+        )
+        .unwrap()
+}
+
 // ==================== NEW DAY TRADE API ====================
 pub async fn get_today_trade_history_handler() -> impl IntoResponse {
     let today = get_daily_folder_name();
     let path = format!("tradeHistory/{}/trade.json", today);
 
-    match std::fs::read_to_string(&path) {
-        Ok(contents) => Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "application/json")
-            .body(axum::body::Body::from(contents))
-            .unwrap(),
-        Err(_) => {
+    match file_store::read_fallback(Path::new(&path), |s| {
+        serde_json::from_str::<serde_json::Value>(s)
+    }) {
+        Some(contents) => axum::Json(contents).into_response(),
+        None => {
             // If file doesn't exist, return empty JSON instead of error
             let empty = serde_json::json!({});
             axum::Json(empty).into_response()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_safe_name() {
+        for name in ["R_10", "2026-10-11", "ticks-1HZ100V_60"] {
+            assert!(is_safe_name(name), "{}", name);
+        }
+        for name in ["", "..", "../etc", "a/b", "a\\b", "a.json", "R 10", "ชื่อ"] {
+            assert!(!is_safe_name(name), "{}", name);
+        }
+        assert!(is_safe_name(&"a".repeat(128)));
+        assert!(!is_safe_name(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn test_validate_tick_history() {
+        let ticks = r#"[{"epoch": 1, "quote": 1.5}, {"epoch": 2, "quote": "1.6"}]"#;
+        assert_eq!(validate_tick_history(ticks), Ok(2));
+        let candles = r#"[{"epoch": 60, "open": 1, "high": 2, "low": 0.5, "close": 1.5}]"#;
+        assert_eq!(validate_tick_history(candles), Ok(1));
+
+        for (data, error) in [
+            ("{}", "not a JSON array"),
+            ("[]", "empty"),
+            (r#"[{"epoch": 1}]"#, "record 0: invalid candle"),
+            (r#"[{"epoch": 1, "quote": "x"}]"#, "record 0: invalid tick"),
+            (r#"[{"quote": 1.5}]"#, "record 0: invalid tick"),
+            (
+                r#"[{"epoch": 60, "open": 1, "high": 0.5, "low": 2, "close": 1}]"#,
+                "record 0: invalid candle",
+            ),
+            (
+                r#"[{"epoch": 2, "quote": 1.5}, {"epoch": 1, "quote": 1.5}]"#,
+                "record 1: epoch 1 is out of order",
+            ),
+        ] {
+            let result = validate_tick_history(data);
+            assert!(
+                result.as_ref().is_err_and(|e| e.contains(error)),
+                "{}: {:?}",
+                data,
+                result
+            );
+        }
+
+        let too_many = format!(
+            "[{}]",
+            vec![r#"{"epoch": 1, "quote": 1}"#; MAX_TICK_UPLOAD_RECORDS + 1].join(",")
+        );
+        assert!(validate_tick_history(&too_many).is_err_and(|e| e.contains("limit")));
+    }
+
+    #[test]
+    fn test_resume_generator_backfills_only_the_gap() {
+        let dir = std::env::temp_dir().join(format!("generator_snapshots_{}", std::process::id()));
+        let analysis = V2AnalysisManager::new(V2AnalysisOptions::default(), Vec::new())
+            .with_retention(V2RetentionPolicy::KeepLast(V2_HISTORY_RETENTION));
+
+        let (_, resumed_from, count) = resume_generator(&analysis, &dir, "R_10", 6000);
+        assert_eq!((resumed_from, count), (None, MAX_SNAPSHOT_GAP as usize));
+
+        let (mut gen, _, _) = resume_generator(&analysis, &dir, "R_10", 6000);
+        for i in 0..100u64 {
+            let price = 100.0 + (i as f64 * 0.3).sin();
+            gen.append_candle(V2Candle {
+                time: i * 60,
+                open: price,
+                high: price + 1.0,
+                low: price - 1.0,
+                close: price,
+            });
+        }
+        analysis.generators.insert("R_10".to_string(), gen);
+        assert!(analysis.save_snapshot(&dir, "R_10").unwrap());
+
+        // Five minutes after the last closed candle: fetch the gap plus the forming candle
+        let (gen, resumed_from, count) = resume_generator(&analysis, &dir, "R_10", 104 * 60);
+        assert_eq!((resumed_from, count), (Some(99 * 60), 7));
+        assert_eq!(gen.analysis_array.len(), 100);
+
+        let stale = (100 + MAX_SNAPSHOT_GAP + 1) * 60;
+        let (gen, resumed_from, _) = resume_generator(&analysis, &dir, "R_10", stale);
+        assert_eq!((resumed_from, gen.last_candle_time()), (None, None));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
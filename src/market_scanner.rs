@@ -6,8 +6,11 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-use crate::firestore_manager::{GlobalFirestore, ScanRecord};
+use crate::firestore_manager::ScanRecord;
+use crate::metrics::metrics;
+use crate::trade_store::TradeLedger;
 
 /// Scanner configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,15 +86,15 @@ impl Default for ScannerStatus {
 pub struct MarketScanner {
     status: Arc<RwLock<ScannerStatus>>,
     task_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
-    firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: Arc<TradeLedger>,
 }
 
 impl MarketScanner {
-    pub fn new(firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>) -> Self {
+    pub fn new(ledger: Arc<TradeLedger>) -> Self {
         Self {
             status: Arc::new(RwLock::new(ScannerStatus::default())),
             task_handle: Arc::new(RwLock::new(None)),
-            firestore,
+            ledger,
         }
     }
 
@@ -123,13 +126,16 @@ impl MarketScanner {
 
         // Clone necessary handles for the async task
         let status_handle = self.status.clone();
-        let firestore_handle = self.firestore.clone();
+        let ledger_handle = self.ledger.clone();
         let config_clone = config.clone();
 
         // Spawn the scanning task
-        let handle = tokio::spawn(async move {
-            run_scanner_loop(status_handle, firestore_handle, config_clone).await;
-        });
+        let handle = tokio::spawn(
+            async move {
+                run_scanner_loop(status_handle, ledger_handle, config_clone).await;
+            }
+            .instrument(info_span!(parent: None, "scanner")),
+        );
 
         // Store the task handle
         {
@@ -137,7 +143,7 @@ impl MarketScanner {
             *task = Some(handle);
         }
 
-        println!(
+        info!(
             "🚀 Market Scanner started with interval: {}s",
             config.interval_seconds
         );
@@ -168,7 +174,7 @@ impl MarketScanner {
             status.next_scan_time = None;
         }
 
-        println!("⏹️ Market Scanner stopped");
+        info!("⏹️ Market Scanner stopped");
         Ok(())
     }
 }
@@ -176,7 +182,7 @@ impl MarketScanner {
 /// Main scanning loop
 async fn run_scanner_loop(
     status: Arc<RwLock<ScannerStatus>>,
-    firestore: Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: Arc<TradeLedger>,
     config: ScanConfig,
 ) {
     let interval = tokio::time::Duration::from_secs(config.interval_seconds);
@@ -185,7 +191,7 @@ async fn run_scanner_loop(
         // Check stop time
         if let Some(ref stop_time_str) = config.stop_time {
             // Try to parse the stop time - it should be in ISO 8601 format
-            println!(
+            info!(
                 "🕐 Checking stop time: {} vs now: {}",
                 stop_time_str,
                 Utc::now()
@@ -193,16 +199,16 @@ async fn run_scanner_loop(
             if let Ok(stop_time) = DateTime::parse_from_rfc3339(stop_time_str) {
                 let stop_utc = stop_time.with_timezone(&Utc);
                 let now = Utc::now();
-                println!("🕐 Parsed stop time: {} vs now: {}", stop_utc, now);
+                debug!("🕐 Parsed stop time: {} vs now: {}", stop_utc, now);
                 if now >= stop_utc {
-                    println!("⏰ Stop time reached. Stopping scanner...");
+                    info!("⏰ Stop time reached. Stopping scanner...");
                     let mut s = status.write().await;
                     s.is_running = false;
                     s.next_scan_time = None;
                     break;
                 }
             } else {
-                println!("⚠️ Could not parse stop time: {}", stop_time_str);
+                warn!("⚠️ Could not parse stop time: {}", stop_time_str);
             }
         }
 
@@ -214,14 +220,17 @@ async fn run_scanner_loop(
         }
 
         // Perform scan
-        match perform_scan(&config, &status, &firestore).await {
+        let timer = metrics().scan_duration.start_timer();
+        let result = perform_scan(&config, &status, &ledger).await;
+        timer.observe_duration();
+        match result {
             Ok(results) => {
                 let mut s = status.write().await;
                 s.total_scans += 1;
                 s.total_records_saved += results.len() as u64;
                 s.last_scan_time = Some(Utc::now().to_rfc3339());
                 s.last_results = results;
-                println!(
+                info!(
                     "✅ Scan #{} completed. {} records saved.",
                     s.total_scans,
                     s.last_results.len()
@@ -235,7 +244,7 @@ async fn run_scanner_loop(
                 if s.errors.len() > 10 {
                     s.errors.remove(0);
                 }
-                println!("❌ Scan error: {}", e);
+                error!("❌ Scan error: {}", e);
             }
         }
 
@@ -248,7 +257,7 @@ async fn run_scanner_loop(
 async fn perform_scan(
     config: &ScanConfig,
     _status: &Arc<RwLock<ScannerStatus>>,
-    firestore: &Arc<tokio::sync::Mutex<GlobalFirestore>>,
+    ledger: &Arc<TradeLedger>,
 ) -> Result<Vec<AssetScanResult>, String> {
     use futures_util::{SinkExt, StreamExt};
     use tokio_tungstenite::connect_async;
@@ -256,9 +265,11 @@ async fn perform_scan(
 
     let url = "wss://ws.derivws.com/websockets/v3?app_id=66726";
 
-    let (ws_stream, _) = connect_async(url)
-        .await
-        .map_err(|e| format!("WebSocket connection failed: {}", e))?;
+    let (ws_stream, _) = connect_async(url).await.map_err(|e| {
+        metrics().connect_failed("scanner");
+        format!("WebSocket connection failed: {}", e)
+    })?;
+    let _connected = metrics().connection("scanner");
 
     let (mut write, mut read) = ws_stream.split();
 
@@ -292,6 +303,7 @@ async fn perform_scan(
         if let Message::Text(text) = response {
             let json: serde_json::Value =
                 serde_json::from_str(&text).map_err(|e| format!("JSON parse error: {}", e))?;
+            metrics().message("scanner", &json);
 
             if let Some(_error) = json.get("error") {
                 continue; // Skip this asset but continue with others
@@ -327,28 +339,35 @@ async fn perform_scan(
         result.rank = (index + 1) as u32;
     }
 
-    // Save to Firestore with ranks
-    if config.save_to_firestore {
-        let fs = firestore.lock().await;
-        for result in &results {
-            let record = ScanRecord {
-                scan_time: result.scan_time.clone(),
-                timeframe: config.candle_timeframe.to_string(),
-                period: config.indicator_period.to_string(),
-                symbol: result.symbol.clone(),
-                price: result.price,
-                ci: result.ci,
-                adx: result.adx,
-                score: result.score,
-                is_bullish: result.is_bullish,
-                recent_candles: result.recent_candles.clone(),
-                rank: result.rank,
-            };
-            let _ = fs.save_scan(&record).await;
+    // Save with ranks: always to the local store, to Firestore when enabled
+    for result in &results {
+        let record = ScanRecord {
+            scan_time: result.scan_time.clone(),
+            timeframe: config.candle_timeframe.to_string(),
+            period: config.indicator_period.to_string(),
+            symbol: result.symbol.clone(),
+            price: result.price,
+            ci: result.ci,
+            adx: result.adx,
+            score: result.score,
+            is_bullish: result.is_bullish,
+            recent_candles: result.recent_candles.clone(),
+            rank: result.rank,
+        };
+        if let Err(e) = ledger.record_scan(&record, config.save_to_firestore).await {
+            warn!("⚠️ Scan save error for {}: {}", record.symbol, e);
         }
-        println!("🔥 Saved {} scan records to Firestore", results.len());
+    }
+    if config.save_to_firestore {
+        info!(
+            "🔥 Saved {} scan records (queued for Firestore)",
+            results.len()
+        );
     } else {
-        println!("⚠️ save_to_firestore is false -> Skipping Firestore save");
+        warn!(
+            "⚠️ save_to_firestore is false -> Saved {} scan records locally only",
+            results.len()
+        );
     }
 
     Ok(results)
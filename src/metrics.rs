@@ -0,0 +1,205 @@
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+
+// Prometheus metrics, served in text format at `GET /metrics`.
+//
+// Counters are bumped where things happen (Deriv read loops, bots, scanner, browser sockets);
+// gauges that mirror existing state, like the Firestore queue depth, are refreshed on scrape.
+// The `bot` label is the task kind: single, multi, auto, scanner or recorder.
+
+pub struct Metrics {
+    registry: Registry,
+    deriv_connected: IntGaugeVec,
+    deriv_connects: IntCounterVec,
+    deriv_reconnects: IntCounterVec,
+    deriv_messages: IntCounterVec,
+    pub broadcast_lagged: IntCounter,
+    pub broadcast_queued: IntGauge,
+    pub browsers: IntGauge,
+    buys_sent: IntCounterVec,
+    buys_failed: IntCounterVec,
+    contracts_settled: IntCounterVec,
+    bot_profit: GaugeVec,
+    pub scan_duration: Histogram,
+    pub firestore_queue_depth: IntGauge,
+}
+
+/// Process-wide metrics
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        fn register<T: prometheus::core::Collector + Clone + 'static>(
+            registry: &Registry,
+            metric: T,
+        ) -> T {
+            registry
+                .register(Box::new(metric.clone()))
+                .expect("metric names are unique");
+            metric
+        }
+        let counter_vec = |name: &str, help: &str, labels: &[&str]| {
+            register(
+                &registry,
+                IntCounterVec::new(Opts::new(name, help), labels).expect("valid metric"),
+            )
+        };
+        let gauge = |name: &str, help: &str| {
+            register(&registry, IntGauge::new(name, help).expect("valid metric"))
+        };
+
+        Self {
+            deriv_connected: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new(
+                        "relay_deriv_connected",
+                        "1 while the Deriv websocket is open",
+                    ),
+                    &["bot"],
+                )
+                .expect("valid metric"),
+            ),
+            deriv_connects: counter_vec(
+                "relay_deriv_connects_total",
+                "Deriv websocket connection attempts",
+                &["bot", "result"],
+            ),
+            deriv_reconnects: counter_vec(
+                "relay_deriv_reconnects_total",
+                "Automatic reconnects after a lost Deriv connection",
+                &["bot"],
+            ),
+            deriv_messages: counter_vec(
+                "relay_deriv_messages_total",
+                "Messages received from Deriv by subscription type",
+                &["bot", "msg_type"],
+            ),
+            broadcast_lagged: register(
+                &registry,
+                IntCounter::new(
+                    "relay_broadcast_lagged_total",
+                    "Broadcast messages skipped by browsers that fell behind",
+                )
+                .expect("valid metric"),
+            ),
+            broadcast_queued: gauge(
+                "relay_broadcast_queued",
+                "Messages waiting in the browser broadcast channel",
+            ),
+            browsers: gauge("relay_browsers_connected", "Open browser websockets"),
+            buys_sent: counter_vec(
+                "relay_buys_sent_total",
+                "Buy requests sent to Deriv",
+                &["bot", "asset"],
+            ),
+            buys_failed: counter_vec(
+                "relay_buys_failed_total",
+                "Buy requests Deriv rejected or answered without a contract",
+                &["bot", "asset"],
+            ),
+            contracts_settled: counter_vec(
+                "relay_contracts_settled_total",
+                "Contracts settled",
+                &["bot", "asset", "result"],
+            ),
+            bot_profit: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new("relay_bot_profit", "Profit/loss of the running lot"),
+                    &["bot"],
+                )
+                .expect("valid metric"),
+            ),
+            scan_duration: register(
+                &registry,
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "relay_scan_duration_seconds",
+                        "Duration of one market scanner pass",
+                    )
+                    .buckets(vec![0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0]),
+                )
+                .expect("valid metric"),
+            ),
+            firestore_queue_depth: gauge(
+                "relay_firestore_queue_depth",
+                "Records waiting in the Firestore outbox",
+            ),
+            registry,
+        }
+    }
+
+    /// Count a successful connect and mark `bot` connected until the guard is dropped
+    pub fn connection(&self, bot: &'static str) -> ConnectionGuard {
+        self.deriv_connects.with_label_values(&[bot, "ok"]).inc();
+        self.deriv_connected.with_label_values(&[bot]).set(1);
+        ConnectionGuard(bot)
+    }
+
+    pub fn connect_failed(&self, bot: &str) {
+        self.deriv_connects.with_label_values(&[bot, "error"]).inc();
+    }
+
+    pub fn reconnect(&self, bot: &str) {
+        self.deriv_reconnects.with_label_values(&[bot]).inc();
+    }
+
+    /// Count a Deriv message under its `msg_type`
+    pub fn message(&self, bot: &str, json: &serde_json::Value) {
+        let msg_type = json
+            .get("msg_type")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown");
+        self.deriv_messages
+            .with_label_values(&[bot, msg_type])
+            .inc();
+    }
+
+    pub fn buy_sent(&self, bot: &str, asset: &str) {
+        self.buys_sent.with_label_values(&[bot, asset]).inc();
+    }
+
+    pub fn buy_failed(&self, bot: &str, asset: &str) {
+        self.buys_failed.with_label_values(&[bot, asset]).inc();
+    }
+
+    pub fn settled(&self, bot: &str, asset: &str, win: bool) {
+        let result = if win { "win" } else { "loss" };
+        self.contracts_settled
+            .with_label_values(&[bot, asset, result])
+            .inc();
+    }
+
+    pub fn bot_profit(&self, bot: &str, profit: f64) {
+        self.bot_profit.with_label_values(&[bot]).set(profit);
+    }
+
+    /// Text exposition of every metric
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            return format!("# encode error: {}\n", e);
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
+
+/// Clears the connected gauge when dropped, including when the task is aborted
+pub struct ConnectionGuard(&'static str);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        metrics()
+            .deriv_connected
+            .with_label_values(&[self.0])
+            .set(0);
+    }
+}
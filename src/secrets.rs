@@ -0,0 +1,567 @@
+use crate::file_store::DirLock;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tracing::error;
+
+// Encrypted store for Deriv API tokens, one per user and account (demo / real).
+//
+// Tokens are sealed with AES-256-GCM under `RELAY_SECRET_KEY` (32 bytes, base64; e.g.
+// `openssl rand -base64 32`) and written to `data/secrets.json`. The user and account are
+// bound in as associated data, so an entry cannot be moved to another user. To rotate the
+// key, move the old one to `RELAY_SECRET_KEY_PREVIOUS` and set a new `RELAY_SECRET_KEY`;
+// entries are re-encrypted at startup. A secrets file that exists but cannot be read is
+// never treated as empty: changes fail instead of overwriting the other users' tokens.
+//
+// `redact` masks token values before text is logged.
+
+pub const SECRETS_FILE: &str = "data/secrets.json";
+const KEY_ENV: &str = "RELAY_SECRET_KEY";
+const PREVIOUS_KEY_ENV: &str = "RELAY_SECRET_KEY_PREVIOUS";
+const MAX_TOKEN_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DerivAccount {
+    Demo,
+    Real,
+}
+
+/// Shape check for a Deriv API token (surrounding whitespace is ignored).
+pub fn is_valid_token(token: &str) -> bool {
+    let token = token.trim();
+    !token.is_empty() && token.len() <= MAX_TOKEN_LEN && !token.chars().any(char::is_whitespace)
+}
+
+impl DerivAccount {
+    pub fn parse(s: &str) -> Option<DerivAccount> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "demo" => Some(DerivAccount::Demo),
+            "real" => Some(DerivAccount::Real),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DerivAccount::Demo => "demo",
+            DerivAccount::Real => "real",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SealedToken {
+    nonce: String,
+    ciphertext: String,
+    updated_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SecretsFile {
+    tokens: BTreeMap<String, BTreeMap<DerivAccount, SealedToken>>,
+}
+
+/// What `GET /api/account/tokens` shows about a stored token
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStatus {
+    pub account: DerivAccount,
+    /// Last four characters, to tell tokens apart
+    pub hint: String,
+    pub updated_at: String,
+}
+
+fn parse_key(var: &str) -> anyhow::Result<Option<Aes256Gcm>> {
+    let Ok(value) = std::env::var(var) else {
+        return Ok(None);
+    };
+    let bytes = BASE64
+        .decode(value.trim())
+        .with_context(|| format!("{} is not valid base64", var))?;
+    Aes256Gcm::new_from_slice(&bytes)
+        .map(Some)
+        .map_err(|_| anyhow!("{} must decode to 32 bytes", var))
+}
+
+fn aad(username: &str, account: DerivAccount) -> Vec<u8> {
+    format!("deriv-token:{}:{}", username, account.as_str()).into_bytes()
+}
+
+pub struct SecretStore {
+    path: PathBuf,
+    key: Aes256Gcm,
+    previous: Option<Aes256Gcm>,
+}
+
+impl SecretStore {
+    /// `None` when `RELAY_SECRET_KEY` is not set; an error when a key is malformed
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(key) = parse_key(KEY_ENV)? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            path: PathBuf::from(SECRETS_FILE),
+            key,
+            previous: parse_key(PREVIOUS_KEY_ENV)?,
+        }))
+    }
+
+    fn read(&self, held: Option<&DirLock>) -> anyhow::Result<SecretsFile> {
+        let file = crate::file_store::read_existing(
+            &self.path,
+            |s| serde_json::from_str::<SecretsFile>(s),
+            held,
+        )?;
+        Ok(file.unwrap_or_default())
+    }
+
+    fn load(&self) -> anyhow::Result<SecretsFile> {
+        self.read(None)
+    }
+
+    /// Read-modify-write under the directory lock, so concurrent changes are not lost.
+    /// Nothing is written when `f` returns false.
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut SecretsFile) -> anyhow::Result<(T, bool)>,
+    ) -> anyhow::Result<T> {
+        let lock = crate::file_store::lock_parent(&self.path)
+            .with_context(|| format!("locking {:?}", self.path))?;
+        let mut file = self.read(Some(&lock))?;
+        let (result, changed) = f(&mut file)?;
+        if changed {
+            let json = serde_json::to_string_pretty(&file)?;
+            crate::file_store::write_atomic_locked(&lock, &self.path, json)
+                .with_context(|| format!("writing {:?}", self.path))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ =
+                    std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o600));
+            }
+        }
+        Ok(result)
+    }
+
+    fn seal(
+        &self,
+        username: &str,
+        account: DerivAccount,
+        token: &str,
+    ) -> anyhow::Result<SealedToken> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .key
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: token.as_bytes(),
+                    aad: &aad(username, account),
+                },
+            )
+            .map_err(|_| anyhow!("token encryption failed"))?;
+        Ok(SealedToken {
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+            updated_at: chrono::Local::now().to_rfc3339(),
+        })
+    }
+
+    /// Plaintext and whether it was sealed with the previous key
+    fn unseal(
+        &self,
+        username: &str,
+        account: DerivAccount,
+        sealed: &SealedToken,
+    ) -> anyhow::Result<(String, bool)> {
+        let nonce = BASE64.decode(&sealed.nonce)?;
+        let ciphertext = BASE64.decode(&sealed.ciphertext)?;
+        if nonce.len() != 12 {
+            bail!("bad nonce");
+        }
+        let aad = aad(username, account);
+        let decrypt = |key: &Aes256Gcm| {
+            key.decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &aad,
+                },
+            )
+            .ok()
+        };
+        let (plain, old_key) = match decrypt(&self.key) {
+            Some(plain) => (plain, false),
+            None => match self.previous.as_ref().and_then(decrypt) {
+                Some(plain) => (plain, true),
+                None => bail!(
+                    "cannot decrypt {} token of {} (wrong {}?)",
+                    account.as_str(),
+                    username,
+                    KEY_ENV
+                ),
+            },
+        };
+        let token = String::from_utf8(plain)?;
+        register_secret(&token);
+        Ok((token, old_key))
+    }
+
+    /// Store or replace (rotate) a token
+    pub fn set_token(
+        &self,
+        username: &str,
+        account: DerivAccount,
+        token: &str,
+    ) -> anyhow::Result<()> {
+        let token = token.trim();
+        if !is_valid_token(token) {
+            bail!("invalid token");
+        }
+        let sealed = self.seal(username, account, token)?;
+        self.update(|file| {
+            file.tokens
+                .entry(username.to_string())
+                .or_default()
+                .insert(account, sealed);
+            Ok(((), true))
+        })?;
+        register_secret(token);
+        Ok(())
+    }
+
+    /// Returns false when there was no such token
+    pub fn remove_token(&self, username: &str, account: DerivAccount) -> anyhow::Result<bool> {
+        self.update(|file| {
+            let Some(tokens) = file.tokens.get_mut(username) else {
+                return Ok((false, false));
+            };
+            let removed = tokens.remove(&account).is_some();
+            if tokens.is_empty() {
+                file.tokens.remove(username);
+            }
+            Ok((removed, removed))
+        })
+    }
+
+    pub fn token(&self, username: &str, account: DerivAccount) -> Option<String> {
+        let file = self
+            .load()
+            .map_err(|e| error!("❌ Secret store: {:#}", e))
+            .ok()?;
+        let sealed = file.tokens.get(username)?.get(&account)?;
+        match self.unseal(username, account, sealed) {
+            Ok((token, _)) => Some(token),
+            Err(e) => {
+                error!("❌ Secret store: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Token for trading: the requested account, otherwise demo before real
+    pub fn trading_token(
+        &self,
+        username: &str,
+        account: Option<DerivAccount>,
+    ) -> Option<(DerivAccount, String)> {
+        match account {
+            Some(account) => self.token(username, account).map(|t| (account, t)),
+            None => [DerivAccount::Demo, DerivAccount::Real]
+                .into_iter()
+                .find_map(|a| self.token(username, a).map(|t| (a, t))),
+        }
+    }
+
+    pub fn status(&self, username: &str) -> Vec<TokenStatus> {
+        let file = match self.load() {
+            Ok(file) => file,
+            Err(e) => {
+                error!("❌ Secret store: {:#}", e);
+                return Vec::new();
+            }
+        };
+        let Some(tokens) = file.tokens.get(username) else {
+            return Vec::new();
+        };
+        tokens
+            .iter()
+            .filter_map(|(account, sealed)| {
+                let (token, _) = self.unseal(username, *account, sealed).ok()?;
+                let tail: String = token
+                    .chars()
+                    .rev()
+                    .take(4)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .collect();
+                Some(TokenStatus {
+                    account: *account,
+                    hint: format!("…{}", tail),
+                    updated_at: sealed.updated_at.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Decrypt every entry (registering it for redaction) and re-encrypt those still under
+    /// the previous key. Returns the number of re-encrypted tokens.
+    pub fn rekey(&self) -> anyhow::Result<usize> {
+        self.update(|file| {
+            let mut rekeyed = 0;
+            for (username, tokens) in file.tokens.iter_mut() {
+                for (account, sealed) in tokens.iter_mut() {
+                    match self.unseal(username, *account, sealed) {
+                        Ok((token, true)) => {
+                            let updated_at = std::mem::take(&mut sealed.updated_at);
+                            *sealed = SealedToken {
+                                updated_at,
+                                ..self.seal(username, *account, &token)?
+                            };
+                            rekeyed += 1;
+                        }
+                        Ok(_) => {}
+                        Err(e) => error!("❌ Secret store: {}", e),
+                    }
+                }
+            }
+            Ok((rekeyed, rekeyed > 0))
+        })
+    }
+}
+
+// ==================== LOG REDACTION ====================
+
+/// JSON fields whose string values are always masked
+const SECRET_FIELDS: &[&str] = &["api_token", "authorize", "token", "password"];
+const MASK: &str = "***";
+
+fn known_secrets() -> &'static Mutex<HashSet<String>> {
+    static KNOWN: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    KNOWN.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Remember a secret so `redact` masks it wherever it appears
+pub fn register_secret(secret: &str) {
+    // Very short values would mask unrelated text
+    if secret.len() >= 8 {
+        if let Ok(mut known) = known_secrets().lock() {
+            known.insert(secret.to_string());
+        }
+    }
+}
+
+/// Mask the values of `"api_token"`, `"authorize"`, `"token"` and `"password"` string fields
+/// and any registered secret in `text`.
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    for field in SECRET_FIELDS {
+        let pattern = format!("\"{}\"", field);
+        let mut from = 0;
+        while let Some(pos) = out[from..].find(&pattern) {
+            let after_key = from + pos + pattern.len();
+            // Expect `: "value"` after the key
+            let rest = &out[after_key..];
+            let trimmed = rest.trim_start();
+            let Some(value) = trimmed.strip_prefix(':').map(str::trim_start) else {
+                from = after_key;
+                continue;
+            };
+            if !value.starts_with('"') {
+                from = after_key;
+                continue;
+            }
+            let value_start = out.len() - value.len() + 1;
+            let mut end = None;
+            let mut escaped = false;
+            for (i, c) in out[value_start..].char_indices() {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    '"' if !escaped => {
+                        end = Some(value_start + i);
+                        break;
+                    }
+                    _ => escaped = false,
+                }
+            }
+            let Some(end) = end else {
+                break;
+            };
+            out.replace_range(value_start..end, MASK);
+            from = value_start + MASK.len() + 1;
+        }
+    }
+    if let Ok(known) = known_secrets().lock() {
+        for secret in known.iter() {
+            if out.contains(secret.as_str()) {
+                out = out.replace(secret.as_str(), MASK);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> Aes256Gcm {
+        Aes256Gcm::new_from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn store(path: &std::path::Path, key: Aes256Gcm, previous: Option<Aes256Gcm>) -> SecretStore {
+        SecretStore {
+            path: path.to_path_buf(),
+            key,
+            previous,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("secrets_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("secrets.json")
+    }
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let path = temp_path("seal");
+        let secrets = store(&path, key(1), None);
+        secrets
+            .set_token("alice", DerivAccount::Demo, " a1b2c3d4e5f6g7 ")
+            .unwrap();
+        assert_eq!(
+            secrets.token("alice", DerivAccount::Demo).as_deref(),
+            Some("a1b2c3d4e5f6g7")
+        );
+        assert_eq!(secrets.token("alice", DerivAccount::Real), None);
+        assert!(!std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("a1b2c3d4e5f6g7"));
+        assert_eq!(secrets.status("alice")[0].hint, "…f6g7");
+
+        // The user and account are bound in: a copied entry does not decrypt
+        let sealed = secrets.load().unwrap().tokens["alice"][&DerivAccount::Demo].clone();
+        assert!(secrets.unseal("bob", DerivAccount::Demo, &sealed).is_err());
+        assert!(secrets
+            .unseal("alice", DerivAccount::Real, &sealed)
+            .is_err());
+        // ... and neither does one under another key
+        assert_eq!(
+            store(&path, key(2), None).token("alice", DerivAccount::Demo),
+            None
+        );
+
+        for bad in ["", "two words", &"x".repeat(MAX_TOKEN_LEN + 1)] {
+            assert!(secrets.set_token("alice", DerivAccount::Real, bad).is_err());
+        }
+        assert!(secrets.remove_token("alice", DerivAccount::Demo).unwrap());
+        assert!(!secrets.remove_token("alice", DerivAccount::Demo).unwrap());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rekey_with_previous_key() {
+        let path = temp_path("rekey");
+        store(&path, key(1), None)
+            .set_token("alice", DerivAccount::Real, "rotate-me-1234")
+            .unwrap();
+
+        let rotated = store(&path, key(2), Some(key(1)));
+        let (_, old_key) = rotated
+            .unseal(
+                "alice",
+                DerivAccount::Real,
+                &rotated.load().unwrap().tokens["alice"][&DerivAccount::Real],
+            )
+            .unwrap();
+        assert!(old_key);
+        assert_eq!(rotated.rekey().unwrap(), 1);
+        assert_eq!(rotated.rekey().unwrap(), 0);
+
+        // The previous key is no longer needed, and no longer enough
+        assert_eq!(
+            store(&path, key(2), None)
+                .token("alice", DerivAccount::Real)
+                .as_deref(),
+            Some("rotate-me-1234")
+        );
+        assert_eq!(
+            store(&path, key(1), None).token("alice", DerivAccount::Real),
+            None
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_file_refuses_changes_and_is_not_overwritten() {
+        let path = temp_path("corrupt");
+        let secrets = store(&path, key(1), Some(key(2)));
+        std::fs::write(&path, "{ torn").unwrap();
+
+        assert_eq!(secrets.token("alice", DerivAccount::Demo), None);
+        assert!(secrets.status("alice").is_empty());
+        assert!(secrets
+            .set_token("alice", DerivAccount::Demo, "new-token-1234")
+            .is_err());
+        assert!(secrets.remove_token("alice", DerivAccount::Demo).is_err());
+        assert!(secrets.rekey().is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ torn");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_set_token_keeps_every_update() {
+        let path = temp_path("concurrent");
+        let secrets = std::sync::Arc::new(store(&path, key(1), None));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let secrets = secrets.clone();
+                std::thread::spawn(move || {
+                    secrets
+                        .set_token(&format!("user{}", i), DerivAccount::Demo, "token-abcd-1234")
+                        .unwrap()
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(secrets.load().unwrap().tokens.len(), 8);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_redact_browser_command() {
+        // What the dashboard sends on /ws, as logged by "Browser sent:"
+        let sent = r#"{"command":"START_DERIV","asset":"R_10","app_id":"66726","api_token":"Zx9\"secret","account":"demo","password": "hunter22"}"#;
+        let redacted = redact(sent);
+        assert_eq!(
+            redacted,
+            r#"{"command":"START_DERIV","asset":"R_10","app_id":"66726","api_token":"***","account":"demo","password": "***"}"#
+        );
+        // Pretty-printed and non-string values
+        assert_eq!(
+            redact("{\n  \"token\": \"abc\",\n  \"authorize\": 1\n}"),
+            "{\n  \"token\": \"***\",\n  \"authorize\": 1\n}"
+        );
+
+        // A registered secret is masked wherever it shows up
+        register_secret("registered-token-42");
+        assert_eq!(
+            redact(r#"{"authorize_response":"registered-token-42 ok"}"#),
+            r#"{"authorize_response":"*** ok"}"#
+        );
+        // Short values are not registered
+        register_secret("R_10");
+        assert_eq!(redact("R_10"), "R_10");
+    }
+}
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tower_sessions::session::{Id, Record};
+use tower_sessions::session_store::{self, SessionStore};
+
+// SQLite-backed store for tower-sessions, so logins survive a relay restart.
+// Records are kept as JSON with their expiry as Unix seconds; expired rows are ignored on
+// load and removed by `delete_expired`.
+
+pub const SESSION_DB_FILE: &str = "data/sessions.db";
+
+#[derive(Debug, Clone)]
+pub struct SqliteSessionStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+fn backend<E: std::fmt::Display>(e: E) -> session_store::Error {
+    session_store::Error::Backend(e.to_string())
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+impl SqliteSessionStore {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS sessions (
+                 id TEXT PRIMARY KEY,
+                 data TEXT NOT NULL,
+                 expiry_date INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_sessions_expiry ON sessions(expiry_date);",
+        )?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run `f` on the blocking pool: rusqlite and the connection mutex must not stall the
+    /// runtime threads that serve requests.
+    async fn with_conn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> session_store::Result<T> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().map_err(backend)?;
+            f(&conn).map_err(backend)
+        })
+        .await
+        .map_err(backend)?
+    }
+
+    /// Remove expired sessions; returns how many were deleted
+    pub async fn delete_expired(&self) -> session_store::Result<usize> {
+        self.with_conn(|c| c.execute("DELETE FROM sessions WHERE expiry_date <= ?1", [now()]))
+            .await
+    }
+
+    fn encode(record: &Record) -> session_store::Result<String> {
+        serde_json::to_string(record).map_err(backend)
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqliteSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        // Ids are random; on the rare collision draw a new one
+        loop {
+            let data = Self::encode(record)?;
+            let (id, expiry) = (record.id.to_string(), record.expiry_date.unix_timestamp());
+            let inserted = self
+                .with_conn(move |c| {
+                    c.execute(
+                        "INSERT OR IGNORE INTO sessions (id, data, expiry_date) VALUES (?1, ?2, ?3)",
+                        params![id, data, expiry],
+                    )
+                })
+                .await?;
+            if inserted == 1 {
+                return Ok(());
+            }
+            record.id = Id::default();
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let data = Self::encode(record)?;
+        let (id, expiry) = (record.id.to_string(), record.expiry_date.unix_timestamp());
+        self.with_conn(move |c| {
+            c.execute(
+                "INSERT OR REPLACE INTO sessions (id, data, expiry_date) VALUES (?1, ?2, ?3)",
+                params![id, data, expiry],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let id = session_id.to_string();
+        let data: Option<String> = self
+            .with_conn(move |c| {
+                c.query_row(
+                    "SELECT data FROM sessions WHERE id = ?1 AND expiry_date > ?2",
+                    params![id, now()],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await?;
+        data.map(|d| serde_json::from_str(&d).map_err(backend))
+            .transpose()
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let id = session_id.to_string();
+        self.with_conn(move |c| c.execute("DELETE FROM sessions WHERE id = ?1", [id]))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+// Graceful shutdown on Ctrl-C or SIGTERM, and on Windows also when the console window is
+// closed or the system shuts down (Windows then allows only a few seconds before it ends the
+// process, so open contracts are usually left for the next start).
+//
+// When a signal arrives, `requested()` turns true and the bots stop placing trades. The HTTP
+// server stops accepting connections. `main` then sends `DRAIN` to the bots: each forgets
+// its tick and candle streams and exits once its open contracts have settled. Bots get at
+// most `SHUTDOWN_GRACE_SECS` (default 75) before they are aborted, and the scanner and
+// recorder are stopped. After that the Firestore outbox gets a last send and the log files
+// are flushed. A contract still open when the bots are aborted stays in the trade store and
+// is reported at the next start. Under systemd, keep `TimeoutStopSec` above the grace period.
+
+/// Bot command: forget market streams, wait for open contracts, then exit
+pub const DRAIN: &str = "DRAIN";
+
+const DEFAULT_GRACE_SECS: u64 = 75;
+/// Time allowed for the last Firestore outbox send
+pub const OUTBOX_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// True once a shutdown signal arrived; no new trades are placed after that
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// How long bots may wait for open contracts to settle
+pub fn grace_period() -> Duration {
+    let secs = std::env::var("SHUTDOWN_GRACE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_GRACE_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Resolve on Ctrl-C or SIGTERM (console close or system shutdown on Windows). Used as the
+/// server's graceful shutdown future.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("⚠️ Cannot listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+                "SIGTERM"
+            }
+            Err(e) => {
+                warn!("⚠️ Cannot listen for SIGTERM: {}", e);
+                std::future::pending().await
+            }
+        }
+    };
+    #[cfg(windows)]
+    let terminate = async {
+        use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+        match (ctrl_close(), ctrl_shutdown()) {
+            (Ok(mut close), Ok(mut shutdown)) => tokio::select! {
+                _ = close.recv() => "Console close",
+                _ = shutdown.recv() => "System shutdown",
+            },
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("⚠️ Cannot listen for console close or shutdown: {}", e);
+                std::future::pending().await
+            }
+        }
+    };
+    #[cfg(not(any(unix, windows)))]
+    let terminate = std::future::pending::<&str>();
+
+    let name = tokio::select! {
+        _ = ctrl_c => "Ctrl-C",
+        name = terminate => name,
+    };
+    REQUESTED.store(true, Ordering::SeqCst);
+    warn!("🛑 {} received: no new trades, shutting down", name);
+}
@@ -0,0 +1,830 @@
+// Server-Side Tick & Candle Recorder
+// Subscribes to ticks and candles for the configured assets and appends them to
+// per-asset, per-day JSON-lines files, independent of any browser:
+//
+//   tickhistory/recorder/<SYMBOL>/<YYYY-MM-DD>.ticks.jsonl      {"t":epoch,"q":quote}
+//   tickhistory/recorder/<SYMBOL>/<YYYY-MM-DD>.candles_<G>.jsonl {"t":open_time,"o","h","l","c"}
+//   tickhistory/recorder/<SYMBOL>/<YYYY-MM-DD>.gaps.jsonl        {"kind","from","to"}
+//
+// Days are UTC. A candle is written once the next one opens. Gaps between consecutive
+// ticks (longer than `tick_gap_seconds`) or candles (more than one granularity apart),
+// including across restarts (also over UTC midnight) and reconnects, are logged to the
+// gaps file. Files stay open
+// while their day is current and are flushed every `FLUSH_INTERVAL_SECS`.
+
+use crate::metrics::metrics;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info, info_span, warn, Instrument};
+
+pub const RECORDER_DIR: &str = "tickhistory/recorder";
+const DEFAULT_APP_ID: &str = "66726";
+const RECONNECT_DELAY_SECS: u64 = 5;
+const FLUSH_INTERVAL_SECS: u64 = 1;
+/// Largest range a single read request may cover
+const MAX_QUERY_DAYS: i64 = 31;
+
+/// Recorder configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecorderConfig {
+    /// Symbols to record (e.g. R_10, 1HZ100V)
+    pub assets: Vec<String>,
+    /// Candle granularity in seconds
+    #[serde(default = "default_granularity")]
+    pub granularity: u64,
+    /// Record every tick, not just closed candles
+    #[serde(default = "default_true")]
+    pub record_ticks: bool,
+    /// Silence between two ticks that counts as a gap
+    #[serde(default = "default_tick_gap")]
+    pub tick_gap_seconds: u64,
+}
+
+fn default_granularity() -> u64 {
+    60
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_tick_gap() -> u64 {
+    30
+}
+
+impl RecorderConfig {
+    /// `TICK_RECORDER_ASSETS=R_10,R_75` starts the recorder at boot;
+    /// `TICK_RECORDER_GRANULARITY` overrides the candle size.
+    pub fn from_env() -> Option<Self> {
+        let assets: Vec<String> = std::env::var("TICK_RECORDER_ASSETS")
+            .ok()?
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if assets.is_empty() {
+            return None;
+        }
+        Some(Self {
+            assets,
+            granularity: std::env::var("TICK_RECORDER_GRANULARITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_granularity),
+            record_ticks: true,
+            tick_gap_seconds: default_tick_gap(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RecordedTick {
+    pub t: u64,
+    pub q: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RecordedCandle {
+    pub t: u64,
+    pub o: f64,
+    pub h: f64,
+    pub l: f64,
+    pub c: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedGap {
+    /// "tick" or "candle"
+    pub kind: String,
+    /// Last epoch before the gap and first epoch after it
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Recorder status
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecorderStatus {
+    pub is_running: bool,
+    pub connected: bool,
+    pub config: Option<RecorderConfig>,
+    pub ticks_written: u64,
+    pub candles_written: u64,
+    pub gaps_detected: u64,
+    /// Last tick epoch per symbol
+    pub last_tick: HashMap<String, u64>,
+    pub errors: Vec<String>,
+}
+
+/// Main Recorder struct
+pub struct TickRecorder {
+    status: Arc<RwLock<RecorderStatus>>,
+    task_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
+}
+
+impl TickRecorder {
+    pub fn new() -> Self {
+        Self {
+            status: Arc::new(RwLock::new(RecorderStatus::default())),
+            task_handle: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub async fn get_status(&self) -> RecorderStatus {
+        self.status.read().await.clone()
+    }
+
+    pub async fn start(&self, config: RecorderConfig) -> Result<(), String> {
+        if config.assets.is_empty() {
+            return Err("No assets to record".to_string());
+        }
+        if let Some(bad) = config.assets.iter().find(|a| !is_valid_symbol(a)) {
+            return Err(format!("Invalid asset: {:?}", bad));
+        }
+        if config.granularity == 0 {
+            return Err("Granularity must be positive".to_string());
+        }
+        {
+            let mut status = self.status.write().await;
+            if status.is_running {
+                return Err("Recorder is already running".to_string());
+            }
+            *status = RecorderStatus {
+                is_running: true,
+                config: Some(config.clone()),
+                ..Default::default()
+            };
+        }
+
+        let status_handle = self.status.clone();
+        let config_clone = config.clone();
+        let handle = tokio::spawn(
+            async move {
+                run_recorder_loop(status_handle, config_clone).await;
+            }
+            .instrument(info_span!(parent: None, "recorder")),
+        );
+        *self.task_handle.write().await = Some(handle);
+
+        info!(
+            "🎙️ Tick Recorder started: {} ({}s candles)",
+            config.assets.join(","),
+            config.granularity
+        );
+        Ok(())
+    }
+
+    pub async fn stop(&self) -> Result<(), String> {
+        if !self.status.read().await.is_running {
+            return Err("Recorder is not running".to_string());
+        }
+        if let Some(handle) = self.task_handle.write().await.take() {
+            handle.abort();
+        }
+        {
+            let mut status = self.status.write().await;
+            status.is_running = false;
+            status.connected = false;
+        }
+        info!("⏹️ Tick Recorder stopped");
+        Ok(())
+    }
+}
+
+/// Symbols name directories, so only `[A-Za-z0-9_]` is accepted
+fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn asset_dir(dir: &Path, symbol: &str) -> PathBuf {
+    dir.join(symbol)
+}
+
+fn day_of(epoch: u64) -> String {
+    DateTime::<Utc>::from_timestamp(epoch as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+fn ticks_file(dir: &Path, symbol: &str, day: &str) -> PathBuf {
+    asset_dir(dir, symbol).join(format!("{}.ticks.jsonl", day))
+}
+
+fn candles_file(dir: &Path, symbol: &str, day: &str, granularity: u64) -> PathBuf {
+    asset_dir(dir, symbol).join(format!("{}.candles_{}.jsonl", day, granularity))
+}
+
+fn gaps_file(dir: &Path, symbol: &str, day: &str) -> PathBuf {
+    asset_dir(dir, symbol).join(format!("{}.gaps.jsonl", day))
+}
+
+/// Buffered append handles, one per asset and file kind. Moving to the next day's file
+/// closes the previous one.
+#[derive(Default)]
+struct OpenFiles {
+    files: HashMap<(String, &'static str), (PathBuf, BufWriter<File>)>,
+}
+
+impl OpenFiles {
+    fn append<T: Serialize>(
+        &mut self,
+        symbol: &str,
+        kind: &'static str,
+        path: PathBuf,
+        value: &T,
+    ) -> Result<(), String> {
+        let key = (symbol.to_string(), kind);
+        if self.files.get(&key).is_none_or(|(open, _)| *open != path) {
+            if let Some((old, mut file)) = self.files.remove(&key) {
+                file.flush().map_err(|e| format!("{:?}: {}", old, e))?;
+            }
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| format!("{:?}: {}", dir, e))?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| format!("{:?}: {}", path, e))?;
+            self.files.insert(key.clone(), (path, BufWriter::new(file)));
+        }
+        let (path, file) = self.files.get_mut(&key).expect("opened above");
+        let line = serde_json::to_string(value).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    /// Flushes every open file; returns the failures
+    fn flush(&mut self) -> Vec<String> {
+        self.files
+            .values_mut()
+            .filter_map(|(path, file)| file.flush().err().map(|e| format!("{:?}: {}", path, e)))
+            .collect()
+    }
+}
+
+/// Last complete record of a JSON-lines file (a torn last line is skipped)
+fn last_record<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str(line).ok())
+}
+
+fn num(v: &serde_json::Value) -> Option<f64> {
+    v.as_f64().or_else(|| v.as_str()?.parse().ok())
+}
+
+/// Per-asset recording state
+#[derive(Default)]
+struct AssetState {
+    last_tick: Option<u64>,
+    last_candle: Option<u64>,
+    open_candle: Option<RecordedCandle>,
+}
+
+impl AssetState {
+    /// Seed from the files of the day of `now` so a restart is checked for gaps too. Falls
+    /// back to the previous day when nothing was recorded yet today (restart after midnight).
+    fn load(dir: &Path, symbol: &str, granularity: u64, now: u64) -> Self {
+        let days = [day_of(now), day_of(now.saturating_sub(86_400))];
+        Self {
+            last_tick: days
+                .iter()
+                .find_map(|day| last_record::<RecordedTick>(&ticks_file(dir, symbol, day)))
+                .map(|t| t.t),
+            last_candle: days
+                .iter()
+                .find_map(|day| {
+                    last_record::<RecordedCandle>(&candles_file(dir, symbol, day, granularity))
+                })
+                .map(|c| c.t),
+            open_candle: None,
+        }
+    }
+}
+
+struct Recorder {
+    dir: PathBuf,
+    config: RecorderConfig,
+    status: Arc<RwLock<RecorderStatus>>,
+    assets: HashMap<String, AssetState>,
+    files: OpenFiles,
+}
+
+impl Recorder {
+    async fn error(&self, message: String) {
+        error!("❌ Recorder: {}", message);
+        let mut s = self.status.write().await;
+        s.errors
+            .push(format!("{}: {}", Utc::now().format("%H:%M:%S"), message));
+        if s.errors.len() > 10 {
+            s.errors.remove(0);
+        }
+    }
+
+    async fn gap(&mut self, symbol: &str, kind: &str, from: u64, to: u64) {
+        warn!(
+            "⚠️ Recorder: {} gap on {} ({} -> {})",
+            kind, symbol, from, to
+        );
+        let gap = RecordedGap {
+            kind: kind.to_string(),
+            from,
+            to,
+        };
+        match self.files.append(
+            symbol,
+            "gaps",
+            gaps_file(&self.dir, symbol, &day_of(to)),
+            &gap,
+        ) {
+            Ok(()) => self.status.write().await.gaps_detected += 1,
+            Err(e) => self.error(e).await,
+        }
+    }
+
+    async fn on_tick(&mut self, tick: &serde_json::Value) {
+        let (Some(symbol), Some(epoch), Some(quote)) = (
+            tick.get("symbol").and_then(|s| s.as_str()),
+            tick.get("epoch").and_then(|e| e.as_u64()),
+            tick.get("quote").and_then(num),
+        ) else {
+            return;
+        };
+        let Some(state) = self.assets.get_mut(symbol) else {
+            return;
+        };
+        let previous = state.last_tick.replace(epoch);
+        if previous.is_some_and(|p| epoch <= p) {
+            state.last_tick = previous;
+            return;
+        }
+
+        if let Some(p) = previous {
+            if epoch - p > self.config.tick_gap_seconds {
+                self.gap(symbol, "tick", p, epoch).await;
+            }
+        }
+        let record = RecordedTick { t: epoch, q: quote };
+        let path = ticks_file(&self.dir, symbol, &day_of(epoch));
+        match self.files.append(symbol, "ticks", path, &record) {
+            Ok(()) => {
+                let mut s = self.status.write().await;
+                s.ticks_written += 1;
+                s.last_tick.insert(symbol.to_string(), epoch);
+            }
+            Err(e) => self.error(e).await,
+        }
+    }
+
+    async fn on_ohlc(&mut self, ohlc: &serde_json::Value) {
+        let Some(symbol) = ohlc.get("symbol").and_then(|s| s.as_str()) else {
+            return;
+        };
+        let field = |k: &str| ohlc.get(k).and_then(num);
+        let (Some(t), Some(o), Some(h), Some(l), Some(c)) = (
+            ohlc.get("open_time").and_then(|v| v.as_u64()),
+            field("open"),
+            field("high"),
+            field("low"),
+            field("close"),
+        ) else {
+            return;
+        };
+        let granularity = self.config.granularity;
+        let Some(state) = self.assets.get_mut(symbol) else {
+            return;
+        };
+        let candle = RecordedCandle { t, o, h, l, c };
+        let closed = match state.open_candle {
+            Some(open) if open.t != t => Some(open),
+            _ => None,
+        };
+        state.open_candle = Some(candle);
+        let Some(closed) = closed else {
+            return;
+        };
+        if state.last_candle.is_some_and(|last| closed.t <= last) {
+            return;
+        }
+        let previous = state.last_candle.replace(closed.t);
+
+        if let Some(p) = previous {
+            if closed.t - p > granularity {
+                self.gap(symbol, "candle", p, closed.t).await;
+            }
+        }
+        match self.files.append(
+            symbol,
+            "candles",
+            candles_file(&self.dir, symbol, &day_of(closed.t), granularity),
+            &closed,
+        ) {
+            Ok(()) => self.status.write().await.candles_written += 1,
+            Err(e) => self.error(e).await,
+        }
+    }
+
+    async fn flush(&mut self) {
+        for e in self.files.flush() {
+            self.error(e).await;
+        }
+    }
+
+    /// One connection; returns when it drops
+    async fn run_connection(&mut self) -> Result<(), String> {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::connect_async;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let app_id = std::env::var("DERIV_APP_ID").unwrap_or_else(|_| DEFAULT_APP_ID.to_string());
+        let url = format!("wss://ws.derivws.com/websockets/v3?app_id={}", app_id);
+        let (ws_stream, _) = connect_async(&url).await.map_err(|e| {
+            metrics().connect_failed("recorder");
+            format!("WebSocket connection failed: {}", e)
+        })?;
+        let _connected = metrics().connection("recorder");
+        let (mut write, mut read) = ws_stream.split();
+
+        for symbol in &self.config.assets {
+            let mut requests = vec![serde_json::json!({
+                "ticks_history": symbol,
+                "style": "candles",
+                "granularity": self.config.granularity,
+                "count": 1,
+                "end": "latest",
+                "subscribe": 1
+            })];
+            if self.config.record_ticks {
+                requests.push(serde_json::json!({ "ticks": symbol, "subscribe": 1 }));
+            }
+            for req in requests {
+                write
+                    .send(Message::Text(req.to_string()))
+                    .await
+                    .map_err(|e| format!("Failed to subscribe {}: {}", symbol, e))?;
+            }
+        }
+        self.status.write().await.connected = true;
+        info!(
+            "🎙️ Recorder subscribed to {} assets",
+            self.config.assets.len()
+        );
+
+        // Deriv drops idle connections; ping keeps it open
+        let mut ping = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        let mut flush =
+            tokio::time::interval(tokio::time::Duration::from_secs(FLUSH_INTERVAL_SECS));
+        loop {
+            tokio::select! {
+                _ = flush.tick() => self.flush().await,
+                _ = ping.tick() => {
+                    let ping_msg = serde_json::json!({ "ping": 1 }).to_string();
+                    if write.send(Message::Text(ping_msg)).await.is_err() {
+                        return Err("Ping failed".to_string());
+                    }
+                }
+                msg = read.next() => {
+                    let text = match msg {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Err("Connection closed".to_string()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(format!("WebSocket error: {}", e)),
+                    };
+                    let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) else {
+                        continue;
+                    };
+                    metrics().message("recorder", &json);
+                    if let Some(err) = json.get("error") {
+                        self.error(format!("Deriv error: {}", err)).await;
+                    } else if let Some(tick) = json.get("tick") {
+                        self.on_tick(tick).await;
+                    } else if let Some(ohlc) = json.get("ohlc") {
+                        self.on_ohlc(ohlc).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Main recording loop: reconnects until stopped
+async fn run_recorder_loop(status: Arc<RwLock<RecorderStatus>>, config: RecorderConfig) {
+    let dir = PathBuf::from(RECORDER_DIR);
+    let now = Utc::now().timestamp().max(0) as u64;
+    let assets = config
+        .assets
+        .iter()
+        .map(|s| {
+            (
+                s.clone(),
+                AssetState::load(&dir, s, config.granularity, now),
+            )
+        })
+        .collect();
+    let mut recorder = Recorder {
+        dir,
+        config,
+        status,
+        assets,
+        files: OpenFiles::default(),
+    };
+
+    loop {
+        if let Err(e) = recorder.run_connection().await {
+            recorder.error(e).await;
+        }
+        recorder.flush().await;
+        recorder.status.write().await.connected = false;
+        // The candle still open when the connection dropped never closes on this socket
+        for state in recorder.assets.values_mut() {
+            state.open_candle = None;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+        metrics().reconnect("recorder");
+    }
+}
+
+// ==================== READ API ====================
+
+/// Query string of the `/api/recorder/*` read endpoints. Times are Unix seconds;
+/// `to` defaults to now and `from` to 24 hours before `to`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordingQuery {
+    pub symbol: String,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// Candle granularity; defaults to 60
+    pub granularity: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl RecordingQuery {
+    fn range(&self) -> Result<(u64, u64), String> {
+        let to = self
+            .to
+            .unwrap_or_else(|| Utc::now().timestamp().max(0) as u64);
+        let from = self.from.unwrap_or_else(|| to.saturating_sub(86_400));
+        if from > to {
+            return Err("from is after to".to_string());
+        }
+        if (to - from) as i64 > MAX_QUERY_DAYS * 86_400 {
+            return Err(format!("range is limited to {} days", MAX_QUERY_DAYS));
+        }
+        if !is_valid_symbol(&self.symbol) {
+            return Err("invalid symbol".to_string());
+        }
+        Ok((from, to))
+    }
+
+    fn days(from: u64, to: u64) -> Vec<String> {
+        let parse = |e: u64| NaiveDate::parse_from_str(&day_of(e), "%Y-%m-%d").ok();
+        let (Some(mut day), Some(last)) = (parse(from), parse(to)) else {
+            return Vec::new();
+        };
+        let mut days = Vec::new();
+        while day <= last {
+            days.push(day.format("%Y-%m-%d").to_string());
+            day = day.succ_opt().unwrap_or(last + chrono::Days::new(1));
+        }
+        days
+    }
+
+    fn read<T: for<'de> Deserialize<'de>>(
+        &self,
+        file: impl Fn(&str) -> PathBuf,
+        time: impl Fn(&T) -> u64,
+    ) -> Result<Vec<T>, String> {
+        let (from, to) = self.range()?;
+        let limit = self.limit.unwrap_or(100_000);
+        let mut out = Vec::new();
+        for day in Self::days(from, to) {
+            let Ok(content) = fs::read_to_string(file(&day)) else {
+                continue;
+            };
+            for record in content
+                .lines()
+                .filter_map(|l| serde_json::from_str::<T>(l).ok())
+            {
+                let t = time(&record);
+                if t >= from && t <= to {
+                    out.push(record);
+                    if out.len() >= limit {
+                        return Ok(out);
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn ticks(&self) -> Result<Vec<RecordedTick>, String> {
+        self.ticks_in(Path::new(RECORDER_DIR))
+    }
+
+    pub fn candles(&self) -> Result<Vec<RecordedCandle>, String> {
+        self.candles_in(Path::new(RECORDER_DIR))
+    }
+
+    pub fn gaps(&self) -> Result<Vec<RecordedGap>, String> {
+        self.gaps_in(Path::new(RECORDER_DIR))
+    }
+
+    fn ticks_in(&self, dir: &Path) -> Result<Vec<RecordedTick>, String> {
+        self.read(
+            |day| ticks_file(dir, &self.symbol, day),
+            |t: &RecordedTick| t.t,
+        )
+    }
+
+    fn candles_in(&self, dir: &Path) -> Result<Vec<RecordedCandle>, String> {
+        let granularity = self.granularity.unwrap_or_else(default_granularity);
+        self.read(
+            |day| candles_file(dir, &self.symbol, day, granularity),
+            |c: &RecordedCandle| c.t,
+        )
+    }
+
+    fn gaps_in(&self, dir: &Path) -> Result<Vec<RecordedGap>, String> {
+        self.read(
+            |day| gaps_file(dir, &self.symbol, day),
+            |g: &RecordedGap| g.to,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_rejects_unsafe_asset_names() {
+        let recorder = TickRecorder::new();
+        for bad in ["../etc", "R_10/x", "", "R 10"] {
+            let config = RecorderConfig {
+                assets: vec!["R_10".to_string(), bad.to_string()],
+                granularity: 60,
+                record_ticks: true,
+                tick_gap_seconds: 30,
+            };
+            assert!(recorder.start(config).await.is_err(), "{:?}", bad);
+        }
+        assert!(!recorder.get_status().await.is_running);
+    }
+
+    #[test]
+    fn test_open_files_keep_one_handle_per_kind_and_day() {
+        let dir = std::env::temp_dir().join(format!("tick_recorder_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let day1 = dir.join("R_10/2026-10-11.ticks.jsonl");
+        let day2 = dir.join("R_10/2026-10-12.ticks.jsonl");
+        let mut files = OpenFiles::default();
+        for t in [1, 2] {
+            let tick = RecordedTick { t, q: 1.5 };
+            files.append("R_10", "ticks", day1.clone(), &tick).unwrap();
+        }
+        assert!(files.flush().is_empty());
+        assert_eq!(fs::read_to_string(&day1).unwrap().lines().count(), 2);
+
+        // The next day's file replaces the handle and flushes the old one
+        let tick = RecordedTick { t: 3, q: 1.5 };
+        files.append("R_10", "ticks", day1.clone(), &tick).unwrap();
+        files.append("R_10", "ticks", day2.clone(), &tick).unwrap();
+        assert_eq!(files.files.len(), 1);
+        assert_eq!(fs::read_to_string(&day1).unwrap().lines().count(), 3);
+        drop(files);
+        assert_eq!(last_record::<RecordedTick>(&day2).map(|t| t.t), Some(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn new_recorder(dir: &Path, now: u64) -> Recorder {
+        let config = RecorderConfig {
+            assets: vec!["R_10".to_string()],
+            granularity: 60,
+            record_ticks: true,
+            tick_gap_seconds: 30,
+        };
+        Recorder {
+            dir: dir.to_path_buf(),
+            assets: HashMap::from([(
+                "R_10".to_string(),
+                AssetState::load(dir, "R_10", config.granularity, now),
+            )]),
+            config,
+            status: Arc::new(RwLock::new(RecorderStatus::default())),
+            files: OpenFiles::default(),
+        }
+    }
+
+    fn query(from: u64, to: u64, limit: Option<usize>) -> RecordingQuery {
+        RecordingQuery {
+            symbol: "R_10".to_string(),
+            from: Some(from),
+            to: Some(to),
+            granularity: None,
+            limit,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorder_writes_gaps_and_resumes_after_midnight() {
+        let dir = std::env::temp_dir().join(format!("tick_recorder_gaps_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut recorder = new_recorder(&dir, 1_000);
+        for epoch in [1_000, 1_010, 1_005, 1_100] {
+            let tick = serde_json::json!({ "symbol": "R_10", "epoch": epoch, "quote": "1.5" });
+            recorder.on_tick(&tick).await;
+        }
+        // Ticks of other symbols are ignored
+        recorder
+            .on_tick(&serde_json::json!({ "symbol": "R_25", "epoch": 1_020, "quote": 2.0 }))
+            .await;
+        for (open_time, close) in [
+            (960, 1.0),
+            (960, 1.1),
+            (1_020, 1.2),
+            (1_200, 1.3),
+            (1_260, 1.4),
+        ] {
+            let ohlc = serde_json::json!({
+                "symbol": "R_10", "open_time": open_time, "open": 1.0,
+                "high": "1.5", "low": 0.9, "close": close
+            });
+            recorder.on_ohlc(&ohlc).await;
+        }
+        recorder.flush().await;
+
+        let gaps = fs::read_to_string(gaps_file(&dir, "R_10", "1970-01-01")).unwrap();
+        let gaps: Vec<serde_json::Value> = gaps
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            gaps,
+            vec![
+                serde_json::json!({ "kind": "tick", "from": 1_010, "to": 1_100 }),
+                serde_json::json!({ "kind": "candle", "from": 1_020, "to": 1_200 }),
+            ]
+        );
+        let status = recorder.status.read().await;
+        assert_eq!(
+            (
+                status.ticks_written,
+                status.candles_written,
+                status.gaps_detected
+            ),
+            (3, 3, 2)
+        );
+        drop(status);
+        drop(recorder);
+
+        // Restarted the next UTC day before anything was recorded: seeded from yesterday
+        let next_day = 86_400 + 100;
+        let mut recorder = new_recorder(&dir, next_day);
+        let state = &recorder.assets["R_10"];
+        assert_eq!(
+            (state.last_tick, state.last_candle),
+            (Some(1_100), Some(1_200))
+        );
+        recorder
+            .on_tick(&serde_json::json!({ "symbol": "R_10", "epoch": next_day, "quote": 1.6 }))
+            .await;
+        recorder.flush().await;
+        let gap: RecordedGap = last_record(&gaps_file(&dir, "R_10", "1970-01-02")).unwrap();
+        assert_eq!(
+            (gap.kind.as_str(), gap.from, gap.to),
+            ("tick", 1_100, next_day)
+        );
+
+        // Two days later there is nothing to seed from
+        let state = AssetState::load(&dir, "R_10", 60, 3 * 86_400 + 100);
+        assert_eq!((state.last_tick, state.last_candle), (None, None));
+
+        // Range reads span days, clip to the range and honour the limit
+        let ticks = query(1_005, next_day, None).ticks_in(&dir).unwrap();
+        assert_eq!(
+            ticks.iter().map(|t| t.t).collect::<Vec<_>>(),
+            vec![1_010, 1_100, next_day]
+        );
+        assert_eq!(query(0, next_day, Some(2)).ticks_in(&dir).unwrap().len(), 2);
+        let candles = query(0, 1_200, None).candles_in(&dir).unwrap();
+        assert_eq!(
+            candles.iter().map(|c| (c.t, c.h, c.c)).collect::<Vec<_>>(),
+            vec![(960, 1.5, 1.1), (1_020, 1.5, 1.2), (1_200, 1.5, 1.3)]
+        );
+        assert_eq!(query(0, next_day, None).gaps_in(&dir).unwrap().len(), 3);
+        assert!(query(1_100, 1_000, None).ticks_in(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+use crate::firestore_manager::TradeRecord;
+use crate::trade_store::{TradeFilter, TradeStore};
+use crate::{DayTradeEntry, DayTradeWrapper};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt32Array};
+use arrow_schema::{Field, Schema};
+use chrono::NaiveDate;
+use parquet::arrow::ArrowWriter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+// Flat trade history export for `GET /api/trade_history` and the analytics report. Rows
+// come from the trade store; `tradeHistory/<date>/trade.json` day files add the trades the
+// store does not have (days recorded before it existed) and fill in status codes and the
+// result text. The day files are only read, never repaired, on this path.
+
+const TRADE_HISTORY_DIR: &str = "tradeHistory";
+
+/// Query string of `GET /api/trade_history`. Dates are YYYY-MM-DD and inclusive;
+/// missing bounds are open.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TradeHistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub asset: Option<String>,
+    /// "json" (default), "csv" or "parquet"
+    pub format: Option<String>,
+}
+
+impl TradeHistoryQuery {
+    /// Parses `from` / `to` and rewrites them as zero-padded YYYY-MM-DD, so the string
+    /// comparisons below see canonical dates.
+    pub fn parse_dates(&mut self) -> Result<(Option<NaiveDate>, Option<NaiveDate>), String> {
+        fn parse(name: &str, value: &mut Option<String>) -> Result<Option<NaiveDate>, String> {
+            let Some(raw) = value.as_deref() else {
+                return Ok(None);
+            };
+            let date = NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("'{}' must be a date like 2026-10-18", name))?;
+            *value = Some(date.format("%Y-%m-%d").to_string());
+            Ok(Some(date))
+        }
+        let from = parse("from", &mut self.from)?;
+        let to = parse("to", &mut self.to)?;
+        Ok((from, to))
+    }
+}
+
+/// One `DayTradeEntry` plus its day and lot. Column names follow `trade.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeHistoryRow {
+    #[serde(rename = "TradeDate")]
+    pub trade_date: String,
+    /// 0 when the trade is not in the store
+    #[serde(rename = "LotNo")]
+    pub lot_no: u32,
+    #[serde(rename = "No")]
+    pub no: u32,
+    #[serde(rename = "ContractID")]
+    pub contract_id: String,
+    #[serde(rename = "Symbol")]
+    pub symbol: String,
+    #[serde(rename = "StatusCode")]
+    pub status_code: String,
+    #[serde(rename = "Type")]
+    pub trade_type: String,
+    #[serde(rename = "BuyPrice")]
+    pub buy_price: f64,
+    /// Index into the martingale stake ladder; empty in fixed-stake mode and for day-file rows
+    #[serde(rename = "MartingaleStep")]
+    pub martingale_step: Option<u32>,
+    #[serde(rename = "Payout")]
+    pub payout: f64,
+    #[serde(rename = "BuyTime")]
+    pub buy_time: String,
+    #[serde(rename = "Expiry")]
+    pub expiry: String,
+    #[serde(rename = "Remaining")]
+    pub remaining: String,
+    #[serde(rename = "MinProfit")]
+    pub min_profit: f64,
+    #[serde(rename = "MaxProfit")]
+    pub max_profit: f64,
+    #[serde(rename = "Profit")]
+    pub profit: f64,
+    #[serde(rename = "Action")]
+    pub action: String,
+}
+
+fn in_range(date: &str, query: &TradeHistoryQuery) -> bool {
+    query.from.as_deref().is_none_or(|from| date >= from)
+        && query.to.as_deref().is_none_or(|to| date <= to)
+}
+
+/// Day folders in range, oldest first
+fn day_folders(dir: &Path, query: &TradeHistoryQuery) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut days: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(str::to_string))
+        .filter(|name| NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok())
+        .filter(|name| in_range(name, query))
+        .collect();
+    days.sort();
+    days
+}
+
+fn result_text(status: &str) -> String {
+    match status {
+        "win" => "WIN ✅".to_string(),
+        "loss" => "LOSS ❌".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+fn from_record(t: TradeRecord) -> TradeHistoryRow {
+    let remaining = t.time_remaining.max(0);
+    TradeHistoryRow {
+        trade_date: t.trade_date,
+        lot_no: t.lot_no,
+        no: t.trade_no_in_lot,
+        contract_id: t.contract_id,
+        symbol: t.symbol,
+        status_code: t.status_code,
+        trade_type: t.trade_type,
+        buy_price: t.buy_price,
+        martingale_step: t.martingale_step,
+        payout: t.payout,
+        buy_time: t.buy_time.to_string(),
+        expiry: t.expiry_time.to_string(),
+        remaining: format!("{:02}:{:02}", remaining / 60, remaining % 60),
+        min_profit: t.min_profit,
+        max_profit: t.max_profit,
+        profit: t.profit_loss,
+        action: result_text(&t.status),
+    }
+}
+
+fn from_day_entry(day: &str, entry: DayTradeEntry) -> TradeHistoryRow {
+    TradeHistoryRow {
+        trade_date: day.to_string(),
+        lot_no: 0,
+        no: entry.no,
+        contract_id: entry.contract_id,
+        symbol: entry.symbol,
+        status_code: entry.status_code,
+        trade_type: entry.trade_type,
+        buy_price: entry.buy_price,
+        martingale_step: None,
+        payout: entry.payout,
+        buy_time: entry.buy_time,
+        expiry: entry.expiry,
+        remaining: entry.remaining,
+        min_profit: entry.min_profit,
+        max_profit: entry.max_profit,
+        profit: entry.profit,
+        action: entry.action,
+    }
+}
+
+/// Trades selected by `query`, oldest first: the store's rows merged with the day files
+pub fn collect_rows(query: &TradeHistoryQuery, store: &dyn TradeStore) -> Vec<TradeHistoryRow> {
+    collect_rows_in(Path::new(TRADE_HISTORY_DIR), query, store)
+}
+
+fn collect_rows_in(
+    dir: &Path,
+    query: &TradeHistoryQuery,
+    store: &dyn TradeStore,
+) -> Vec<TradeHistoryRow> {
+    let filter = TradeFilter {
+        from: query.from.clone(),
+        to: query.to.clone(),
+        symbol: query.asset.clone(),
+        ..Default::default()
+    };
+    let mut rows: HashMap<(String, String), TradeHistoryRow> = match store.query_trades(&filter) {
+        Ok(trades) => trades
+            .into_iter()
+            .map(|t| {
+                (
+                    (t.trade_date.clone(), t.contract_id.clone()),
+                    from_record(t),
+                )
+            })
+            .collect(),
+        Err(e) => {
+            warn!("⚠️ Trade store query failed, using day files only: {}", e);
+            HashMap::new()
+        }
+    };
+
+    for day in day_folders(dir, query) {
+        let path = dir.join(&day).join("trade.json");
+        let Some(wrapper) =
+            crate::file_store::read_fallback(&path, |s| serde_json::from_str::<DayTradeWrapper>(s))
+        else {
+            continue;
+        };
+        for entry in wrapper.day_trade.day_trade_list {
+            if query.asset.as_ref().is_some_and(|a| *a != entry.symbol) {
+                continue;
+            }
+            match rows.get_mut(&(day.clone(), entry.contract_id.clone())) {
+                Some(row) => {
+                    if row.status_code.is_empty() {
+                        row.status_code = entry.status_code;
+                    }
+                    row.action = entry.action;
+                }
+                None => {
+                    let row = from_day_entry(&day, entry);
+                    rows.insert((day.clone(), row.contract_id.clone()), row);
+                }
+            }
+        }
+    }
+
+    let mut rows: Vec<TradeHistoryRow> = rows.into_values().collect();
+    rows.sort_by_cached_key(|r| {
+        (
+            r.trade_date.clone(),
+            r.buy_time.parse::<u64>().unwrap_or(0),
+            r.lot_no,
+            r.no,
+            r.contract_id.clone(),
+        )
+    });
+    rows
+}
+
+pub fn to_csv(rows: &[TradeHistoryRow]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+pub fn to_parquet(rows: &[TradeHistoryRow]) -> anyhow::Result<Vec<u8>> {
+    fn text(rows: &[TradeHistoryRow], f: fn(&TradeHistoryRow) -> &str) -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(rows.iter().map(f)))
+    }
+    fn float(rows: &[TradeHistoryRow], f: fn(&TradeHistoryRow) -> f64) -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(f)))
+    }
+    fn int(rows: &[TradeHistoryRow], f: fn(&TradeHistoryRow) -> u32) -> ArrayRef {
+        Arc::new(UInt32Array::from_iter_values(rows.iter().map(f)))
+    }
+    fn opt_int(rows: &[TradeHistoryRow], f: fn(&TradeHistoryRow) -> Option<u32>) -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<UInt32Array>())
+    }
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("TradeDate", text(rows, |r| &r.trade_date)),
+        ("LotNo", int(rows, |r| r.lot_no)),
+        ("No", int(rows, |r| r.no)),
+        ("ContractID", text(rows, |r| &r.contract_id)),
+        ("Symbol", text(rows, |r| &r.symbol)),
+        ("StatusCode", text(rows, |r| &r.status_code)),
+        ("Type", text(rows, |r| &r.trade_type)),
+        ("BuyPrice", float(rows, |r| r.buy_price)),
+        ("MartingaleStep", opt_int(rows, |r| r.martingale_step)),
+        ("Payout", float(rows, |r| r.payout)),
+        ("BuyTime", text(rows, |r| &r.buy_time)),
+        ("Expiry", text(rows, |r| &r.expiry)),
+        ("Remaining", text(rows, |r| &r.remaining)),
+        ("MinProfit", float(rows, |r| r.min_profit)),
+        ("MaxProfit", float(rows, |r| r.max_profit)),
+        ("Profit", float(rows, |r| r.profit)),
+        ("Action", text(rows, |r| &r.action)),
+    ];
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|(name, array)| {
+                Field::new(*name, array.data_type().clone(), *name == "MartingaleStep")
+            })
+            .collect::<Vec<_>>(),
+    ));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        columns.into_iter().map(|(_, a)| a).collect(),
+    )?;
+
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trade_store::SqliteTradeStore;
+
+    fn record(date: &str, contract_id: &str, buy_time: u64, status: &str) -> TradeRecord {
+        TradeRecord {
+            trade_date: date.to_string(),
+            contract_id: contract_id.to_string(),
+            symbol: "R_10".to_string(),
+            trade_type: "CALL".to_string(),
+            status: status.to_string(),
+            buy_price: 1.0,
+            payout: 1.95,
+            profit_loss: if status == "win" { 0.95 } else { -1.0 },
+            buy_time,
+            lot_no: 3,
+            trade_no_in_lot: 1,
+            ..Default::default()
+        }
+    }
+
+    fn day_file(contract_ids: &[(&str, &str)]) -> String {
+        let entries: Vec<serde_json::Value> = contract_ids
+            .iter()
+            .enumerate()
+            .map(|(i, (id, code))| {
+                serde_json::json!({
+                    "No": i + 1, "ContractID": id, "Symbol": "R_10", "StatusCode": code,
+                    "Type": "PUT", "BuyPrice": 2.0, "Payout": 3.9, "BuyTime": "50",
+                    "Expiry": "110", "Remaining": "00:00", "MinProfit": -2.0,
+                    "MaxProfit": 1.9, "Profit": 1.9, "Action": "WIN ✅"
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "DayTrade": {
+                "LotNoCurrent": 1, "DayTrade": "2026-10-11", "StartTradeOfDay": "",
+                "LastTradeOfDay": "", "TotalTradeOnThisDay": 2, "TotalProfit": 0.0,
+                "StatusofTrade": "", "CurrentProfit": 0.0, "DayTradeList": entries
+            }
+        })
+        .to_string()
+    }
+
+    fn sample_rows() -> Vec<TradeHistoryRow> {
+        let mut rows = vec![from_record(record("2026-10-11", "c1", 100, "win"))];
+        rows[0].status_code = "12".to_string();
+        rows.push(from_record(record("2026-10-12", "c2", 200, "loss")));
+        rows[1].martingale_step = Some(1);
+        rows
+    }
+
+    #[test]
+    fn test_collect_rows_merges_store_and_day_files() {
+        let dir = std::env::temp_dir().join(format!("trade_export_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("2026-10-11")).unwrap();
+        std::fs::write(
+            dir.join("2026-10-11/trade.json"),
+            day_file(&[("c1", "12"), ("old", "7")]),
+        )
+        .unwrap();
+
+        let store = SqliteTradeStore::open_in_memory().unwrap();
+        store
+            .save_trade(&record("2026-10-11", "c1", 100, "win"))
+            .unwrap();
+        store
+            .save_trade(&record("2026-10-12", "c2", 200, "loss"))
+            .unwrap();
+        store
+            .save_trade(&record("2026-10-13", "c3", 300, "win"))
+            .unwrap();
+
+        let query = TradeHistoryQuery {
+            to: Some("2026-10-12".to_string()),
+            ..Default::default()
+        };
+        let rows = collect_rows_in(&dir, &query, &store);
+        let ids: Vec<&str> = rows.iter().map(|r| r.contract_id.as_str()).collect();
+        assert_eq!(ids, ["old", "c1", "c2"]);
+        // The store row wins, the day file adds its status code
+        assert_eq!((rows[1].lot_no, rows[1].buy_price), (3, 1.0));
+        assert_eq!(rows[1].status_code, "12");
+        // Without day files the store alone still fills the report
+        assert_eq!(rows[2].action, "LOSS ❌");
+        assert_eq!((rows[0].lot_no, rows[0].status_code.as_str()), (0, "7"));
+
+        // Reading never repairs or rewrites the day files
+        std::fs::write(dir.join("2026-10-11/trade.json"), "{ torn").unwrap();
+        assert_eq!(collect_rows_in(&dir, &query, &store).len(), 2);
+        let names: Vec<_> = std::fs::read_dir(dir.join("2026-10-11"))
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        assert_eq!(names, ["trade.json"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let mut query = TradeHistoryQuery {
+            from: Some("2026-1-5".to_string()),
+            to: Some(" 2026-10-18 ".to_string()),
+            ..Default::default()
+        };
+        let (from, to) = query.parse_dates().unwrap();
+        assert_eq!(from, NaiveDate::from_ymd_opt(2026, 1, 5));
+        assert_eq!(to, NaiveDate::from_ymd_opt(2026, 10, 18));
+        assert_eq!(query.from.as_deref(), Some("2026-01-05"));
+        assert_eq!(query.to.as_deref(), Some("2026-10-18"));
+
+        for bad in ["abc", "\n", "2026-13-01", ""] {
+            let mut query = TradeHistoryQuery {
+                to: Some(bad.to_string()),
+                ..Default::default()
+            };
+            assert!(query.parse_dates().is_err(), "{:?} accepted", bad);
+        }
+        assert_eq!(TradeHistoryQuery::default().parse_dates(), Ok((None, None)));
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let rows = sample_rows();
+        let bytes = to_csv(&rows).unwrap();
+        let mut reader = csv::Reader::from_reader(bytes.as_slice());
+        let headers: Vec<String> = reader
+            .headers()
+            .unwrap()
+            .iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(headers[..4], ["TradeDate", "LotNo", "No", "ContractID"]);
+        assert_eq!(headers.len(), 17);
+
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        let field = |row: usize, name: &str| {
+            let i = headers.iter().position(|h| h == name).unwrap();
+            records[row][i].to_string()
+        };
+        assert_eq!(field(0, "ContractID"), "c1");
+        assert_eq!(field(0, "StatusCode"), "12");
+        assert_eq!(field(0, "Profit").parse::<f64>().unwrap(), 0.95);
+        assert_eq!(field(1, "Action"), "LOSS ❌");
+        assert_eq!(field(1, "LotNo"), "3");
+        assert_eq!(
+            (field(0, "MartingaleStep"), field(1, "MartingaleStep")),
+            ("".into(), "1".into())
+        );
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        use arrow_array::Array;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let rows = sample_rows();
+        let path =
+            std::env::temp_dir().join(format!("trade_export_{}.parquet", std::process::id()));
+        std::fs::write(&path, to_parquet(&rows).unwrap()).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!((batch.num_rows(), batch.num_columns()), (2, 17));
+        let column = |name: &str| batch.column(batch.schema().index_of(name).unwrap()).clone();
+        let ids = column("ContractID");
+        let ids = ids.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((ids.value(0), ids.value(1)), ("c1", "c2"));
+        let lots = column("LotNo");
+        let lots = lots.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(lots.value(1), 3);
+        let steps = column("MartingaleStep");
+        let steps = steps.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!((steps.is_null(0), steps.value(1)), (true, 1));
+        let profit = column("Profit");
+        let profit = profit.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!((profit.value(0), profit.value(1)), (0.95, -1.0));
+        assert_eq!(profit.null_count(), 0);
+    }
+}
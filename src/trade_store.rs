@@ -0,0 +1,702 @@
+use crate::firestore_manager::{ScanRecord, TradeRecord};
+use crate::firestore_outbox::{FirestoreOutbox, OutboxEntry, OutboxRecord, OutboxStore};
+use crate::LotLog;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Local SQLite database, the source of truth for trades, lots and scans
+pub const TRADE_DB_FILE: &str = "data/trades.db";
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many ran.
+/// Never edit an entry once released; append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: trades, lots, scans
+    "CREATE TABLE trades (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        trade_date TEXT NOT NULL,
+        contract_id TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        trade_type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        status_code TEXT NOT NULL DEFAULT '',
+        lot_no INTEGER NOT NULL DEFAULT 0,
+        trade_no_in_lot INTEGER NOT NULL DEFAULT 0,
+        order_no INTEGER NOT NULL DEFAULT 0,
+        buy_price REAL NOT NULL,
+        payout REAL NOT NULL,
+        profit_loss REAL NOT NULL,
+        buy_time INTEGER NOT NULL DEFAULT 0,
+        expiry_time INTEGER NOT NULL DEFAULT 0,
+        time_remaining INTEGER NOT NULL DEFAULT 0,
+        min_profit REAL NOT NULL DEFAULT 0,
+        max_profit REAL NOT NULL DEFAULT 0,
+        entry_spot REAL NOT NULL DEFAULT 0,
+        exit_spot REAL NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL,
+        UNIQUE (trade_date, contract_id)
+    );
+    CREATE INDEX idx_trades_date ON trades (trade_date);
+    CREATE INDEX idx_trades_symbol ON trades (symbol, trade_date);
+    CREATE INDEX idx_trades_lot ON trades (trade_date, lot_no);
+    CREATE INDEX idx_trades_status_code ON trades (status_code);
+
+    CREATE TABLE lots (
+        trade_date TEXT NOT NULL,
+        lot_no INTEGER NOT NULL,
+        trade_count INTEGER NOT NULL,
+        balance REAL NOT NULL,
+        money_mode TEXT NOT NULL,
+        stopped INTEGER NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (trade_date, lot_no)
+    );
+
+    CREATE TABLE scans (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        scan_time TEXT NOT NULL,
+        timeframe TEXT NOT NULL,
+        period TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        price REAL NOT NULL,
+        ci REAL NOT NULL,
+        adx REAL NOT NULL,
+        score REAL NOT NULL,
+        is_bullish INTEGER NOT NULL,
+        recent_candles TEXT NOT NULL,
+        rank INTEGER NOT NULL
+    );
+    CREATE INDEX idx_scans_time ON scans (scan_time);
+    CREATE INDEX idx_scans_symbol ON scans (symbol, scan_time);",
+    // 2: Firestore outbox
+    "CREATE TABLE firestore_outbox (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        collection TEXT NOT NULL,
+        doc_id TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_ms INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        UNIQUE (collection, doc_id)
+    );
+    CREATE INDEX idx_outbox_due ON firestore_outbox (next_attempt_ms, seq);",
+    // 3: contracts bought but not settled yet
+    "CREATE TABLE open_contracts (
+        contract_id TEXT PRIMARY KEY,
+        bot TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        contract_type TEXT NOT NULL,
+        stake REAL NOT NULL,
+        lot_no INTEGER NOT NULL,
+        opened_at TEXT NOT NULL
+    );",
+    // 4: index into the martingale stake ladder, NULL in fixed-stake mode
+    "ALTER TABLE trades ADD COLUMN martingale_step INTEGER;",
+];
+
+/// Filter for `TradeStore::query_trades`. Every field is optional; dates are YYYY-MM-DD
+/// and inclusive. Also used as the query string of `GET /api/trades`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TradeFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub symbol: Option<String>,
+    pub lot_no: Option<u32>,
+    pub status_code: Option<String>,
+    /// "win" / "loss" / "sold"
+    pub status: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// A contract the bots bought and have not seen settle. Rows left over from a previous run
+/// (crash, or shutdown before the result arrived) are reported at startup.
+#[derive(Debug, Clone)]
+pub struct OpenContract {
+    pub contract_id: String,
+    /// single / auto
+    pub bot: String,
+    pub symbol: String,
+    pub contract_type: String,
+    pub stake: f64,
+    pub lot_no: u32,
+    pub opened_at: String,
+}
+
+/// Persistent storage for trading data
+pub trait TradeStore: Send + Sync {
+    /// Insert a trade, replacing an earlier row for the same date and contract
+    fn save_trade(&self, record: &TradeRecord) -> anyhow::Result<()>;
+    /// Insert or update the summary row of a lot
+    fn save_lot(&self, trade_date: &str, lot: &LotLog) -> anyhow::Result<()>;
+    fn save_scan(&self, record: &ScanRecord) -> anyhow::Result<()>;
+    /// Matching trades, newest first
+    fn query_trades(&self, filter: &TradeFilter) -> anyhow::Result<Vec<TradeRecord>>;
+    fn save_open_contract(&self, contract: &OpenContract) -> anyhow::Result<()>;
+    fn remove_open_contract(&self, contract_id: &str) -> anyhow::Result<()>;
+    /// Open contracts, oldest first
+    fn open_contracts(&self) -> anyhow::Result<Vec<OpenContract>>;
+}
+
+/// `TradeStore` on an embedded SQLite file
+pub struct SqliteTradeStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteTradeStore {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> anyhow::Result<Self> {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        migrate(&conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> anyhow::Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("trade store lock poisoned"))
+    }
+}
+
+fn migrate(conn: &Connection) -> anyhow::Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+        info!("🗄️ Trade store migrated to version {}", i + 1);
+    }
+    Ok(())
+}
+
+fn trade_from_row(row: &Row) -> rusqlite::Result<TradeRecord> {
+    Ok(TradeRecord {
+        order_no: row.get("order_no")?,
+        contract_id: row.get("contract_id")?,
+        symbol: row.get("symbol")?,
+        trade_type: row.get("trade_type")?,
+        buy_price: row.get("buy_price")?,
+        payout: row.get("payout")?,
+        profit_loss: row.get("profit_loss")?,
+        buy_time: row.get::<_, i64>("buy_time")? as u64,
+        expiry_time: row.get::<_, i64>("expiry_time")? as u64,
+        time_remaining: row.get("time_remaining")?,
+        min_profit: row.get("min_profit")?,
+        max_profit: row.get("max_profit")?,
+        status: row.get("status")?,
+        status_code: row.get("status_code")?,
+        martingale_step: row.get("martingale_step")?,
+        entry_spot: row.get("entry_spot")?,
+        exit_spot: row.get("exit_spot")?,
+        lot_no: row.get("lot_no")?,
+        trade_no_in_lot: row.get("trade_no_in_lot")?,
+        trade_date: row.get("trade_date")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+impl TradeStore for SqliteTradeStore {
+    fn save_trade(&self, r: &TradeRecord) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO trades (trade_date, contract_id, symbol, trade_type, status,
+                status_code, lot_no, trade_no_in_lot, order_no, buy_price, payout, profit_loss,
+                buy_time, expiry_time, time_remaining, min_profit, max_profit, entry_spot,
+                exit_spot, created_at, martingale_step)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                ?17, ?18, ?19, ?20, ?21)",
+            params![
+                r.trade_date,
+                r.contract_id,
+                r.symbol,
+                r.trade_type,
+                r.status,
+                r.status_code,
+                r.lot_no,
+                r.trade_no_in_lot,
+                r.order_no,
+                r.buy_price,
+                r.payout,
+                r.profit_loss,
+                r.buy_time as i64,
+                r.expiry_time as i64,
+                r.time_remaining,
+                r.min_profit,
+                r.max_profit,
+                r.entry_spot,
+                r.exit_spot,
+                r.created_at,
+                r.martingale_step,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn save_lot(&self, trade_date: &str, lot: &LotLog) -> anyhow::Result<()> {
+        let last = lot.trade_object_list.last();
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO lots (trade_date, lot_no, trade_count, balance, money_mode,
+                stopped, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                trade_date,
+                lot.lot_no,
+                lot.trade_object_list.len() as i64,
+                last.map(|t| t.balance_on_lot).unwrap_or(0.0),
+                last.map(|t| t.money_trade_type.as_str()).unwrap_or(""),
+                last.is_some_and(|t| t.is_stop_trade),
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn save_scan(&self, r: &ScanRecord) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT INTO scans (scan_time, timeframe, period, symbol, price, ci, adx, score,
+                is_bullish, recent_candles, rank)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                r.scan_time,
+                r.timeframe,
+                r.period,
+                r.symbol,
+                r.price,
+                r.ci,
+                r.adx,
+                r.score,
+                r.is_bullish,
+                r.recent_candles,
+                r.rank,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn query_trades(&self, filter: &TradeFilter) -> anyhow::Result<Vec<TradeRecord>> {
+        let mut clauses: Vec<&str> = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(from) = &filter.from {
+            clauses.push("trade_date >= ?");
+            values.push(from.clone().into());
+        }
+        if let Some(to) = &filter.to {
+            clauses.push("trade_date <= ?");
+            values.push(to.clone().into());
+        }
+        if let Some(symbol) = &filter.symbol {
+            clauses.push("symbol = ?");
+            values.push(symbol.clone().into());
+        }
+        if let Some(lot_no) = filter.lot_no {
+            clauses.push("lot_no = ?");
+            values.push(i64::from(lot_no).into());
+        }
+        if let Some(code) = &filter.status_code {
+            clauses.push("status_code = ?");
+            values.push(code.clone().into());
+        }
+        if let Some(status) = &filter.status {
+            clauses.push("status = ?");
+            values.push(status.to_lowercase().into());
+        }
+
+        let mut sql = String::from("SELECT * FROM trades");
+        if !clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&clauses.join(" AND "));
+        }
+        sql.push_str(" ORDER BY trade_date DESC, buy_time DESC, id DESC");
+        if let Some(limit) = filter.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), trade_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn save_open_contract(&self, c: &OpenContract) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO open_contracts (contract_id, bot, symbol, contract_type,
+                stake, lot_no, opened_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                c.contract_id,
+                c.bot,
+                c.symbol,
+                c.contract_type,
+                c.stake,
+                c.lot_no,
+                c.opened_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn remove_open_contract(&self, contract_id: &str) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "DELETE FROM open_contracts WHERE contract_id = ?1",
+            [contract_id],
+        )?;
+        Ok(())
+    }
+
+    fn open_contracts(&self) -> anyhow::Result<Vec<OpenContract>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT * FROM open_contracts ORDER BY opened_at")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(OpenContract {
+                contract_id: row.get("contract_id")?,
+                bot: row.get("bot")?,
+                symbol: row.get("symbol")?,
+                contract_type: row.get("contract_type")?,
+                stake: row.get("stake")?,
+                lot_no: row.get("lot_no")?,
+                opened_at: row.get("opened_at")?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+// Replacing a queued document gives it a new `seq`, so completing the old one after an
+// in-flight batch cannot delete the newer version.
+impl OutboxStore for SqliteTradeStore {
+    fn enqueue(&self, record: &OutboxRecord, max_depth: usize) -> anyhow::Result<bool> {
+        let (collection, doc_id) = (record.collection(), record.doc_id());
+        let conn = self.conn()?;
+        let others: usize = conn.query_row(
+            "SELECT COUNT(*) FROM firestore_outbox WHERE NOT (collection = ?1 AND doc_id = ?2)",
+            params![collection, doc_id],
+            |r| r.get(0),
+        )?;
+        if others >= max_depth {
+            return Ok(false);
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO firestore_outbox (collection, doc_id, payload)
+             VALUES (?1, ?2, ?3)",
+            params![collection, doc_id, serde_json::to_string(record)?],
+        )?;
+        Ok(true)
+    }
+
+    fn due(&self, now_ms: u64, limit: usize) -> anyhow::Result<Vec<OutboxEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, doc_id, payload, attempts FROM firestore_outbox
+             WHERE next_attempt_ms <= ?1 ORDER BY seq LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![now_ms as i64, limit as i64], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, u32>(3)?,
+            ))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (seq, doc_id, payload, attempts) = row?;
+            match serde_json::from_str(&payload) {
+                Ok(record) => entries.push(OutboxEntry {
+                    seq,
+                    record,
+                    attempts,
+                }),
+                Err(e) => {
+                    error!("❌ Dropping unreadable outbox entry {}: {}", doc_id, e);
+                    conn.execute("DELETE FROM firestore_outbox WHERE seq = ?1", [seq])?;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn complete(&self, seqs: &[i64]) -> anyhow::Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        for seq in seqs {
+            tx.execute("DELETE FROM firestore_outbox WHERE seq = ?1", [seq])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn reschedule(&self, seq: i64, next_attempt_ms: u64, error: &str) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "UPDATE firestore_outbox SET attempts = attempts + 1, next_attempt_ms = ?2,
+                last_error = ?3
+             WHERE seq = ?1",
+            params![seq, next_attempt_ms as i64, error],
+        )?;
+        Ok(())
+    }
+
+    fn depth(&self) -> anyhow::Result<usize> {
+        Ok(self
+            .conn()?
+            .query_row("SELECT COUNT(*) FROM firestore_outbox", [], |r| r.get(0))?)
+    }
+}
+
+/// Which writers run besides the local store. Read from `TRADE_SINK_JSON` and
+/// `TRADE_SINK_FIRESTORE` in `.env` ("false" disables, both default to on).
+#[derive(Debug, Clone, Copy)]
+pub struct TradeSinks {
+    /// `logs/<date>/lot_N.json` and `tradeHistory/<date>/trade.json`
+    pub json_logs: bool,
+    pub firestore: bool,
+}
+
+impl TradeSinks {
+    pub fn from_env() -> Self {
+        let enabled = |key: &str| {
+            std::env::var(key)
+                .map(|v| !v.eq_ignore_ascii_case("false"))
+                .unwrap_or(true)
+        };
+        Self {
+            json_logs: enabled("TRADE_SINK_JSON"),
+            firestore: enabled("TRADE_SINK_FIRESTORE"),
+        }
+    }
+}
+
+/// Single entry point for recording trading data: always writes the local store,
+/// then the enabled sinks. Firestore writes go through the outbox and never block.
+/// SQLite and file writes run on the blocking pool, so the bot loops calling these
+/// never hold a runtime thread on the connection mutex or on disk.
+pub struct TradeLedger {
+    pub store: Arc<dyn TradeStore>,
+    pub outbox: Arc<FirestoreOutbox>,
+    pub sinks: TradeSinks,
+}
+
+impl TradeLedger {
+    /// Run a store call on the blocking pool
+    pub async fn with_store<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn TradeStore) -> anyhow::Result<T> + Send + 'static,
+    {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || f(store.as_ref())).await?
+    }
+
+    pub async fn record_trade(&self, record: &TradeRecord) {
+        let record = record.clone();
+        let outbox = self.sinks.firestore.then(|| self.outbox.clone());
+        let saved = self
+            .with_store(move |store| {
+                let saved = store.save_trade(&record);
+                if let Some(outbox) = outbox {
+                    outbox.enqueue(OutboxRecord::Trade(record));
+                }
+                saved
+            })
+            .await;
+        if let Err(e) = saved {
+            error!("❌ Trade store save error: {}", e);
+        }
+    }
+
+    /// Store the lot summary and, when enabled, the `lot_N.json` file.
+    pub async fn record_lot(&self, lot: &LotLog) {
+        let lot = lot.clone();
+        let json_logs = self.sinks.json_logs;
+        let saved = self
+            .with_store(move |store| {
+                let folder_name = crate::get_daily_folder_name();
+                let saved = store.save_lot(&folder_name, &lot);
+                if json_logs {
+                    crate::save_lot_log(&crate::ensure_daily_folder(&folder_name), &lot);
+                }
+                saved
+            })
+            .await;
+        if let Err(e) = saved {
+            error!("❌ Trade store lot save error: {}", e);
+        }
+    }
+
+    /// Remember a bought contract until its result is recorded
+    pub async fn contract_opened(&self, contract: &OpenContract) {
+        let contract = contract.clone();
+        if let Err(e) = self
+            .with_store(move |store| store.save_open_contract(&contract))
+            .await
+        {
+            error!("❌ Trade store open contract save error: {}", e);
+        }
+    }
+
+    pub async fn contract_settled(&self, contract_id: &str) {
+        let contract_id = contract_id.to_string();
+        if let Err(e) = self
+            .with_store(move |store| store.remove_open_contract(&contract_id))
+            .await
+        {
+            error!("❌ Trade store open contract remove error: {}", e);
+        }
+    }
+
+    /// Store a scan row; queue it for Firestore when both the sink and `to_firestore` allow it.
+    pub async fn record_scan(&self, record: &ScanRecord, to_firestore: bool) -> anyhow::Result<()> {
+        let record = record.clone();
+        let outbox = (self.sinks.firestore && to_firestore).then(|| self.outbox.clone());
+        self.with_store(move |store| {
+            store.save_scan(&record)?;
+            if let Some(outbox) = outbox {
+                outbox.enqueue(OutboxRecord::Scan(record));
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(date: &str, contract_id: &str, symbol: &str, status: &str) -> TradeRecord {
+        TradeRecord {
+            contract_id: contract_id.to_string(),
+            symbol: symbol.to_string(),
+            trade_type: "CALL".to_string(),
+            status: status.to_string(),
+            buy_price: 1.0,
+            trade_date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn user_version(store: &SqliteTradeStore) -> usize {
+        store
+            .conn()
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrations_run_once_and_upgrade_old_databases() {
+        let store = SqliteTradeStore::open_in_memory().unwrap();
+        assert_eq!(user_version(&store), MIGRATIONS.len());
+        migrate(&store.conn().unwrap()).unwrap();
+        assert_eq!(user_version(&store), MIGRATIONS.len());
+
+        // A database created by the first release keeps its rows and gains the later tables
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO trades (trade_date, contract_id, symbol, trade_type, status, buy_price,
+                payout, profit_loss, created_at)
+             VALUES ('2026-10-01', 'c1', 'R_10', 'CALL', 'win', 1, 1.95, 0.95, '')",
+            [],
+        )
+        .unwrap();
+        let store = SqliteTradeStore::with_connection(conn).unwrap();
+        assert_eq!(user_version(&store), MIGRATIONS.len());
+        let trades = store.query_trades(&TradeFilter::default()).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].martingale_step, None);
+        assert!(store.open_contracts().unwrap().is_empty());
+        assert_eq!(store.depth().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_query_trades_filters() {
+        let store = SqliteTradeStore::open_in_memory().unwrap();
+        let mut rows = vec![
+            trade("2026-10-10", "c1", "R_10", "win"),
+            trade("2026-10-11", "c2", "R_10", "loss"),
+            trade("2026-10-11", "c3", "R_75", "win"),
+            trade("2026-10-12", "c4", "R_75", "loss"),
+        ];
+        rows[1].lot_no = 2;
+        rows[1].status_code = "12".to_string();
+        rows[1].martingale_step = Some(3);
+        rows[2].buy_time = 100;
+        rows[1].buy_time = 50;
+        for r in &rows {
+            store.save_trade(r).unwrap();
+        }
+        let ids = |filter: TradeFilter| -> Vec<String> {
+            store
+                .query_trades(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.contract_id)
+                .collect()
+        };
+
+        // Newest day first, then latest buy within the day
+        assert_eq!(ids(TradeFilter::default()), ["c4", "c3", "c2", "c1"]);
+        assert_eq!(
+            ids(TradeFilter {
+                from: Some("2026-10-11".into()),
+                to: Some("2026-10-11".into()),
+                ..Default::default()
+            }),
+            ["c3", "c2"]
+        );
+        assert_eq!(
+            ids(TradeFilter {
+                symbol: Some("R_75".into()),
+                status: Some("WIN".into()),
+                ..Default::default()
+            }),
+            ["c3"]
+        );
+        assert_eq!(
+            ids(TradeFilter {
+                lot_no: Some(2),
+                status_code: Some("12".into()),
+                ..Default::default()
+            }),
+            ["c2"]
+        );
+        let c2 = &store
+            .query_trades(&TradeFilter {
+                lot_no: Some(2),
+                ..Default::default()
+            })
+            .unwrap()[0];
+        assert_eq!(c2.martingale_step, Some(3));
+        assert_eq!(
+            ids(TradeFilter {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            ["c4"]
+        );
+
+        // Same date and contract replaces the row
+        let mut settled = rows[0].clone();
+        settled.status = "loss".to_string();
+        store.save_trade(&settled).unwrap();
+        let losses = ids(TradeFilter {
+            status: Some("loss".into()),
+            ..Default::default()
+        });
+        assert_eq!(losses, ["c4", "c2", "c1"]);
+    }
+}
@@ -0,0 +1,550 @@
+use crate::auth::Role;
+use crate::file_store::DirLock;
+use crate::secrets::{DerivAccount, SecretStore};
+use anyhow::{anyhow, bail, Context};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::error;
+
+// User accounts for the relay login, kept in `data/users.json`.
+//
+// Passwords are stored as argon2id PHC strings; Deriv API tokens live encrypted in the secret
+// store (see `secrets`). The file is re-read on every lookup, so accounts changed with the
+// `users` CLI apply to a running relay without a restart. Only a users file that does not
+// exist means "no accounts"; one that cannot be read or parsed refuses every login.
+
+pub const USERS_FILE: &str = "data/users.json";
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub username: String,
+    pub password_hash: String,
+    pub role: Role,
+    /// Plaintext token from before the secret store; moved there by `migrate_legacy_tokens`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    deriv_token: String,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsersFile {
+    users: Vec<UserAccount>,
+}
+
+/// Account details safe to show (no hash, no token)
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSummary {
+    pub username: String,
+    pub role: Role,
+    pub created_at: String,
+}
+
+impl From<&UserAccount> for UserSummary {
+    fn from(user: &UserAccount) -> Self {
+        Self {
+            username: user.username.clone(),
+            role: user.role,
+            created_at: user.created_at.clone(),
+        }
+    }
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| anyhow!("password hashing failed: {}", e))
+}
+
+/// Hash checked for unknown usernames, so a login takes as long whether the user exists or not
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("not-a-real-password").unwrap_or_default())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+fn validate_username(username: &str) -> anyhow::Result<()> {
+    let valid = !username.is_empty()
+        && username.len() <= 64
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        bail!("username may only contain letters, digits, '_', '-' and '.' (max 64)");
+    }
+    Ok(())
+}
+
+fn validate_password(password: &str) -> anyhow::Result<()> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        bail!("password must be at least {} characters", MIN_PASSWORD_LEN);
+    }
+    Ok(())
+}
+
+pub struct UserStore {
+    path: PathBuf,
+}
+
+impl UserStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read(&self, held: Option<&DirLock>) -> anyhow::Result<UsersFile> {
+        let file = crate::file_store::read_existing(
+            &self.path,
+            |s| serde_json::from_str::<UsersFile>(s),
+            held,
+        )?;
+        Ok(file.unwrap_or_default())
+    }
+
+    fn load(&self) -> anyhow::Result<UsersFile> {
+        self.read(None)
+    }
+
+    /// Read-modify-write under the directory lock, so concurrent edits (CLI, startup
+    /// migration) are not lost
+    fn update<T>(&self, f: impl FnOnce(&mut UsersFile) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let lock = crate::file_store::lock_parent(&self.path)
+            .with_context(|| format!("locking {:?}", self.path))?;
+        let mut file = self.read(Some(&lock))?;
+        let result = f(&mut file)?;
+        let json = serde_json::to_string_pretty(&file)?;
+        crate::file_store::write_atomic_locked(&lock, &self.path, json)
+            .with_context(|| format!("writing {:?}", self.path))?;
+        restrict_permissions(&self.path);
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.load()?.users.is_empty())
+    }
+
+    pub fn list(&self) -> anyhow::Result<Vec<UserSummary>> {
+        Ok(self.load()?.users.iter().map(UserSummary::from).collect())
+    }
+
+    pub fn get(&self, username: &str) -> anyhow::Result<Option<UserAccount>> {
+        Ok(self
+            .load()?
+            .users
+            .into_iter()
+            .find(|u| u.username == username))
+    }
+
+    /// Check a login. While no accounts exist, `APP_USER`/`APP_PASSWORD` from `.env` are
+    /// accepted (role from `APP_ROLE`, default admin) so the first admin can sign in.
+    pub fn authenticate(&self, username: &str, password: &str) -> Option<(String, Role)> {
+        let file = match self.load() {
+            Ok(file) => file,
+            Err(e) => {
+                error!("❌ Login refused: {:#}", e);
+                return None;
+            }
+        };
+        if !file.users.is_empty() {
+            return match file.users.into_iter().find(|u| u.username == username) {
+                Some(user) => verify_password(password, &user.password_hash)
+                    .then_some((user.username, user.role)),
+                None => {
+                    verify_password(password, dummy_hash());
+                    None
+                }
+            };
+        }
+        let (env_user, env_pass, role) = env_account()?;
+        if env_pass.is_empty() || username != env_user || password != env_pass {
+            return None;
+        }
+        Some((env_user, role))
+    }
+
+    /// Current role of a logged-in user; `None` once the account is gone. Sessions are
+    /// checked against this so removing a user or changing a role applies to them too.
+    pub fn role_of(&self, username: &str) -> anyhow::Result<Option<Role>> {
+        let file = self.load()?;
+        if !file.users.is_empty() {
+            return Ok(file
+                .users
+                .iter()
+                .find(|u| u.username == username)
+                .map(|u| u.role));
+        }
+        Ok(env_account()
+            .filter(|(env_user, _, _)| env_user == username)
+            .map(|(_, _, role)| role))
+    }
+
+    pub fn add(&self, username: &str, password: &str, role: Role) -> anyhow::Result<()> {
+        validate_username(username)?;
+        validate_password(password)?;
+        let password_hash = hash_password(password)?;
+        self.update(|file| {
+            if file.users.iter().any(|u| u.username == username) {
+                bail!("user {} already exists", username);
+            }
+            file.users.push(UserAccount {
+                username: username.to_string(),
+                password_hash,
+                role,
+                deriv_token: String::new(),
+                created_at: chrono::Local::now().to_rfc3339(),
+            });
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, username: &str) -> anyhow::Result<()> {
+        self.update(|file| {
+            let before = file.users.len();
+            file.users.retain(|u| u.username != username);
+            if file.users.len() == before {
+                bail!("no user {}", username);
+            }
+            Ok(())
+        })
+    }
+
+    fn modify(&self, username: &str, f: impl FnOnce(&mut UserAccount)) -> anyhow::Result<()> {
+        self.update(|file| {
+            let user = file
+                .users
+                .iter_mut()
+                .find(|u| u.username == username)
+                .ok_or_else(|| anyhow!("no user {}", username))?;
+            f(user);
+            Ok(())
+        })
+    }
+
+    pub fn set_password(&self, username: &str, password: &str) -> anyhow::Result<()> {
+        validate_password(password)?;
+        let hash = hash_password(password)?;
+        self.modify(username, |u| u.password_hash = hash)
+    }
+
+    pub fn set_role(&self, username: &str, role: Role) -> anyhow::Result<()> {
+        self.modify(username, |u| u.role = role)
+    }
+
+    /// Hand plaintext tokens left by older versions to `store` and drop those it accepted.
+    /// Returns the users whose token moved.
+    pub fn migrate_legacy_tokens(
+        &self,
+        store: impl Fn(&str, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Vec<String>> {
+        let legacy: Vec<(String, String)> = self
+            .load()?
+            .users
+            .into_iter()
+            .filter(|u| !u.deriv_token.is_empty())
+            .map(|u| (u.username, u.deriv_token))
+            .collect();
+        let mut moved = Vec::new();
+        for (username, token) in legacy {
+            match store(&username, &token) {
+                Ok(()) => moved.push((username, token)),
+                Err(e) => error!("❌ Could not move {}'s token: {}", username, e),
+            }
+        }
+        if moved.is_empty() {
+            return Ok(Vec::new());
+        }
+        // `store` writes next to the users file, so the lock is only taken afterwards; a
+        // token changed in the meantime is left alone
+        self.update(|file| {
+            for user in file.users.iter_mut() {
+                if moved
+                    .iter()
+                    .any(|(name, token)| *name == user.username && *token == user.deriv_token)
+                {
+                    user.deriv_token.clear();
+                }
+            }
+            Ok(())
+        })?;
+        Ok(moved.into_iter().map(|(username, _)| username).collect())
+    }
+}
+
+/// `APP_USER`, `APP_PASSWORD` and the role from `APP_ROLE` (default admin)
+fn env_account() -> Option<(String, String, Role)> {
+    let user = std::env::var("APP_USER").ok()?;
+    let password = std::env::var("APP_PASSWORD").ok()?;
+    let role = std::env::var("APP_ROLE")
+        .ok()
+        .and_then(|r| Role::parse(&r))
+        .unwrap_or(Role::Admin);
+    Some((user, password, role))
+}
+
+/// The users file holds password hashes and API tokens; keep it owner-only
+fn restrict_permissions(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+// ==================== ADMIN CLI ====================
+
+const CLI_USAGE: &str = "\
+usage: rust-deriv-relay users <command>
+  list                      show accounts
+  add <username> [role]     create an account (role: viewer|trader|admin, default trader)
+  remove <username>         delete an account
+  passwd <username>         change a password
+  role <username> <role>    change a role
+  token <username> <demo|real>
+                            set the Deriv API token (empty input removes it);
+                            needs RELAY_SECRET_KEY";
+
+fn prompt_secret(prompt: &str) -> anyhow::Result<String> {
+    // Falls back to a plain stdin line when there is no terminal (e.g. piped input)
+    rpassword::prompt_password(prompt)
+        .or_else(|_| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .map(|s| s.trim_end_matches(['\r', '\n']).to_string())
+        .context("reading input")
+}
+
+fn prompt_new_password() -> anyhow::Result<String> {
+    let password = prompt_secret("New password: ")?;
+    validate_password(&password)?;
+    if prompt_secret("Repeat password: ")? != password {
+        bail!("passwords do not match");
+    }
+    Ok(password)
+}
+
+fn parse_role(s: &str) -> anyhow::Result<Role> {
+    Role::parse(s).ok_or_else(|| anyhow!("unknown role {} (viewer|trader|admin)", s))
+}
+
+/// `rust-deriv-relay users ...`
+pub fn run_cli(args: &[String]) -> anyhow::Result<()> {
+    let store = UserStore::new(USERS_FILE);
+    let secrets = SecretStore::from_env()?;
+    let arg = |i: usize| {
+        args.get(i)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("{}", CLI_USAGE))
+    };
+
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            let users = store.list()?;
+            if users.is_empty() {
+                println!("No users in {}", USERS_FILE);
+            }
+            for u in users {
+                let tokens = match &secrets {
+                    Some(secrets) => {
+                        let accounts: Vec<&str> = secrets
+                            .status(&u.username)
+                            .iter()
+                            .map(|t| t.account.as_str())
+                            .collect();
+                        if accounts.is_empty() {
+                            "none".to_string()
+                        } else {
+                            accounts.join(",")
+                        }
+                    }
+                    None => "?".to_string(),
+                };
+                println!(
+                    "{:<24} {:<8} tokens={} created={}",
+                    u.username,
+                    u.role.as_str(),
+                    tokens,
+                    u.created_at
+                );
+            }
+        }
+        Some("add") => {
+            let username = arg(1)?;
+            let role = args
+                .get(2)
+                .map(|r| parse_role(r))
+                .transpose()?
+                .unwrap_or(Role::Trader);
+            validate_username(username)?;
+            let password = prompt_new_password()?;
+            store.add(username, &password, role)?;
+            println!("✅ Added {} ({})", username, role.as_str());
+        }
+        Some("remove") => {
+            let username = arg(1)?;
+            store.remove(username)?;
+            if let Some(secrets) = &secrets {
+                for account in [DerivAccount::Demo, DerivAccount::Real] {
+                    secrets.remove_token(username, account)?;
+                }
+            }
+            println!("✅ Removed {}", username);
+        }
+        Some("passwd") => {
+            let username = arg(1)?;
+            if store.get(username)?.is_none() {
+                bail!("no user {}", username);
+            }
+            let password = prompt_new_password()?;
+            store.set_password(username, &password)?;
+            println!("✅ Password changed for {}", username);
+        }
+        Some("role") => {
+            let username = arg(1)?;
+            let role = parse_role(arg(2)?)?;
+            store.set_role(username, role)?;
+            println!("✅ {} is now {}", username, role.as_str());
+        }
+        Some("token") => {
+            let username = arg(1)?;
+            let account = DerivAccount::parse(arg(2)?)
+                .ok_or_else(|| anyhow!("account must be demo or real"))?;
+            let secrets = secrets.ok_or_else(|| anyhow!("RELAY_SECRET_KEY is not set"))?;
+            if store.get(username)?.is_none() {
+                bail!("no user {}", username);
+            }
+            let token = prompt_secret("Deriv API token: ")?;
+            if token.trim().is_empty() {
+                secrets.remove_token(username, account)?;
+                println!("✅ {} token removed for {}", account.as_str(), username);
+            } else {
+                secrets.set_token(username, account, &token)?;
+                println!("✅ {} token set for {}", account.as_str(), username);
+            }
+        }
+        _ => bail!("{}", CLI_USAGE),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> (PathBuf, UserStore) {
+        let dir = std::env::temp_dir().join(format!("users_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = UserStore::new(dir.join("users.json"));
+        (dir, store)
+    }
+
+    #[test]
+    fn test_missing_file_means_no_accounts() {
+        let (dir, store) = temp_store("missing");
+        assert!(store.is_empty().unwrap());
+        assert!(store.list().unwrap().is_empty());
+        assert!(store.get("alice").unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_file_refuses_logins_and_is_not_overwritten() {
+        let (dir, store) = temp_store("corrupt");
+        let path = dir.join("users.json");
+        std::fs::write(&path, "{ torn").unwrap();
+
+        assert!(store.is_empty().is_err());
+        assert!(store.role_of("admin").is_err());
+        assert_eq!(store.authenticate("admin", "whatever"), None);
+        assert!(store.add("bob", "password123", Role::Admin).is_err());
+        assert!(store.migrate_legacy_tokens(|_, _| Ok(())).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ torn");
+
+        // A usable backup is still recovered
+        std::fs::write(dir.join("users.json.bak"), r#"{"users": []}"#).unwrap();
+        assert!(store.is_empty().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let (dir, store) = temp_store("concurrent");
+        let store = std::sync::Arc::new(store);
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    store
+                        .add(&format!("user{}", i), "password123", Role::Viewer)
+                        .unwrap()
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(store.list().unwrap().len(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy_tokens() {
+        let (dir, store) = temp_store("legacy");
+        let account = |name: &str, token: &str| {
+            serde_json::json!({
+                "username": name, "password_hash": "x", "role": "trader", "deriv_token": token
+            })
+        };
+        let users = serde_json::json!({
+            "users": [account("alice", "alice-token"), account("bob", "bob-token"), account("carol", "")]
+        });
+        std::fs::write(dir.join("users.json"), users.to_string()).unwrap();
+
+        let moved = store
+            .migrate_legacy_tokens(|username, _| match username {
+                "bob" => Err(anyhow!("store failed")),
+                _ => Ok(()),
+            })
+            .unwrap();
+        assert_eq!(moved, ["alice"]);
+        let file = store.load().unwrap();
+        let tokens: Vec<&str> = file.users.iter().map(|u| u.deriv_token.as_str()).collect();
+        assert_eq!(tokens, ["", "bob-token", ""]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_role_of_follows_the_account() {
+        let (dir, store) = temp_store("roles");
+        store.add("alice", "password123", Role::Trader).unwrap();
+        assert_eq!(
+            store.authenticate("alice", "password123"),
+            Some(("alice".to_string(), Role::Trader))
+        );
+        assert_eq!(store.authenticate("alice", "wrong-password"), None);
+        assert_eq!(store.role_of("alice").unwrap(), Some(Role::Trader));
+        assert_eq!(store.role_of("mallory").unwrap(), None);
+
+        store.set_role("alice", Role::Viewer).unwrap();
+        assert_eq!(store.role_of("alice").unwrap(), Some(Role::Viewer));
+        store.remove("alice").unwrap();
+        assert_eq!(store.role_of("alice").unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}